@@ -0,0 +1,170 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+// times walking generated trees that are deep and that are wide, and the
+// command printing them in full against only counting them with --count, and
+// counts the allocations made while rendering them; run it with and without
+// default features to compare reading directories with openat and statx
+// against std:
+// cargo bench --bench traversal [--no-default-features]
+
+use std::alloc;
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+use std::process;
+use std::sync::atomic;
+use std::time;
+use std::vec;
+
+// how many times each tree is walked, the fastest of them is what's reported
+const RUNS: usize = 10;
+
+// the system allocator, counting how many times it's asked for memory
+struct Counting;
+
+static ALLOCATIONS: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+unsafe impl alloc::GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: alloc::Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, atomic::Ordering::Relaxed);
+        alloc::System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: alloc::Layout) {
+        alloc::System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: alloc::Layout,
+                      size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, atomic::Ordering::Relaxed);
+        alloc::System.realloc(ptr, layout, size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// counts what a walk goes through, so that the walk can't be optimized away
+struct Counter {
+    entries: u64,
+}
+
+impl tree::Visitor for Counter {
+    fn enter_dir(&mut self, _dir: &tree::EntryInfo, _depth: usize)
+        -> tree::WalkAction {
+        self.entries += 1;
+        tree::WalkAction::Continue
+    }
+
+    fn file(&mut self, _file: &tree::EntryInfo, _depth: usize)
+        -> tree::WalkAction {
+        self.entries += 1;
+        tree::WalkAction::Continue
+    }
+}
+
+// the fastest of RUNS runs of the command on the tree at root with args, with
+// what it prints thrown away
+fn command(root: &path::Path, args: &[&str]) -> time::Duration {
+    let mut times = vec::Vec::new();
+    for _ in 0..RUNS {
+        let start = time::Instant::now();
+        let status = process::Command::new(env!("CARGO_BIN_EXE_tree"))
+            .args(args)
+            .arg(root)
+            .stdout(process::Stdio::null())
+            .status()
+            .unwrap();
+        times.push(start.elapsed());
+        assert!(status.success());
+    }
+    times.into_iter().min().unwrap()
+}
+
+// how many allocations rendering the tree at root takes, the scan of it into
+// memory left out, so that it's only the printing of each line being counted
+fn allocations(root: &path::Path, options: &tree::TreeOptions) -> u64 {
+    let scanned = tree::scan(root, options).unwrap();
+    let before = ALLOCATIONS.load(atomic::Ordering::Relaxed);
+    tree::render_to(&scanned, options, io::sink()).unwrap();
+    ALLOCATIONS.load(atomic::Ordering::Relaxed) - before
+}
+
+// a chain of depth directories with files files in each
+fn deep(root: &path::Path, depth: usize, files: usize) {
+    let mut dir = root.to_path_buf();
+    for _ in 0..depth {
+        for file in 0..files {
+            fs::write(dir.join(format!("f{}", file)), "").unwrap();
+        }
+        dir.push("d");
+        fs::create_dir(&dir).unwrap();
+    }
+}
+
+// dirs directories beneath the root with files files in each
+fn wide(root: &path::Path, dirs: usize, files: usize) {
+    for dir in 0..dirs {
+        let dir = root.join(format!("d{}", dir));
+        fs::create_dir(&dir).unwrap();
+        for file in 0..files {
+            fs::write(dir.join(format!("f{}", file)), "").unwrap();
+        }
+    }
+}
+
+fn bench(name: &str, generate: impl FnOnce(&path::Path)) {
+    let root = env::temp_dir()
+        .join(format!("tree-bench-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    generate(&root);
+
+    let options = tree::TreeOptions::new().files(true);
+    let mut times = vec::Vec::new();
+    let mut entries = 0;
+    for _ in 0..RUNS {
+        let mut counter = Counter { entries: 0 };
+        let start = time::Instant::now();
+        tree::walk(&root, &options, &mut counter).unwrap();
+        times.push(start.elapsed());
+        entries = counter.entries;
+    }
+    let fastest = times.iter().min().unwrap();
+    println!("{:<6} {:>7} entries {:>10.2?}", name, entries, fastest);
+    let allocations = allocations(&root, &options);
+    println!("{:<22} {:>10} ({:.2} an entry)", "  render allocations",
+             allocations, allocations as f64 / entries as f64);
+    println!("{:<22} {:>10.2?}", "  tree -f", command(&root, &["-f"]));
+    println!("{:<22} {:>10.2?}", "  tree -f --count",
+             command(&root, &["-f", "--count"]));
+    fs::remove_dir_all(root).unwrap();
+}
+
+fn main() {
+    bench("deep", |root| deep(root, 1000, 20));
+    bench("wide", |root| wide(root, 200, 100));
+}
@@ -0,0 +1,498 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+// the library's way into what the command does, without any of the args
+// parsing or printing to stdout
+
+use std::ffi;
+use std::fs;
+use std::io;
+use std::path;
+#[cfg(feature = "async")]
+use std::sync;
+use std::time;
+use std::vec;
+
+use crate::ignores;
+use crate::snapshot;
+use crate::walk::{self, Stats, WalkAction};
+use crate::{Decision, Entry, Kind, Options, Source, TraversalError, TreeError};
+
+/// A tree held in memory, as scan returns it and render draws it, along
+/// with the size and mtime of each entry. Every entry takes around 150
+/// bytes plus it's name, so a tree of millions of entries takes hundreds of
+/// megabytes.
+pub type TreeNode = snapshot::Node;
+
+/// How a tree is scanned and rendered. It starts out as the tree command
+/// without any args: files are left out, names starting with a . are listed,
+/// and the connectors are drawn with extended characters.
+pub struct TreeOptions {
+    pub(crate) options: Options,
+}
+
+impl TreeOptions {
+    pub fn new() -> TreeOptions {
+        TreeOptions { options: Options::new() }
+    }
+
+    /// Wether files are listed along with directories, like -f.
+    pub fn files(mut self, files: bool) -> TreeOptions {
+        self.options.show_files = files;
+        self
+    }
+
+    /// Descends at most depth directories deep, like -L.
+    pub fn max_depth(mut self, depth: usize) -> TreeOptions {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    /// Wether the connectors are drawn with ASCII, like --ascii.
+    pub fn ascii(mut self, ascii: bool) -> TreeOptions {
+        let (connectors, ellipsis) = match ascii {
+            true => (["\\---", "+---", "    ", "|   "], "..."),
+            false => (["└───", "├───", "    ", "│   "], "…"),
+        };
+        self.options.format_str = connectors.map(String::from).to_vec();
+        self.options.ellipsis = ellipsis;
+        self.options.ascii = ascii;
+        self
+    }
+
+    /// Wether names starting with a . are listed.
+    pub fn dotfiles(mut self, dotfiles: bool) -> TreeOptions {
+        self.options.filter.dotfiles = dotfiles;
+        self
+    }
+
+    /// Wether directories without anything listed beneath them are left
+    /// out, like --prune.
+    pub fn prune(mut self, prune: bool) -> TreeOptions {
+        self.options.prune = prune;
+        self
+    }
+
+    /// Decides which files are listed before the rest of the filter does, a
+    /// file the hook accepts is listed regardless of the rest and one it
+    /// rejects isn't; directories are always listed.
+    pub fn shows(mut self,
+                 hook: impl Fn(&EntryInfo) -> Decision + Send + Sync + 'static)
+        -> TreeOptions {
+        self.options.filter.shows = Some(Box::new(move |entry, _| {
+            hook(&EntryInfo { entry })
+        }));
+        self
+    }
+
+    /// Decides which directories are descended into, the ones the hook
+    /// rejects are listed with the reason it gives after their name.
+    pub fn descends(mut self,
+                    hook: impl Fn(&EntryInfo) -> Decision + Send + Sync
+                        + 'static)
+        -> TreeOptions {
+        self.options.descends = Some(Box::new(move |entry, _| {
+            hook(&EntryInfo { entry })
+        }));
+        self
+    }
+
+    /// Wether a directory that can't be read is an error, rather than being
+    /// listed without it's contents, like --strict.
+    pub fn strict(mut self, strict: bool) -> TreeOptions {
+        self.options.filter.strict = strict;
+        self
+    }
+}
+
+impl Default for TreeOptions {
+    fn default() -> TreeOptions {
+        TreeOptions::new()
+    }
+}
+
+/// Scans the tree beneath root into memory, with the options' filter and
+/// depth limit applied. The root is named after the last component of it's
+/// canonical path, like it is on the command line.
+pub fn scan(root: &path::Path, options: &TreeOptions)
+    -> Result<TreeNode, TreeError> {
+    let (path, name) = resolve(root)?;
+    let rules = ignores::Rules::none();
+    Ok(snapshot::build(&name, &Source::Path(path), &rules, &options.options)?)
+}
+
+/// Like scan, but on one of tokio's blocking threads so that the runtime
+/// isn't held up while the tree is read; a panic in a hook of the options is
+/// resumed here.
+#[cfg(feature = "async")]
+pub async fn scan_async(root: &path::Path, options: sync::Arc<TreeOptions>)
+    -> Result<TreeNode, TreeError> {
+    let root = root.to_path_buf();
+    match tokio::task::spawn_blocking(move || scan(&root, &options)).await {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => {
+            std::panic::resume_unwind(error.into_panic())
+        },
+        // the only other way for the task to end is the runtime shutting
+        // down before it ran
+        Err(_) => Err(TreeError::Interrupted),
+    }
+}
+
+/// Renders a tree the way the command prints it, the options' filter and
+/// depth limit apply on top of the ones it was scanned with.
+pub fn render(root: &TreeNode, options: &TreeOptions) -> String {
+    let mut rendered = vec::Vec::new();
+    // nothing is read to list a tree that's in memory and writing to a vec
+    // can't fail, so neither can this
+    let _ = render_to(root, options, &mut rendered);
+    String::from_utf8_lossy(&rendered).into_owned()
+}
+
+/// Like render, but writes the tree to out as it goes instead of holding all
+/// of it.
+pub fn render_to<W: io::Write>(root: &TreeNode, options: &TreeOptions,
+                               out: W) -> Result<(), TreeError> {
+    crate::try_print_tree(&root.name, &Source::Node(root),
+                          &ignores::Rules::none(), &options.options,
+                          crate::Output::new(out))
+}
+
+/// An entry of a tree as hooks and visitors see it, what listing it already
+/// read is at hand and the rest of it's metadata is read on demand.
+pub struct EntryInfo<'e> {
+    pub(crate) entry: &'e Entry<'e>,
+}
+
+impl EntryInfo<'_> {
+    pub fn name(&self) -> &ffi::OsStr {
+        &self.entry.name
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.entry.kind
+    }
+
+    pub fn size(&self) -> Option<u64> {
+        self.entry.meta().size
+    }
+
+    pub fn modified(&self) -> Option<time::SystemTime> {
+        self.entry.meta().mtime
+    }
+
+    /// Where the entry is on disk, the entries of a tree in memory aren't
+    /// anywhere.
+    pub fn path(&self) -> Option<path::PathBuf> {
+        self.entry.path()
+    }
+
+    /// Reads the metadata of the entry, of a symlink itself rather than of
+    /// what it points to.
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        match self.path() {
+            Some(path) => fs::symlink_metadata(path),
+            None => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+}
+
+/// Receives the entries of a tree from walk, in the order they're rendered
+/// in, with the contents of each directory between it's enter_dir and
+/// leave_dir. Depth is 1 for the entries of the root.
+pub trait Visitor {
+    /// Called for every directory that's listed, SkipChildren keeps the
+    /// walk out of it and Stop ends the walk.
+    fn enter_dir(&mut self, _dir: &EntryInfo, _depth: usize) -> WalkAction {
+        WalkAction::Continue
+    }
+
+    /// Called for every entry that's listed other than directories, Stop
+    /// ends the walk.
+    fn file(&mut self, _file: &EntryInfo, _depth: usize) -> WalkAction {
+        WalkAction::Continue
+    }
+
+    /// Called once everything beneath the directory enter_dir was last
+    /// called for without a leave_dir has been visited, right after it for
+    /// a directory that isn't descended into.
+    fn leave_dir(&mut self) {}
+
+    /// Called for a directory that's contents couldn't be read, before it's
+    /// left; with strict the walk ends with the error instead.
+    fn error(&mut self, _error: &TraversalError) {}
+}
+
+/// Walks the tree beneath root, handing each entry that's listed to the
+/// visitor, with the options' filter and depth limit applied. Only the
+/// directories from the root down to the one being walked are held, so
+/// unlike scan this takes the same memory for a tree of any size.
+pub fn walk(root: &path::Path, options: &TreeOptions,
+            visitor: &mut dyn Visitor) -> Result<Stats, TreeError> {
+    let (path, _) = resolve(root)?;
+    let mut adapter = Adapter { visitor, depth: 0, pending: false };
+    Ok(walk::walk(&Source::Path(path), &ignores::Rules::none(),
+                  &options.options, &mut adapter)?)
+}
+
+// hands what a walk goes through to a Visitor of the library
+struct Adapter<'v> {
+    visitor: &'v mut dyn Visitor,
+    // how many directories are being walked, the root included
+    depth: usize,
+    // wether the entry visited last is a directory that might be entered
+    // next, once it's known that it isn't it's left right away
+    pending: bool,
+}
+
+impl Adapter<'_> {
+    fn settle(&mut self) {
+        if std::mem::take(&mut self.pending) {
+            self.visitor.leave_dir();
+        }
+    }
+}
+
+impl walk::Visitor for Adapter<'_> {
+    fn visit(&mut self, node: &walk::Node)
+        -> Result<WalkAction, TraversalError> {
+        self.settle();
+        let info = EntryInfo { entry: node.entry };
+        if node.entry.kind != Kind::Directory {
+            return Ok(self.visitor.file(&info, node.depth()));
+        }
+        let action = self.visitor.enter_dir(&info, node.depth());
+        match action {
+            WalkAction::Continue => self.pending = true,
+            WalkAction::SkipChildren => self.visitor.leave_dir(),
+            WalkAction::Stop => {},
+        }
+        Ok(action)
+    }
+
+    fn enter(&mut self, _dir: &Source) {
+        self.pending = false;
+        self.depth += 1;
+    }
+
+    fn leave(&mut self) {
+        self.settle();
+        self.depth -= 1;
+        // the root was never visited, so it isn't left either
+        if self.depth > 0 {
+            self.visitor.leave_dir();
+        }
+    }
+
+    fn unreadable(&mut self, _prefix: &[bool], _rel_path: &path::Path,
+                  error: &TraversalError) {
+        self.visitor.error(error);
+        self.settle();
+    }
+}
+
+// the canonical path of a root along with the name it's shown with, or why
+// it can't be listed
+fn resolve(root: &path::Path)
+    -> Result<(path::PathBuf, String), TraversalError> {
+    let failed = |error| TraversalError { path: root.to_path_buf(), error };
+    let path = root.canonicalize().map_err(failed)?;
+    if !path.metadata().map_err(failed)?.is_dir() {
+        return Err(failed(io::Error::from(io::ErrorKind::NotADirectory)));
+    }
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_string_lossy().into_owned(),
+    };
+    Ok((path, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::tests::fixture;
+
+    #[test]
+    fn renders_what_it_scans() {
+        let root = fixture("api-render", &["b/", "b/c", "a", "d/"]);
+        let name = root.file_name().unwrap().to_string_lossy().into_owned();
+        let options = TreeOptions::new().files(true);
+        let scanned = scan(&root, &options).unwrap();
+        assert_eq!(scanned.name, name);
+        assert_eq!(render(&scanned, &options), format!("\
+{}
+├───a
+├───b
+│   └───c
+└───d
+", name));
+        assert_eq!(render(&scanned, &TreeOptions::new().ascii(true)),
+                   format!("{}\n+---b\n\\---d\n", name));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn scans_the_same_tree_asynchronously() {
+        let root = fixture("api-async", &["a/", "a/b/", "a/b/c", "a/d", "e"]);
+        let options = sync::Arc::new(TreeOptions::new().files(true));
+        let scanned = scan_async(&root, options.clone()).await.unwrap();
+        assert_eq!(serde_json::to_string(&scanned).unwrap(),
+                   serde_json::to_string(&scan(&root, &options).unwrap())
+                       .unwrap());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // a writer that's out of space from the start
+    struct Full;
+
+    impl io::Write for Full {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::StorageFull))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn renders_into_any_writer() {
+        let root = fixture("api-render-to", &["a/", "a/b"]);
+        let options = TreeOptions::new().files(true);
+        let scanned = scan(&root, &options).unwrap();
+        let mut rendered = vec::Vec::new();
+        render_to(&scanned, &options, &mut rendered).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(),
+                   render(&scanned, &options));
+        match render_to(&scanned, &options, Full) {
+            Err(TreeError::Write(error)) => {
+                assert_eq!(error.kind(), io::ErrorKind::StorageFull);
+            },
+            _ => panic!("a failed write wasn't reported"),
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn scans_with_the_filter_and_depth_limit() {
+        let root = fixture("api-scan", &["a/", "a/b/", "a/b/c", ".d", "e"]);
+        let options = TreeOptions::new().files(true).dotfiles(false)
+            .max_depth(2);
+        let names = |node: &TreeNode| {
+            node.children.iter().map(|child| child.name.clone())
+                .collect::<vec::Vec<_>>()
+        };
+        let scanned = scan(&root, &options).unwrap();
+        assert_eq!(names(&scanned), ["a", "e"]);
+        assert_eq!(names(&scanned.children[0]), ["b"]);
+        assert!(scanned.children[0].children[0].children.is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn renders_with_hooks() {
+        let root = fixture("api-hooks", &["a.rs", "b.txt", "src/", "src/c.rs",
+                                          "target/", "target/d.rs"]);
+        let options = TreeOptions::new().files(true)
+            .shows(|file| match file.name().to_string_lossy().ends_with(".rs") {
+                true => Decision::Accept,
+                false => Decision::Reject("not rust"),
+            })
+            .descends(|dir| match dir.name() == "target" {
+                true => Decision::Reject("[build output]"),
+                false => Decision::Defer,
+            });
+        let scanned = scan(&root, &options).unwrap();
+        assert_eq!(render(&scanned, &options), format!("\
+{}
+├───a.rs
+├───src
+│   └───c.rs
+└───target  [build output]
+", scanned.name));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // what a walk hands a visitor, one line per call
+    struct Recorder {
+        calls: vec::Vec<String>,
+    }
+
+    impl Visitor for Recorder {
+        fn enter_dir(&mut self, dir: &EntryInfo, depth: usize) -> WalkAction {
+            let name = dir.name().to_string_lossy();
+            self.calls.push(format!("enter {} {}", name, depth));
+            match name.as_ref() {
+                "skipped" => WalkAction::SkipChildren,
+                _ => WalkAction::Continue,
+            }
+        }
+
+        fn file(&mut self, file: &EntryInfo, depth: usize) -> WalkAction {
+            let name = file.name().to_string_lossy();
+            self.calls.push(format!("file {} {}", name, depth));
+            match name.as_ref() {
+                "stop" => WalkAction::Stop,
+                _ => WalkAction::Continue,
+            }
+        }
+
+        fn leave_dir(&mut self) {
+            self.calls.push(String::from("leave"));
+        }
+    }
+
+    #[test]
+    fn walks_into_a_visitor() {
+        let root = fixture("api-walk", &["a/", "a/b/", "a/b/c", "a/d", "e/",
+                                         "skipped/", "skipped/f", "stop",
+                                         "z"]);
+        let options = TreeOptions::new().files(true);
+        let mut recorder = Recorder { calls: vec::Vec::new() };
+        let stats = walk(&root, &options, &mut recorder).unwrap();
+        assert_eq!(recorder.calls, [
+            "enter a 1", "enter b 2", "file c 3", "leave", "file d 2",
+            "leave", "enter e 1", "leave", "enter skipped 1", "leave",
+            "file stop 1",
+        ]);
+        assert_eq!((stats.directories, stats.files), (4, 3));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_root_that_isnt_a_directory() {
+        let root = fixture("api-file", &["a"]);
+        match scan(&root.join("a"), &TreeOptions::new()) {
+            Err(TreeError::Traversal(error)) => {
+                assert_eq!(error.path(), root.join("a"));
+            },
+            _ => panic!("a file was scanned as a tree"),
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::collections;
+use std::path;
+use std::vec;
+
+use crate::snapshot::Node;
+use crate::Kind;
+
+// how an entry differs from the snapshot it's compared against
+#[derive(Clone, Copy, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    // the type of the entry changed, or for anything but a directory the size
+    // or modification time did, when both sides have them
+    Changed,
+    // only in the snapshot but still on disk, so it's hidden by the filter or
+    // beyond the depth limit rather than removed
+    NotCompared,
+}
+
+impl Change {
+    pub fn marker(self) -> char {
+        match self {
+            Change::Added => '+',
+            Change::Removed => '-',
+            Change::Changed => '~',
+            Change::NotCompared => '?',
+        }
+    }
+}
+
+// an entry that differs from the snapshot, or a directory without a change
+// of it's own that has differences beneath it
+pub struct Difference {
+    pub name: String,
+    pub kind: Kind,
+    pub change: Option<Change>,
+    pub children: vec::Vec<Difference>,
+}
+
+impl Difference {
+    fn leaf(node: &Node, change: Change) -> Difference {
+        Difference {
+            name: node.name.clone(),
+            kind: node.kind,
+            change: Some(change),
+            children: vec::Vec::new(),
+        }
+    }
+
+    // wether this is, or has beneath it, a difference that was compared
+    pub fn differs(&self) -> bool {
+        match self.change {
+            Some(Change::NotCompared) => false,
+            Some(_) => true,
+            None => self.children.iter().any(Difference::differs),
+        }
+    }
+}
+
+// the differences between the contents of a directory as it was saved and as
+// it is now, ordered by name; dir is where the directory is on disk, which
+// tells entries that were removed apart from those that weren't compared
+pub fn compare(saved: &Node, current: &Node, dir: &path::Path)
+    -> vec::Vec<Difference> {
+    let mut names = collections::BTreeMap::new();
+    for node in saved.children.iter() {
+        names.entry(node.name.as_str()).or_insert((None, None)).0 = Some(node);
+    }
+    for node in current.children.iter() {
+        names.entry(node.name.as_str()).or_insert((None, None)).1 = Some(node);
+    }
+
+    let mut differences = vec::Vec::new();
+    for (name, versions) in names {
+        let difference = match versions {
+            (None, Some(current)) => Difference::leaf(current, Change::Added),
+            (Some(saved), None) => {
+                let change = if dir.join(name).symlink_metadata().is_ok() {
+                    Change::NotCompared
+                } else {
+                    Change::Removed
+                };
+                Difference::leaf(saved, change)
+            },
+            (Some(saved), Some(current)) if is_changed(saved, current) => {
+                Difference::leaf(current, Change::Changed)
+            },
+            (Some(saved), Some(current)) => {
+                // the contents of a directory that couldn't be read when it
+                // was saved are unknown, so they can't be compared
+                if current.kind != Kind::Directory || saved.unreadable {
+                    continue;
+                }
+                let children = compare(saved, current, &dir.join(name));
+                if children.is_empty() {
+                    continue;
+                }
+                Difference {
+                    name: current.name.clone(),
+                    kind: current.kind,
+                    change: None,
+                    children,
+                }
+            },
+            (None, None) => continue,
+        };
+        differences.push(difference);
+    }
+    differences
+}
+
+fn is_changed(saved: &Node, current: &Node) -> bool {
+    if saved.kind != current.kind {
+        return true;
+    }
+    // a directory's own size and time change whenever it's contents do, a
+    // change there is shown on the entries in it instead
+    if current.kind == Kind::Directory {
+        return false;
+    }
+    let differ = |a: Option<u64>, b: Option<u64>| {
+        a.zip(b).is_some_and(|(a, b)| a != b)
+    };
+    differ(saved.size, current.size) || differ(saved.mtime, current.mtime)
+}
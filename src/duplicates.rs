@@ -0,0 +1,146 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use std::collections;
+use std::fs;
+use std::io;
+use std::path;
+use std::vec;
+
+// a group of files whose contents are identical, along with the hex encoded
+// sha256 hash of those contents and the size of each of them; hardlinks of
+// the same file only have the first of their paths in a group
+pub struct Group {
+    pub hash: String,
+    pub size: u64,
+    pub paths: vec::Vec<path::PathBuf>,
+}
+
+impl Group {
+    // the space taken up by every copy but one
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+// what find turns up, the files that are copies of each other and the paths
+// that are hardlinks of the same file, which don't take up any extra space
+pub struct Report {
+    pub groups: vec::Vec<Group>,
+    pub hardlinks: vec::Vec<vec::Vec<path::PathBuf>>,
+}
+
+// groups files by their contents, each file is given as its path and size;
+// files are first bucketed by size so only those that share a size with
+// another file are ever read, and files that can't be read are left out;
+// with parallel the files of a size are hashed on the threads of --threads
+pub fn find(files: vec::Vec<(path::PathBuf, u64)>, parallel: bool) -> Report {
+    let mut by_size = collections::BTreeMap::new();
+    for (path, size) in files {
+        by_size.entry(size).or_insert_with(vec::Vec::new).push(path);
+    }
+
+    let mut groups = vec::Vec::new();
+    let mut hardlinks = vec::Vec::new();
+    for (size, paths) in by_size {
+        if paths.len() < 2 {
+            continue;
+        }
+
+        // hardlinks always share a size, so they're only looked for among
+        // the files that do; each file is only read through one of it's paths
+        let mut by_id = collections::BTreeMap::new();
+        let mut files = vec::Vec::new();
+        for path in paths {
+            match file_id(&path) {
+                Some(id) => {
+                    by_id.entry(id).or_insert_with(vec::Vec::new).push(path);
+                },
+                None => files.push(path),
+            }
+        }
+        for (_, mut paths) in by_id {
+            files.push(paths[0].clone());
+            if paths.len() > 1 {
+                paths.sort();
+                hardlinks.push(paths);
+            }
+        }
+        if files.len() < 2 {
+            continue;
+        }
+
+        let hash = |path: path::PathBuf| (hash_file(&path), path);
+        let hashes: vec::Vec<_> = if parallel {
+            files.into_par_iter().map(hash).collect()
+        } else {
+            files.into_iter().map(hash).collect()
+        };
+        let mut by_hash = collections::BTreeMap::new();
+        for (hash, path) in hashes {
+            if let Ok(hash) = hash {
+                by_hash.entry(hash).or_insert_with(vec::Vec::new).push(path);
+            }
+        }
+        for (hash, paths) in by_hash {
+            if paths.len() > 1 {
+                groups.push(Group { hash, size, paths });
+            }
+        }
+    }
+
+    // the output is ordered by path so it doesn't depend on file sizes
+    for group in groups.iter_mut() {
+        group.paths.sort();
+    }
+    groups.sort_by(|a, b| a.paths[0].cmp(&b.paths[0]));
+    hardlinks.sort();
+    Report { groups, hardlinks }
+}
+
+// the device and inode numbers of a file, which are the same for all of it's
+// hardlinks; these are only available on unix
+#[cfg(unix)]
+fn file_id(path: &path::Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &path::Path) -> Option<(u64, u64)> {
+    None
+}
+
+// the hex encoded sha256 hash of a file's contents, the file is streamed
+// through the hasher so it never has to fit in memory
+pub fn hash_file(path: &path::Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
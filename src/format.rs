@@ -0,0 +1,491 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use chrono::format::{Item, StrftimeItems};
+
+use std::fmt::Write;
+use std::path;
+use std::time;
+use std::vec;
+
+use crate::Kind;
+
+// the placeholders accepted in a template, listed in error messages
+const PLACEHOLDERS: &str = "{name}, {path}, {size}, {size:h}, {mtime}, \
+                            {mtime:<strftime format>}, {perms}, {type}, \
+                            {depth}";
+
+// the colors that can be chosen by name, in the order of their ansi codes
+pub const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue",
+                               "magenta", "cyan", "white"];
+
+// the format used for {mtime} when no strftime format is given
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+enum Token {
+    Literal(String),
+    Name,
+    Path,
+    Size,
+    HumanSize,
+    // the strftime format, None when the default is used
+    Mtime(Option<String>),
+    Perms,
+    Type,
+    Depth,
+}
+
+// a parsed --format template, this is validated once up front so rendering an
+// entry can't fail
+pub struct Template {
+    tokens: vec::Vec<Token>,
+    // what {mtime} is shown relative to, when it's shown as a relative time
+    // rather than a date
+    relative_to: Option<time::SystemTime>,
+}
+
+// everything about an entry a template can refer to, fields that a template
+// doesn't use don't need to be filled in
+pub struct Fields<'a> {
+    pub name: &'a str,
+    pub path: &'a path::Path,
+    pub depth: usize,
+    pub kind: Kind,
+    pub size: Option<u64>,
+    pub mtime: Option<time::SystemTime>,
+    pub mode: Option<u32>,
+    // how {name} and {path} are quoted
+    pub quoting: Quoting,
+}
+
+impl Template {
+    // tokenize a template, placeholders are wrapped in braces, literal braces
+    // are written doubled and the escapes \t, \n, and \\ are supported
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut tokens = vec::Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    },
+                    None => literal.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!(
+                                "unterminated placeholder \"{{{}\" in format",
+                                placeholder)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        tokens.push(Token::Literal(literal));
+                        literal = String::new();
+                    }
+                    tokens.push(parse_placeholder(&placeholder)?);
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+
+        Ok(Template { tokens, relative_to: None })
+    }
+
+    // shows {mtime} as how long ago it was, relative to now, placeholders with
+    // an explicit strftime format still show a date
+    pub fn use_relative_time(&mut self) {
+        self.relative_to = Some(time::SystemTime::now());
+    }
+
+    // wether the template shows the size of entries, in either form
+    pub fn shows_size(&self) -> bool {
+        self.tokens.iter().any(|token| {
+            matches!(token, Token::Size | Token::HumanSize)
+        })
+    }
+
+    // puts the template rendered for the entry described by fields in line
+    pub fn push_rendered(&self, line: &mut String, fields: &Fields) {
+        for token in self.tokens.iter() {
+            match token {
+                Token::Literal(literal) => line.push_str(literal),
+                Token::Name => fields.quoting.push(line, fields.name),
+                Token::Path => {
+                    fields.quoting.push(line, &fields.path.to_string_lossy());
+                },
+                Token::Size => match fields.size {
+                    Some(size) => {
+                        let _ = write!(line, "{}", size);
+                    },
+                    None => line.push('-'),
+                },
+                Token::HumanSize => match fields.size {
+                    Some(size) => line.push_str(&human_size(size)),
+                    None => line.push('-'),
+                },
+                Token::Mtime(format) => match (fields.mtime, format,
+                                               self.relative_to) {
+                    (Some(mtime), None, Some(now)) => {
+                        let _ = write!(line, "{:<13}",
+                                       relative_time(mtime, now));
+                    },
+                    (Some(mtime), format, _) => {
+                        let format = format.as_deref()
+                            .unwrap_or(DEFAULT_TIME_FORMAT);
+                        let local: chrono::DateTime<chrono::Local> =
+                            mtime.into();
+                        let _ = write!(line, "{}", local.format(format));
+                    },
+                    (None, _, _) => line.push('-'),
+                },
+                Token::Perms => match fields.mode {
+                    Some(mode) => {
+                        line.push_str(&permissions(fields.kind, mode));
+                    },
+                    None => line.push('-'),
+                },
+                Token::Type => line.push_str(fields.kind.name()),
+                Token::Depth => {
+                    let _ = write!(line, "{}", fields.depth);
+                },
+            }
+        }
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Token, String> {
+    let (key, arg) = match placeholder.split_once(':') {
+        Some((key, arg)) => (key, Some(arg)),
+        None => (placeholder, None),
+    };
+    match (key, arg) {
+        ("name", None) => Ok(Token::Name),
+        ("path", None) => Ok(Token::Path),
+        ("size", None) => Ok(Token::Size),
+        ("size", Some("h")) => Ok(Token::HumanSize),
+        ("mtime", None) => Ok(Token::Mtime(None)),
+        ("mtime", Some(format)) => {
+            // chrono only reports a bad format when it's used, so check for
+            // invalid specifiers now rather than failing partway through
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                Err(format!("invalid time format \"{}\" in placeholder \
+                             \"{{{}}}\"", format, placeholder))
+            } else {
+                Ok(Token::Mtime(Some(String::from(format))))
+            }
+        },
+        ("perms", None) => Ok(Token::Perms),
+        ("type", None) => Ok(Token::Type),
+        ("depth", None) => Ok(Token::Depth),
+        _ => Err(format!("unknown placeholder \"{{{}}}\" in format, valid \
+                          placeholders are: {}", placeholder, PLACEHOLDERS)),
+    }
+}
+
+// how names are written when they're displayed
+#[derive(Clone, Copy)]
+pub enum Quoting {
+    Plain,
+    // in double quotes, see push_quoted
+    Double,
+    // the way a shell needs them, see push_shell_escaped
+    Shell,
+}
+
+impl Quoting {
+    // puts name in line the way it's written
+    pub fn push(self, line: &mut String, name: &str) {
+        match self {
+            Quoting::Plain => line.push_str(name),
+            Quoting::Double => push_quoted(line, name),
+            Quoting::Shell => push_shell_escaped(line, name),
+        }
+    }
+}
+
+// wraps a name in double quotes, with any quotes and backslashes in it escaped
+// by a backslash, the same way gnu tree's -Q does
+pub fn push_quoted(line: &mut String, name: &str) {
+    line.push('"');
+    for c in name.chars() {
+        if c == '"' || c == '\\' {
+            line.push('\\');
+        }
+        line.push(c);
+    }
+    line.push('"');
+}
+
+// escapes a name so that a posix shell reads it back as the same word, names
+// made only of characters that are never special are left as they are and
+// anything else is single quoted, with each single quote in it written as '\''
+#[cfg(not(windows))]
+pub fn push_shell_escaped(line: &mut String, name: &str) {
+    push_single_quoted(line, name, "_-+=:,./@%", "'\\''");
+}
+
+// the powershell equivalent, where a single quote inside single quotes is
+// written doubled
+#[cfg(windows)]
+pub fn push_shell_escaped(line: &mut String, name: &str) {
+    push_single_quoted(line, name, "_-.", "''");
+}
+
+// puts name in line as it is if it's shell safe, and otherwise single quoted
+// with each single quote in it written as quote
+fn push_single_quoted(line: &mut String, name: &str, safe: &str,
+                      quote: &str) {
+    if is_shell_safe(name, safe) {
+        line.push_str(name);
+        return;
+    }
+    line.push('\'');
+    for c in name.chars() {
+        match c {
+            '\'' => line.push_str(quote),
+            c => line.push(c),
+        }
+    }
+    line.push('\'');
+}
+
+// wether a name is made only of ascii letters, digits, and the given
+// characters; an empty name isn't, since it has to be quoted to be seen
+fn is_shell_safe(name: &str, safe: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| {
+        c.is_ascii_alphanumeric() || safe.contains(c)
+    })
+}
+
+// describes how long before now a time was, e.g. 5 min ago or 3 days ago, or
+// how long after for times in the future, e.g. in 2 h; months are counted as
+// 30 days and years as 365, and anything under a minute away is just now
+pub fn relative_time(then: time::SystemTime, now: time::SystemTime) -> String {
+    let (seconds, future) = match now.duration_since(then) {
+        Ok(elapsed) => (elapsed.as_secs(), false),
+        Err(error) => (error.duration().as_secs(), true),
+    };
+    let (count, unit, plural) = match seconds {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (seconds/60, "min", "min"),
+        3600..=86399 => (seconds/3600, "h", "h"),
+        86400..=2591999 => (seconds/86400, "day", "days"),
+        2592000..=31535999 => (seconds/2592000, "month", "months"),
+        _ => (seconds/31536000, "year", "years"),
+    };
+    let unit = if count == 1 { unit } else { plural };
+    if future {
+        format!("in {} {}", count, unit)
+    } else {
+        format!("{} {} ago", count, unit)
+    }
+}
+
+// formats a byte count using binary units, e.g. 1536 becomes 1.5K
+pub fn human_size(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G", "T", "P", "E"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len()-1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, units[unit])
+    } else {
+        format!("{:.1}{}", size, units[unit])
+    }
+}
+
+// formats unix permission bits the way ls does, e.g. drwxr-xr-x, for special
+// files the file type bits of the mode decide the first character
+pub fn permissions(kind: Kind, mode: u32) -> String {
+    let mut perms = String::from(match kind {
+        Kind::Directory => 'd',
+        Kind::File => '-',
+        Kind::Symlink => 'l',
+        Kind::Other => match mode & 0o170000 {
+            0o010000 => 'p',
+            0o020000 => 'c',
+            0o060000 => 'b',
+            0o140000 => 's',
+            _ => '?',
+        },
+    });
+    // each triplet is made up of the read, write, and execute bits along with
+    // the special bit (setuid, setgid, or sticky) that shares the execute slot
+    let triplets = [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')];
+    for (shift, special_bit, special) in triplets {
+        let bits = (mode >> shift) & 0o7;
+        perms.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        perms.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        perms.push(match (bits & 0o1 != 0, mode & special_bit != 0) {
+            (true, true) => special,
+            (false, true) => special.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    perms
+}
+
+// formats the permission bits, including the setuid, setgid, and sticky bits,
+// as four octal digits, e.g. 0755
+pub fn octal_permissions(mode: u32) -> String {
+    format!("{:04o}", mode & 0o7777)
+}
+
+// the ansi foreground color code for a color name from COLORS
+pub fn color_code(name: &str) -> Option<&'static str> {
+    let codes = ["30", "31", "32", "33", "34", "35", "36", "37"];
+    COLORS.iter().position(|color| *color == name).map(|index| codes[index])
+}
+
+// how many days old an entry is when --age-color draws it fully red, unless
+// --age-scale says otherwise
+pub const DEFAULT_AGE_SCALE: u64 = 365;
+
+// the 256 color foreground code for something of the given age, going from
+// green through yellow to red as the age goes from nothing to scale, anything
+// older than scale is red
+pub fn age_color(age: time::Duration, scale: time::Duration) -> String {
+    let ratio = (age.as_secs_f64() / scale.as_secs_f64()).clamp(0.0, 1.0);
+    // the six steps of each channel of the 6x6x6 color cube
+    let red = (ratio * 2.0).min(1.0) * 5.0;
+    let green = ((1.0 - ratio) * 2.0).min(1.0) * 5.0;
+    let code = 16 + 36 * red.round() as u32 + 6 * green.round() as u32;
+    format!("38;5;{}", code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn shell_escaped_names_read_back_unchanged() {
+        let names = [
+            "plain", "with space", "it's", "''", "$HOME", "`date`", "$(date)",
+            "a\"b", "back\\slash", "new\nline", "tab\t", "*", "?[a]", "",
+            "-dash", "~user", "!bang", "a;b&c|d>e", "{a,b}", "a=b", "ünï",
+            "%s", "#hash",
+        ];
+        let mut script = String::from("printf '%s\\0'");
+        for name in names {
+            script.push(' ');
+            push_shell_escaped(&mut script, name);
+        }
+        let output = std::process::Command::new("sh").arg("-c").arg(&script)
+            .output().unwrap();
+        assert!(output.status.success(), "{}", script);
+        let read_back = String::from_utf8(output.stdout).unwrap();
+        let read_back: vec::Vec<&str> = read_back.split_terminator('\0')
+            .collect();
+        assert_eq!(read_back, names);
+    }
+
+    #[test]
+    fn relative_time_rounds_down_to_the_unit() {
+        let now = time::UNIX_EPOCH + time::Duration::from_secs(1 << 30);
+        let cases = [
+            (0, "just now"),
+            (59, "just now"),
+            (60, "1 min ago"),
+            (119, "1 min ago"),
+            (120, "2 min ago"),
+            (3599, "59 min ago"),
+            (3600, "1 h ago"),
+            (86399, "23 h ago"),
+            (86400, "1 day ago"),
+            (172800, "2 days ago"),
+            (2591999, "29 days ago"),
+            (2592000, "1 month ago"),
+            (31535999, "12 months ago"),
+            (31536000, "1 year ago"),
+            (63072000, "2 years ago"),
+        ];
+        for (seconds, expected) in cases {
+            let then = now - time::Duration::from_secs(seconds);
+            assert_eq!(relative_time(then, now), expected, "{}s", seconds);
+        }
+    }
+
+    #[test]
+    fn relative_time_counts_forward_for_the_future() {
+        let now = time::UNIX_EPOCH + time::Duration::from_secs(1 << 30);
+        let cases = [
+            (59, "just now"),
+            (60, "in 1 min"),
+            (86399, "in 23 h"),
+            (86400, "in 1 day"),
+        ];
+        for (seconds, expected) in cases {
+            let then = now + time::Duration::from_secs(seconds);
+            assert_eq!(relative_time(then, now), expected, "{}s", seconds);
+        }
+    }
+
+    #[test]
+    fn octal_permissions_keeps_the_special_bits() {
+        // st_mode values with the file type bits still in them
+        let cases = [
+            (Kind::File, 0o100644, "0644", "-rw-r--r--"),
+            (Kind::File, 0o100755, "0755", "-rwxr-xr-x"),
+            (Kind::File, 0o104755, "4755", "-rwsr-xr-x"),
+            (Kind::File, 0o104644, "4644", "-rwSr--r--"),
+            (Kind::Directory, 0o042755, "2755", "drwxr-sr-x"),
+            (Kind::Directory, 0o041777, "1777", "drwxrwxrwt"),
+            (Kind::Directory, 0o041776, "1776", "drwxrwxrwT"),
+            (Kind::Symlink, 0o120777, "0777", "lrwxrwxrwx"),
+            (Kind::Other, 0o010600, "0600", "prw-------"),
+            (Kind::File, 0o107000, "7000", "---S--S--T"),
+        ];
+        for (kind, mode, octal, symbolic) in cases {
+            assert_eq!(octal_permissions(mode), octal, "{:o}", mode);
+            assert_eq!(permissions(kind, mode), symbolic, "{:o}", mode);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+
+use git2::{Repository, StatusOptions};
+
+use std::collections;
+use std::path;
+
+// the state of a changed file the way git status --short shows it, one
+// letter for the index and one for the working tree, with a space for
+// whichever side has no change; untracked files are ??
+#[derive(Clone, Copy)]
+pub struct Marker {
+    pub index: char,
+    pub worktree: char,
+}
+
+// the status of every changed file in a repository by its absolute path,
+// files that aren't in it are unchanged
+pub struct Statuses {
+    markers: collections::HashMap<path::PathBuf, Marker>,
+}
+
+impl Statuses {
+    // reads the status of the repository dir is in, once for the whole tree,
+    // or None if it isn't in a repository with a working tree
+    pub fn load(dir: &path::Path) -> Option<Statuses> {
+        let repository = Repository::discover(dir).ok()?;
+        let workdir = repository.workdir()?;
+        // entries are looked up by paths beneath the canonicalized root
+        let workdir = workdir.canonicalize()
+            .unwrap_or_else(|_| workdir.to_path_buf());
+
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true);
+        let statuses = repository.statuses(Some(&mut options)).ok()?;
+        let markers = statuses.iter()
+            .filter_map(|entry| {
+                let path = workdir.join(entry.path().ok()?);
+                Some((path, marker(entry.status())?))
+            })
+            .collect();
+        Some(Statuses { markers })
+    }
+
+    pub fn marker(&self, path: &path::Path) -> Option<Marker> {
+        self.markers.get(path).copied()
+    }
+}
+
+fn marker(status: git2::Status) -> Option<Marker> {
+    if status.is_wt_new() {
+        return Some(Marker { index: '?', worktree: '?' });
+    }
+    if status.is_conflicted() {
+        return Some(Marker { index: 'U', worktree: 'U' });
+    }
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    let worktree = if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    if index == ' ' && worktree == ' ' {
+        None
+    } else {
+        Some(Marker { index, worktree })
+    }
+}
+
+// the absolute paths of every file in the index of the repositories that the
+// roots are in, which is what --git-tracked shows
+pub struct Tracked {
+    paths: collections::HashSet<path::PathBuf>,
+}
+
+impl Tracked {
+    // reads the index of each root's repository once, the error is the first
+    // root that isn't inside a repository with a working tree
+    pub fn load(roots: &[path::PathBuf]) -> Result<Tracked, path::PathBuf> {
+        let mut paths = collections::HashSet::new();
+        for root in roots {
+            let repository = Repository::discover(root)
+                .map_err(|_| root.clone())?;
+            let workdir = repository.workdir().ok_or_else(|| root.clone())?;
+            let workdir = workdir.canonicalize()
+                .unwrap_or_else(|_| workdir.to_path_buf());
+            let index = repository.index().map_err(|_| root.clone())?;
+            paths.extend(index.iter()
+                .filter_map(|entry| {
+                    let path = std::str::from_utf8(&entry.path).ok()?;
+                    Some(workdir.join(path))
+                }));
+        }
+        Ok(Tracked { paths })
+    }
+
+    pub fn contains(&self, path: &path::Path) -> bool {
+        self.paths.contains(path)
+    }
+}
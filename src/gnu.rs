@@ -0,0 +1,238 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::ffi;
+use std::sync;
+use std::vec;
+
+// the connectors gnu tree draws with, and with --ascii the ones of it's
+// --charset=ascii; each is followed by a space instead of a fourth line, and
+// like in gnu tree the vertical line is padded with no-break spaces
+pub const CONNECTORS: [&str; 4] = ["└── ", "├── ", "    ",
+                                   "│\u{a0}\u{a0} "];
+pub const ASCII_CONNECTORS: [&str; 4] = ["`-- ", "|-- ", "    ", "|   "];
+
+// the note gnu tree gives a directory, the root included, that it couldn't
+// open, in place of it's contents
+pub const OPEN_ERROR: &str = "[error opening dir]";
+
+// the short flags of gnu tree that don't mean the same thing here, what the
+// ones that aren't in the table mean is the same, like -L, -I, -P, -l, -p,
+// -t, -r, -x and -Q
+pub struct Profile {
+    // -a, dotfiles are hidden without it
+    pub all: bool,
+    // -d, files are shown without it
+    pub dirs_only: bool,
+}
+
+// wether the arguments ask for --gnu, which changes how the rest of them are
+// read, so this is looked for before they're parsed
+pub fn is_requested(args: &[ffi::OsString]) -> bool {
+    args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--gnu")
+}
+
+// rewrites the arguments of gnu tree into the ones that mean the same here,
+// short flags can be grouped like -ad and a flag that takes a value can have
+// it attached like -L2, the way gnu tree reads them; a flag gnu tree has that
+// can't be mapped onto anything is an error. values are the spellings of the
+// options that take a value, like -L and --level, what follows them is their
+// value and is passed on as it is, and so is everything after --
+pub fn translate(args: vec::Vec<ffi::OsString>, values: &[String])
+    -> Result<(vec::Vec<ffi::OsString>, Profile), String> {
+    let takes_value = |option: &str| values.iter().any(|value| value == option);
+    let mut profile = Profile { all: false, dirs_only: false };
+    let mut translated = vec::Vec::new();
+    let mut args = args.into_iter();
+    translated.extend(args.next());
+    while let Some(arg) = args.next() {
+        let flags = match arg.to_str() {
+            Some("--") => {
+                translated.push(arg);
+                translated.extend(args.by_ref());
+                break;
+            },
+            Some("--noreport") => {
+                translated.push(ffi::OsString::from("--no-report"));
+                continue;
+            },
+            Some(option) if option.starts_with("--") && takes_value(option) => {
+                translated.push(arg);
+                translated.extend(args.next());
+                continue;
+            },
+            Some(text) if text.starts_with('-') && !text.starts_with("--")
+                && text.len() > 1 => &text[1..],
+            _ => {
+                translated.push(arg);
+                continue;
+            },
+        };
+        for (i, flag) in flags.char_indices() {
+            let long = match flag {
+                'a' => {
+                    profile.all = true;
+                    continue;
+                },
+                'd' => {
+                    profile.dirs_only = true;
+                    continue;
+                },
+                'i' => "--flat",
+                'v' => "--sort=version",
+                'n' => "--color=never",
+                'C' => "--color=always",
+                'f' | 'D' | 's' | 'h' | 'u' | 'g' | 'F' | 'J' | 'X' | 'o'
+                    | 'A' | 'S' | 'N' | 'c' | 'U' | 'H' | 'R' | 'T' => {
+                    return Err(format!("-{} of gnu tree isn't supported",
+                                       flag));
+                },
+                _ if takes_value(&format!("-{}", flag)) => {
+                    // the rest of the group is the value, or the next
+                    // argument if there isn't any
+                    translated.push(ffi::OsString::from(format!("-{}", flag)));
+                    let value = &flags[i+flag.len_utf8()..];
+                    if value.is_empty() {
+                        translated.extend(args.next());
+                    } else {
+                        translated.push(ffi::OsString::from(value));
+                    }
+                    break;
+                },
+                _ => {
+                    translated.push(ffi::OsString::from(format!("-{}", flag)));
+                    continue;
+                },
+            };
+            translated.push(ffi::OsString::from(long));
+        }
+    }
+    Ok((translated, profile))
+}
+
+// what gnu tree counts, which is added up over every root and only reported
+// once after the last of them
+struct Totals {
+    directories: u64,
+    files: u64,
+    // wether any directory couldn't be opened
+    failed: bool,
+}
+
+static TOTALS: sync::Mutex<Totals> = sync::Mutex::new(Totals {
+    directories: 0,
+    files: 0,
+    failed: false,
+});
+
+fn lock_totals() -> sync::MutexGuard<'static, Totals> {
+    TOTALS.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+// counts the entries of a tree, gnu tree counts a symlink as a directory when
+// it points to one wether it's followed or not, and anything else that isn't
+// a directory as a file
+pub fn add(directories: u64, files: u64) {
+    let mut totals = lock_totals();
+    totals.directories += directories;
+    totals.files += files;
+}
+
+// records that a directory couldn't be opened, which gnu tree exits with 2
+// for once the tree has been printed
+pub fn failed() {
+    lock_totals().failed = true;
+}
+
+// the line gnu tree ends with, without files if they aren't shown
+pub fn report(show_files: bool) -> String {
+    let totals = lock_totals();
+    let directories = format!("{} director{}", totals.directories,
+                              if totals.directories == 1 { "y" }
+                              else { "ies" });
+    if !show_files {
+        return directories;
+    }
+    format!("{}, {} file{}", directories, totals.files,
+            if totals.files == 1 { "" } else { "s" })
+}
+
+// the exit status gnu tree would have
+pub fn exit_code() -> i32 {
+    if lock_totals().failed { 2 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the arguments translates gives back, without the program name, and the
+    // -a and -d of the profile
+    fn translated(args: &[&str]) -> Result<(vec::Vec<String>, bool, bool),
+                                            String> {
+        let values = ["-L", "-I", "-P", "--level", "--format", "--sort"]
+            .map(String::from);
+        let args = ["tree"].iter().chain(args)
+            .map(ffi::OsString::from)
+            .collect();
+        let (args, profile) = translate(args, &values)?;
+        let args = args.into_iter().skip(1)
+            .map(|arg| arg.into_string().unwrap())
+            .collect();
+        Ok((args, profile.all, profile.dirs_only))
+    }
+
+    #[test]
+    fn translates_flags_but_not_values() {
+        let cases: [(&[&str], &[&str], bool, bool); 13] = [
+            (&["src"], &["src"], false, false),
+            (&["-ad", "src"], &["src"], true, true),
+            (&["-in"], &["--flat", "--color=never"], false, false),
+            (&["-vC"], &["--sort=version", "--color=always"], false, false),
+            (&["--noreport"], &["--no-report"], false, false),
+            (&["-pL2"], &["-p", "-L", "2"], false, false),
+            (&["-L", "2", "-l"], &["-L", "2", "-l"], false, false),
+            (&["-I", "-a"], &["-I", "-a"], false, false),
+            (&["-dP", "-n"], &["-P", "-n"], false, true),
+            (&["--format", "-d {name}"], &["--format", "-d {name}"], false,
+             false),
+            (&["--format=-a", "-a"], &["--format=-a"], true, false),
+            (&["--sort", "--", "-d"], &["--sort", "--"], false, true),
+            (&["-a", "--", "-d", "-v"], &["--", "-d", "-v"], true, false),
+        ];
+        for (args, expected, all, dirs_only) in cases {
+            let expected = expected.iter().map(|arg| String::from(*arg))
+                .collect();
+            assert_eq!(translated(args), Ok((expected, all, dirs_only)),
+                       "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn rejects_flags_without_an_equivalent() {
+        assert_eq!(translated(&["-aX"]),
+                   Err(String::from("-X of gnu tree isn't supported")));
+        assert!(translated(&["-P", "-X"]).is_ok());
+    }
+}
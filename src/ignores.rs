@@ -0,0 +1,600 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder, Glob as Rule};
+use ignore::Match;
+use unicode_normalization::UnicodeNormalization;
+
+use std::borrow;
+use std::env;
+use std::ffi;
+use std::fs;
+use std::io;
+use std::iter;
+use std::path;
+use std::rc;
+use std::vec;
+
+// the ignore files read from each directory, in increasing order of
+// precedence, so a rule in .ignore overrides one in .gitignore, along with
+// wether each one is one of the version control files and wether it's one of
+// the ripgrep and fd conventions, .ignore is both
+const IGNORE_FILES: [(&str, bool, bool); 3] = [
+    (".gitignore", true, false),
+    (".ignore", true, true),
+    (".fdignore", false, true),
+];
+
+// which families of ignore files are read
+#[derive(Clone, Copy)]
+pub struct Sources {
+    pub vcs: bool,
+    pub files: bool,
+}
+
+// the names of the metadata directories of common version control systems,
+// these are what --exclude-vcs hides
+pub const VCS_DIRS: [&str; 6] = [".git", ".hg", ".svn", ".bzr", "CVS", ".jj"];
+
+// an ordered list of glob patterns that entry names are matched against,
+// like in a .gitignore a pattern starting with ! re-includes names that an
+// earlier pattern matched, and the last pattern that matches decides; a
+// pattern with a / in it is matched against the path of the entry relative
+// to the root instead, where * stays within one component and ** matches
+// across any number of them, like target/** or src/**/*.rs
+pub struct Patterns {
+    set: GlobSet,
+    negated: vec::Vec<bool>,
+    // wether each pattern is matched against paths rather than names
+    paths: vec::Vec<bool>,
+    // each pattern as it was given, for --verbose to name
+    patterns: vec::Vec<String>,
+    // wether names are put in normalization form C before matching
+    normalize: bool,
+}
+
+impl Patterns {
+    // compiles the patterns in order, the error names the pattern that was
+    // invalid
+    pub fn new(patterns: &[&str], normalize: bool)
+        -> Result<Patterns, String> {
+        Patterns::build(patterns, normalize, false)
+    }
+
+    // like new, but every pattern is matched against the path relative to
+    // the root, even ones without a / in them, the way a .dockerignore is
+    pub fn anchored(patterns: &[&str], normalize: bool)
+        -> Result<Patterns, String> {
+        Patterns::build(patterns, normalize, true)
+    }
+
+    fn build(patterns: &[&str], normalize: bool, anchored: bool)
+        -> Result<Patterns, String> {
+        let mut builder = GlobSetBuilder::new();
+        let mut negated = vec::Vec::new();
+        let mut paths = vec::Vec::new();
+        for pattern in patterns {
+            // the patterns are normalized like the names, so that a pattern
+            // typed either way matches names stored either way
+            let pattern: borrow::Cow<str> = match normalize {
+                true if !unicode_normalization::is_nfc(pattern) => {
+                    borrow::Cow::Owned(pattern.nfc().collect())
+                },
+                _ => borrow::Cow::Borrowed(pattern),
+            };
+            let (glob, negate) = match pattern.strip_prefix('!') {
+                Some(glob) => (glob, true),
+                None => (&*pattern, false),
+            };
+            let is_path = anchored || glob.contains('/');
+            match GlobBuilder::new(glob).literal_separator(is_path).build() {
+                Ok(glob) => builder.add(glob),
+                Err(error) => return Err(format!("\"{}\" Is not a valid \
+                                                  pattern: {}", pattern,
+                                                 error.kind())),
+            };
+            negated.push(negate);
+            paths.push(is_path);
+        }
+        let set = builder.build().map_err(|error| error.to_string())?;
+        let patterns = patterns.iter().map(|pattern| String::from(*pattern))
+            .collect();
+        Ok(Patterns { set, negated, paths, patterns, normalize })
+    }
+
+    // path is the entry's path relative to the root, entries without one,
+    // like those of a loaded snapshot, are only matched by name
+    pub fn is_match(&self, name: &ffi::OsStr, path: Option<&path::Path>)
+        -> bool {
+        let name = self.normalized(name);
+        // without negated or path patterns any match is enough, which the
+        // set can answer without finding every pattern that matched
+        if !self.negated.contains(&true) && !self.paths.contains(&true) {
+            return self.set.is_match(&*name);
+        }
+        self.last_match(&name, path).is_some_and(|last| !self.negated[last])
+    }
+
+    // the pattern that decides wether a name matches, if any of them do
+    pub fn deciding(&self, name: &ffi::OsStr, path: Option<&path::Path>)
+        -> Option<&str> {
+        let name = self.normalized(name);
+        self.last_match(&name, path).map(|last| self.patterns[last].as_str())
+    }
+
+    fn last_match(&self, name: &ffi::OsStr, path: Option<&path::Path>)
+        -> Option<usize> {
+        let by_name = self.set.matches(name).into_iter()
+            .filter(|i| !self.paths[*i]);
+        let by_path = path
+            .map(|path| self.set.matches(&*self.normalized(path.as_os_str())))
+            .unwrap_or_default().into_iter()
+            .filter(|i| self.paths[*i]);
+        by_name.chain(by_path).max()
+    }
+
+    fn normalized<'n>(&self, name: &'n ffi::OsStr)
+        -> borrow::Cow<'n, ffi::OsStr> {
+        if self.normalize {
+            crate::nfc(name)
+        } else {
+            borrow::Cow::Borrowed(name)
+        }
+    }
+}
+
+// splits a pattern argument on the | characters between it's alternatives, like
+// -P '*.c|*.h' in GNU tree; a | escaped with a backslash stays in the pattern,
+// where the glob matches it literally
+pub fn split_alternatives(pattern: &str) -> vec::Vec<&str> {
+    let bytes = pattern.as_bytes();
+    let mut alternatives = vec::Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'|' => {
+                alternatives.push(&pattern[start..i]);
+                start = i+1;
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+    alternatives.push(&pattern[start..]);
+    alternatives
+}
+
+// the patterns of the .dockerignore in root, in the form Docker compares them
+// in: comments and blank lines are dropped, surrounding whitespace is
+// trimmed, and paths are cleaned of ., .., and leading and trailing slashes,
+// with any ! kept in front; there being no file is the same as an empty one
+pub fn dockerignore(root: &path::Path) -> io::Result<vec::Vec<String>> {
+    let text = match fs::read_to_string(root.join(".dockerignore")) {
+        Ok(value) => value,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Ok(vec::Vec::new());
+        },
+        Err(error) => return Err(error),
+    };
+    let patterns = text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.strip_prefix('!') {
+            Some(pattern) => format!("!{}", clean_path(pattern.trim())),
+            None => clean_path(line),
+        })
+        .collect();
+    Ok(patterns)
+}
+
+// cleans a path the way Go's filepath.Clean does, relative to the root
+fn clean_path(path: &str) -> String {
+    let mut components = vec::Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {},
+            ".." => {
+                components.pop();
+            },
+            _ => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        String::from(".")
+    } else {
+        components.join("/")
+    }
+}
+
+// expands the brace groups of a pattern the way bash does, so *.{rs,toml}
+// becomes *.rs and *.toml, groups can be nested and a pattern can have
+// several of them; this is done by tree itself rather than relying on the
+// shell, and braces without a comma between them, or escaped with a
+// backslash, are left as they are
+pub fn expand_braces(pattern: &str) -> vec::Vec<String> {
+    let bytes = pattern.as_bytes();
+    let mut start = 0;
+    while let Some((open, close, commas)) = brace_group(bytes, start) {
+        if commas.is_empty() {
+            // a group without alternatives might still have groups inside it
+            start = open+1;
+            continue;
+        }
+        let prefix = &pattern[..open];
+        let suffix = &pattern[close+1..];
+        let bounds = iter::once(open)
+            .chain(commas.iter().copied())
+            .chain(iter::once(close));
+        let bounds: vec::Vec<usize> = bounds.collect();
+        return bounds.windows(2)
+            .flat_map(|window| {
+                let alternative = &pattern[window[0]+1..window[1]];
+                expand_braces(&format!("{}{}{}", prefix, alternative, suffix))
+            })
+            .collect();
+    }
+    vec::Vec::from([String::from(pattern)])
+}
+
+// the first brace group at or after start whose braces are balanced, as the
+// positions of its braces and the commas directly inside it
+fn brace_group(bytes: &[u8], start: usize)
+    -> Option<(usize, usize, vec::Vec<usize>)> {
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'{' => {
+                if let Some((close, commas)) = group_end(bytes, i) {
+                    return Some((i, close, commas));
+                }
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+    None
+}
+
+fn group_end(bytes: &[u8], open: usize) -> Option<(usize, vec::Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = vec::Vec::new();
+    let mut i = open+1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Some((i, commas)),
+            b'}' => depth -= 1,
+            b',' if depth == 0 => commas.push(i),
+            _ => {},
+        }
+        i += 1;
+    }
+    None
+}
+
+// the ignore rules in effect for a directory, made up of the rules from its own
+// ignore files layered over the rules of every directory above it; the layers
+// are shared with the rules of the directories beneath, so cloning the rules
+// of a directory is cheap and they don't borrow the rules above them
+#[derive(Clone)]
+pub struct Rules {
+    // the rules of the closest directory with any ignore files, none if
+    // there isn't one
+    layer: Option<rc::Rc<Layer>>,
+}
+
+struct Layer {
+    matcher: Gitignore,
+    parent: Rules,
+}
+
+impl Rules {
+    // rules that don't ignore anything
+    pub fn none() -> Rules {
+        Rules { layer: None }
+    }
+
+    // the rules of the global fd ignore file, which apply to the whole tree as
+    // if the file was in root, with a lower precedence than any file in it
+    pub fn global(root: &path::Path) -> Rules {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .map(path::PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .map(|home| path::PathBuf::from(home).join(".config"))
+            });
+        match config {
+            Some(config) => {
+                Rules::build(root, [config.join("fd").join("ignore")],
+                             &Rules::none())
+            },
+            None => Rules::none(),
+        }
+    }
+
+    // read the ignore files of dir from the chosen sources, when reading the
+    // version control files the .git/info/exclude file of the root of a git
+    // repository is read too, with the lowest precedence
+    pub fn load(dir: &path::Path, parent: &Rules, sources: Sources)
+        -> Rules {
+        let exclude = dir.join(".git").join("info").join("exclude");
+        let files = [exclude].into_iter()
+            .filter(|_| sources.vcs)
+            .chain(IGNORE_FILES.iter()
+                .filter(|(_, vcs, files)| {
+                    (sources.vcs && *vcs) || (sources.files && *files)
+                })
+                .map(|(file, _, _)| dir.join(file)));
+        Rules::build(dir, files, parent)
+    }
+
+    fn build(dir: &path::Path, files: impl IntoIterator<Item = path::PathBuf>,
+             parent: &Rules) -> Rules {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+
+        for file in files {
+            // unreadable or malformed ignore files are skipped
+            if file.is_file() && builder.add(&file).is_none() {
+                log::debug!("\"{}\" read", file.to_string_lossy());
+                found = true;
+            }
+        }
+
+        // a directory without any ignore files of it's own shares the
+        // rules of the one it's in
+        if !found {
+            return parent.clone();
+        }
+        match builder.build() {
+            Ok(matcher) => Rules {
+                layer: Some(rc::Rc::new(Layer {
+                    matcher,
+                    parent: parent.clone(),
+                })),
+            },
+            Err(_) => parent.clone(),
+        }
+    }
+
+    // wether there are no rules at all, which nothing is ignored by
+    pub fn is_empty(&self) -> bool {
+        self.layer.is_none()
+    }
+
+    pub fn is_ignored(&self, path: &path::Path, is_dir: bool) -> bool {
+        self.matched(path, is_dir).is_ignore()
+    }
+
+    // why a path is ignored, as the rule that ignores it and the file that
+    // rule is from
+    pub fn ignored_by(&self, path: &path::Path, is_dir: bool)
+        -> Option<(&str, Option<&path::Path>)> {
+        match self.matched(path, is_dir) {
+            Match::Ignore(rule) => Some((rule.original(), rule.from())),
+            _ => None,
+        }
+    }
+
+    // checks the rules of the closest directory first, and only falls back to
+    // the rules of its parents if nothing there matched; this is what makes
+    // nested ignore files and negated patterns override broader rules
+    fn matched(&self, path: &path::Path, is_dir: bool) -> Match<&Rule> {
+        let mut layer = self.layer.as_deref();
+        while let Some(current) = layer {
+            let matched = current.matcher.matched(path, is_dir);
+            if !matched.is_none() {
+                return matched;
+            }
+            layer = current.parent.layer.as_deref();
+        }
+        Match::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lets_the_last_matching_pattern_win() {
+        let cases: [(&[&str], [bool; 3]); 4] = [
+            (&["*.log"], [true, true, false]),
+            (&["*.log", "!important.log"], [true, false, false]),
+            (&["!important.log", "*.log"], [true, true, false]),
+            (&["*.log", "!*.log", "debug.log"], [true, false, false]),
+        ];
+        for (patterns, expected) in cases {
+            let rules = Patterns::new(patterns, false).unwrap();
+            let names = ["debug.log", "important.log", "notes.txt"];
+            for (name, expected) in names.iter().zip(expected) {
+                assert_eq!(rules.is_match(ffi::OsStr::new(name), None),
+                           expected, "{} {:?}", name, patterns);
+            }
+        }
+    }
+
+    // the patterns a -P or -I argument turns into, in order
+    fn expanded(argument: &str) -> vec::Vec<String> {
+        split_alternatives(argument).into_iter().flat_map(expand_braces)
+            .collect()
+    }
+
+    #[test]
+    fn splits_on_unescaped_bars() {
+        let cases: [(&str, &[&str]); 7] = [
+            ("*.c", &["*.c"]),
+            ("*.c|*.h", &["*.c", "*.h"]),
+            ("a\\|b", &["a\\|b"]),
+            ("a\\|b|c", &["a\\|b", "c"]),
+            ("a\\\\|b", &["a\\\\", "b"]),
+            ("*.o|!keep.o", &["*.o", "!keep.o"]),
+            ("a||b|", &["a", "", "b", ""]),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(split_alternatives(pattern), expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn expands_brace_groups() {
+        let cases: [(&str, &[&str]); 10] = [
+            ("*.rs", &["*.rs"]),
+            ("*.{rs,toml}", &["*.rs", "*.toml"]),
+            ("{a,b}{1,2}", &["a1", "a2", "b1", "b2"]),
+            ("{a,{b,c}}d", &["ad", "bd", "cd"]),
+            ("{{a,b}}", &["{a}", "{b}"]),
+            ("{,x}y", &["y", "xy"]),
+            ("{a}", &["{a}"]),
+            ("{a,b", &["{a,b"]),
+            ("\\{a,b}", &["\\{a,b}"]),
+            ("{a,b\\,c}", &["a", "b\\,c"]),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(expand_braces(pattern), expected, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn keeps_negation_on_each_alternative() {
+        assert_eq!(expanded("*.o|!{keep,also}.o|a\\|b.{c,h}"),
+                   ["*.o", "!keep.o", "!also.o", "a\\|b.c", "a\\|b.h"]);
+
+        let expanded = expanded("*.o|!keep.o|a\\|b");
+        let expanded: vec::Vec<&str> = expanded.iter().map(String::as_str)
+            .collect();
+        let patterns = Patterns::new(&expanded, false).unwrap();
+        let cases = [
+            ("main.o", true),
+            ("keep.o", false),
+            ("a|b", true),
+            ("a", false),
+            ("b", false),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(patterns.is_match(ffi::OsStr::new(name), None),
+                       expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn matches_names_and_relative_paths() {
+        let patterns = ["*.rs", "target/**", "src/**/*.txt", "docs/*",
+                        "!lib.rs", "!target/keep"];
+        let patterns = Patterns::new(&patterns, false).unwrap();
+        let cases = [
+            ("main.rs", "main.rs", true),
+            ("main.rs", "src/bin/main.rs", true),
+            ("lib.rs", "src/lib.rs", false),
+            ("debug", "target/debug", true),
+            ("build", "target/debug/build", true),
+            ("keep", "target/keep", false),
+            ("target", "target", false),
+            ("a.txt", "src/a.txt", true),
+            ("b.txt", "src/x/y/b.txt", true),
+            ("c.txt", "c.txt", false),
+            ("index.md", "docs/index.md", true),
+            // * stays within one component of a path
+            ("index.md", "docs/api/index.md", false),
+            ("docs", "other/docs", false),
+        ];
+        for (name, path, expected) in cases {
+            let is_match = patterns.is_match(ffi::OsStr::new(name),
+                                             Some(path::Path::new(path)));
+            assert_eq!(is_match, expected, "{}", path);
+        }
+
+        // without a path only the patterns without a / can match
+        assert!(patterns.is_match(ffi::OsStr::new("main.rs"), None));
+        assert!(!patterns.is_match(ffi::OsStr::new("debug"), None));
+    }
+
+    #[test]
+    fn matches_names_written_either_way_when_normalizing() {
+        // é as one code point and as e followed by a combining accent
+        let composed = "caf\u{e9}.txt";
+        let decomposed = "cafe\u{301}.txt";
+        for (pattern, name) in [(composed, decomposed),
+                                (decomposed, composed)] {
+            let name = ffi::OsStr::new(name);
+            assert!(!Patterns::new(&[pattern], false).unwrap()
+                .is_match(name, None));
+            assert!(Patterns::new(&[pattern], true).unwrap()
+                .is_match(name, None));
+        }
+    }
+
+    #[test]
+    fn matches_every_pattern_against_paths_when_anchored() {
+        let patterns = Patterns::anchored(&["*.rs", "src/**"], false).unwrap();
+        let cases = [
+            ("main.rs", true),
+            ("bin/main.rs", false),
+            ("src/bin/main.rs", true),
+        ];
+        for (path, expected) in cases {
+            let path = path::Path::new(path);
+            let is_match = patterns.is_match(path.file_name().unwrap(),
+                                             Some(path));
+            assert_eq!(is_match, expected, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn reads_dockerignore_files_the_way_docker_does() {
+        let root = crate::tests::fixture("dockerignore", &[]);
+        assert!(dockerignore(&root).unwrap().is_empty());
+        fs::write(root.join(".dockerignore"),
+                  "# build output\n  target/  \n\n./docs/../*.md\n**/*.log\n\
+                   ! /keep.log\n/a//b/.\n").unwrap();
+        let patterns = dockerignore(&root).unwrap();
+        assert_eq!(patterns, ["target", "*.md", "**/*.log", "!keep.log",
+                              "a/b"]);
+
+        let patterns: vec::Vec<&str> = patterns.iter().map(String::as_str)
+            .collect();
+        let patterns = Patterns::anchored(&patterns, false).unwrap();
+        let cases = [
+            ("target", true),
+            ("README.md", true),
+            ("docs/README.md", false),
+            ("src/x.log", true),
+            ("keep.log", false),
+            ("a/b", true),
+        ];
+        for (path, expected) in cases {
+            let path = path::Path::new(path);
+            let is_match = patterns.is_match(path.file_name().unwrap(),
+                                             Some(path));
+            assert_eq!(is_match, expected, "{}", path.display());
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+}
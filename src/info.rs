@@ -0,0 +1,93 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::fs;
+use std::path;
+use std::vec;
+
+// name of the per-directory description file read in info mode
+pub const INFO_FILE_NAME: &str = ".info";
+
+// the parsed contents of an info file, each rule pairs a glob pattern with the
+// comment that should be displayed beside entries whose name matches it
+pub struct InfoFile {
+    rules: vec::Vec<(globset::GlobMatcher, String)>,
+}
+
+impl InfoFile {
+    // read and parse the info file at path, returning None if the file is
+    // missing, unreadable, or malformed so that callers can simply skip it
+    pub fn load(path: &path::Path) -> Option<InfoFile> {
+        let contents = fs::read_to_string(path).ok()?;
+        InfoFile::parse(&contents)
+    }
+
+    // each non-blank line that doesn't start with a '#' must be of the form
+    // `pattern<TAB>comment`, any line that isn't makes the whole file invalid
+    pub fn parse(contents: &str) -> Option<InfoFile> {
+        let mut rules = vec::Vec::new();
+        for line in contents.lines() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pattern, comment) = line.split_once('\t')?;
+            let comment = comment.trim();
+            if pattern.is_empty() || comment.is_empty() {
+                return None;
+            }
+            let matcher = globset::Glob::new(pattern).ok()?.compile_matcher();
+            rules.push((matcher, String::from(comment)));
+        }
+        Some(InfoFile { rules })
+    }
+
+    // returns the comment of the first rule whose pattern matches name
+    pub fn comment(&self, name: &str) -> Option<&str> {
+        self.rules.iter()
+            .find(|(matcher, _)| matcher.is_match(name))
+            .map(|(_, comment)| comment.as_str())
+    }
+}
+
+// greedily split text on whitespace into lines no wider than width, words that
+// are longer than width on their own are kept intact on a line of their own
+pub fn wrap(text: &str, width: usize) -> vec::Vec<String> {
+    let mut lines = vec::Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty()
+            && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,415 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event;
+use ratatui::crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::{execute, terminal};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Cell, Paragraph, Row, Table, TableState};
+
+use std::io;
+use std::panic;
+use std::path;
+use std::vec;
+
+use crate::format;
+use crate::ignores;
+use crate::{Options, Source};
+
+// what a row of the browser refers to, unreadable rows are placeholders shown
+// in place of the children of a directory that couldn't be read
+#[derive(PartialEq)]
+enum Kind {
+    Dir,
+    File,
+    Symlink,
+    Other,
+    Unreadable,
+}
+
+// one visible line of the browser, the prefix has the same meaning as in
+// walk::Node: one bool per level recording wether the entry at that level
+// was the last of its siblings
+struct Node {
+    name: String,
+    path: path::PathBuf,
+    kind: Kind,
+    size: Option<u64>,
+    prefix: vec::Vec<bool>,
+    expanded: bool,
+    // wether it's highlighted by --changed-within
+    changed: bool,
+}
+
+// the browser is drawn on stderr so that stdout only ever has the path that
+// was picked, which lets it be used as cd "$(tree -i)"
+type Terminal = ratatui::Terminal<CrosstermBackend<io::Stderr>>;
+
+// runs the interactive browser rooted at path until the user quits, the
+// terminal is restored before returning even if drawing fails or something
+// panics; returns the path of the selected entry if the user picked one
+pub fn run(path: &path::Path, name: &str, options: &Options)
+    -> io::Result<Option<path::PathBuf>> {
+    let mut nodes = vec::Vec::from([Node {
+        name: String::from(name),
+        path: path.to_path_buf(),
+        kind: Kind::Dir,
+        size: None,
+        prefix: vec::Vec::new(),
+        expanded: false,
+        changed: false,
+    }]);
+    expand(&mut nodes, 0, options);
+
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore();
+        hook(info);
+    }));
+    let result = init().and_then(|mut terminal| {
+        event_loop(&mut terminal, &mut nodes, options)
+    });
+    restore();
+    result
+}
+
+fn init() -> io::Result<Terminal> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stderr(), terminal::EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(io::stderr()))
+}
+
+// errors are ignored since there's nothing left to do about them by the time
+// the terminal is being put back
+fn restore() {
+    let _ = terminal::disable_raw_mode();
+    let _ = execute!(io::stderr(), terminal::LeaveAlternateScreen);
+}
+
+fn event_loop(terminal: &mut Terminal, nodes: &mut vec::Vec<Node>,
+              options: &Options) -> io::Result<Option<path::PathBuf>> {
+    let mut state = TableState::default().with_selected(0);
+    let mut page_height = 1;
+    // the search being typed, if any, and the last one that was run
+    let mut prompt: Option<String> = None;
+    let mut query = String::new();
+
+    loop {
+        // drawing again after any event, including a resize, lays the rows
+        // out for the current size of the terminal
+        terminal.draw(|frame| {
+            let [pane, status] = Layout::vertical([
+                Constraint::Min(1),
+                Constraint::Length(prompt.is_some() as u16),
+            ]).areas(frame.area());
+            // the header takes up one line of the pane
+            page_height = (pane.height as usize).saturating_sub(1).max(1);
+            frame.render_stateful_widget(table(nodes, options), pane,
+                                         &mut state);
+            if let Some(text) = &prompt {
+                frame.render_widget(Paragraph::new(format!("/{}", text)),
+                                    status);
+            }
+        })?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        let selected = state.selected().unwrap_or(0);
+        let last = nodes.len()-1;
+
+        // while typing a search keys edit it instead of moving around
+        if let Some(text) = &mut prompt {
+            match key.code {
+                KeyCode::Enter => {
+                    query = prompt.take().unwrap_or_default();
+                    state.select(Some(search(nodes, selected, &query, true)));
+                },
+                KeyCode::Esc => prompt = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                },
+                KeyCode::Char(c) => text.push(c),
+                _ => {},
+            }
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c')
+                if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(None);
+            },
+            KeyCode::Char('y') => {
+                return Ok(Some(nodes[selected].path.clone()));
+            },
+            KeyCode::Char('/') => prompt = Some(String::new()),
+            KeyCode::Char('n') => {
+                state.select(Some(search(nodes, selected, &query, true)));
+            },
+            KeyCode::Char('N') => {
+                state.select(Some(search(nodes, selected, &query, false)));
+            },
+            KeyCode::Up | KeyCode::Char('k') => {
+                state.select(Some(selected.saturating_sub(1)));
+            },
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.select(Some((selected+1).min(last)));
+            },
+            KeyCode::PageUp => {
+                state.select(Some(selected.saturating_sub(page_height)));
+            },
+            KeyCode::PageDown => {
+                state.select(Some((selected+page_height).min(last)));
+            },
+            KeyCode::Home | KeyCode::Char('g') => state.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => state.select(Some(last)),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if nodes[selected].expanded {
+                    collapse(nodes, selected);
+                } else {
+                    expand(nodes, selected, options);
+                }
+            },
+            // right opens a directory and then steps into it, left closes it
+            // and then steps out to its parent
+            KeyCode::Right | KeyCode::Char('l') => {
+                if !nodes[selected].expanded {
+                    expand(nodes, selected, options);
+                } else if selected < last
+                    && nodes[selected+1].prefix.len()
+                        > nodes[selected].prefix.len() {
+                    state.select(Some(selected+1));
+                }
+            },
+            KeyCode::Left | KeyCode::Char('h') => {
+                if nodes[selected].expanded {
+                    collapse(nodes, selected);
+                } else {
+                    state.select(Some(parent(nodes, selected)));
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+// the index of the next row after start, or the previous one before it when
+// not going forward, whose name contains the query ignoring case, wrapping
+// around the ends; only rows that are visible are searched since the
+// contents of collapsed directories haven't been loaded, start is returned
+// if nothing matches
+fn search(nodes: &[Node], start: usize, query: &str, forward: bool) -> usize {
+    if query.is_empty() {
+        return start;
+    }
+    let query = query.to_lowercase();
+    let count = nodes.len();
+    (1..=count)
+        .map(|offset| if forward {
+            (start+offset) % count
+        } else {
+            (start+count-offset) % count
+        })
+        .find(|index| nodes[*index].name.to_lowercase().contains(&query))
+        .unwrap_or(start)
+}
+
+// the index of the directory that the row at index is in, the root is it's
+// own parent
+fn parent(nodes: &[Node], index: usize) -> usize {
+    let depth = nodes[index].prefix.len();
+    nodes[..index].iter()
+        .rposition(|node| node.prefix.len() < depth)
+        .unwrap_or(index)
+}
+
+// lazily loads the children of the directory at index and inserts them right
+// after it, does nothing if the node isn't a directory
+fn expand(nodes: &mut vec::Vec<Node>, index: usize, options: &Options) {
+    if nodes[index].kind != Kind::Dir {
+        return;
+    }
+
+    let parent = &nodes[index];
+    let mut children = vec::Vec::new();
+    let source = Source::Path(parent.path.clone());
+    let dirs: vec::Vec<&path::Path> = nodes[index].path.ancestors()
+        .take(nodes[index].prefix.len()+1)
+        .collect();
+    let base_rules = match options.ignore_sources {
+        Some(sources) if sources.files => {
+            ignores::Rules::global(dirs[dirs.len()-1])
+        },
+        _ => ignores::Rules::none(),
+    };
+    let listing = with_rules(&dirs, &base_rules, options, &mut |rules| {
+        crate::list(&source, rules, &options.filter, &options.sort,
+                    options.show_files)
+    });
+    match listing {
+        Ok(entries) => {
+            let entries_count = entries.len();
+            for (i, entry) in entries.into_iter().enumerate() {
+                let kind = match entry.kind {
+                    crate::Kind::Directory => Kind::Dir,
+                    crate::Kind::File => Kind::File,
+                    crate::Kind::Symlink => Kind::Symlink,
+                    crate::Kind::Other => Kind::Other,
+                };
+                let size = match kind {
+                    Kind::File => entry.meta().size,
+                    _ => None,
+                };
+                let changed = options.changed.as_ref()
+                    .is_some_and(|(since, _)| {
+                        crate::is_changed(&entry, *since)
+                    });
+                let path = match entry.source() {
+                    Source::Path(path) => path,
+                    Source::Node(_) | Source::Roots(_) => unreachable!(),
+                };
+                let mut prefix = parent.prefix.clone();
+                prefix.push(i == entries_count-1);
+                children.push(Node {
+                    name: entry.name.to_string_lossy().into_owned(),
+                    path,
+                    kind,
+                    size,
+                    prefix,
+                    expanded: false,
+                    changed,
+                });
+            }
+        },
+        Err(_) => {
+            let mut prefix = parent.prefix.clone();
+            prefix.push(true);
+            children.push(Node {
+                name: String::from("<CONTENTS UNREADABLE>"),
+                path: parent.path.clone(),
+                kind: Kind::Unreadable,
+                size: None,
+                prefix,
+                expanded: false,
+                changed: false,
+            });
+        },
+    }
+
+    nodes[index].expanded = true;
+    nodes.splice(index+1..index+1, children);
+}
+
+// calls f with the ignore rules for the contents of the first of dirs, which
+// are built the same way as when printing by layering the ignore files of
+// each directory from the root, the last of dirs, down to it; the browser
+// doesn't keep them around so they're loaded again on every expansion
+fn with_rules<T>(dirs: &[&path::Path], rules: &ignores::Rules,
+                 options: &Options, f: &mut dyn FnMut(&ignores::Rules) -> T)
+    -> T {
+    match dirs.split_last() {
+        Some((dir, rest)) => {
+            let source = Source::Path(dir.to_path_buf());
+            let rules = crate::child_rules(&source, rules, options);
+            with_rules(rest, &rules, options, f)
+        },
+        None => f(rules),
+    }
+}
+
+// removes every descendant of the node at index from the visible rows
+fn collapse(nodes: &mut vec::Vec<Node>, index: usize) {
+    let depth = nodes[index].prefix.len();
+    let end = nodes[index+1..].iter()
+        .position(|node| node.prefix.len() <= depth)
+        .map_or(nodes.len(), |offset| index+1+offset);
+    nodes.drain(index+1..end);
+    nodes[index].expanded = false;
+}
+
+// builds the table widget for the currently visible rows, with the tree
+// connectors and name in the first column followed by the type and size
+fn table<'a>(nodes: &'a [Node], options: &Options) -> Table<'a> {
+    let format_str = &options.format_str;
+    let rows = nodes.iter().map(|node| {
+        let mut line = String::new();
+        let max_depth = node.prefix.len().saturating_sub(1);
+        for (i, last_entry) in node.prefix.iter().enumerate() {
+            if i == max_depth {
+                if *last_entry {
+                    line.push_str(&format_str[0]);
+                } else {
+                    line.push_str(&format_str[1]);
+                }
+            } else if *last_entry {
+                line.push_str(&format_str[2]);
+            } else {
+                line.push_str(&format_str[3]);
+            }
+        }
+        line.push_str(&node.name);
+
+        let kind = match node.kind {
+            Kind::Dir => "dir",
+            Kind::File => "file",
+            Kind::Symlink => "link",
+            Kind::Other => "othr",
+            Kind::Unreadable => "",
+        };
+        let size = node.size.map(format::human_size).unwrap_or_default();
+        let style = match &options.changed {
+            Some((_, code)) if node.changed => style(code),
+            _ => Style::new(),
+        };
+
+        Row::new([
+            Cell::from(line).style(style),
+            Cell::from(kind),
+            Cell::from(Line::from(size).right_aligned()),
+        ])
+    });
+
+    let widths = [Constraint::Min(10), Constraint::Length(4),
+                  Constraint::Length(8)];
+    Table::new(rows, widths)
+        .header(Row::new(["Name", "Type", "Size"])
+            .style(Style::new().add_modifier(Modifier::BOLD)))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+// the style matching one of the escape codes from format::color_code, or the
+// bold used when no color is given
+fn style(code: &str) -> Style {
+    match code.strip_prefix('3').and_then(|color| color.parse().ok()) {
+        Some(color) => Style::new().fg(Color::Indexed(color)),
+        None => Style::new().add_modifier(Modifier::BOLD),
+    }
+}
@@ -0,0 +1,5552 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+mod api;
+mod diff;
+mod duplicates;
+mod format;
+#[cfg(feature = "git")]
+mod git;
+mod gnu;
+mod ignores;
+mod info;
+mod interactive;
+mod logger;
+mod mime;
+mod mounts;
+mod ndjson;
+#[cfg(all(target_os = "linux", feature = "openat"))]
+mod openat;
+mod snapshot;
+mod sort;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod walk;
+
+pub use api::{render, render_to, scan, walk};
+pub use api::{EntryInfo, TreeNode, TreeOptions, Visitor};
+#[cfg(feature = "async")]
+pub use api::scan_async;
+pub use walk::{Item, Stats, TreeIter, WalkAction};
+
+use color_eyre::eyre::Result;
+use ratatui::crossterm::terminal;
+use rayon::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+use unicode_width::UnicodeWidthStr;
+
+use std::borrow;
+use std::collections;
+use std::env;
+use std::ffi;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::iter;
+use std::num;
+use std::ops;
+use std::path;
+use std::process;
+use std::sync;
+use std::sync::atomic;
+use std::thread;
+use std::time;
+use std::vec;
+
+const VERSION: &str = "1.1.1";
+
+// the kinds of entries that can appear in a tree, other covers special files
+// like sockets, fifos, and devices
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Directory,
+    File,
+    Symlink,
+    Other,
+}
+
+impl Kind {
+    // the name of the kind as it appears in output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Directory => "directory",
+            Kind::File => "file",
+            Kind::Symlink => "symlink",
+            Kind::Other => "other",
+        }
+    }
+}
+
+// what's gathered about the listed entries while traversing, for the modes
+// that print a summary instead of the tree: totals by kind, the paths and
+// sizes of regular files when looking for duplicates, and the database rows
+// are written to when exporting
+#[derive(Default)]
+struct Summary {
+    directories: u64,
+    files: u64,
+    symlinks: u64,
+    // the total size of the files, as --count gives it
+    bytes: u64,
+    // the symlinks that point to directories, these are only counted with
+    // --gnu
+    directory_links: u64,
+    other: u64,
+    regular_files: vec::Vec<(path::PathBuf, u64)>,
+    // the directories with nothing in them, with --show-empty
+    empty_dirs: u64,
+    // the mount points -x didn't descend into, relative to the root
+    mount_points: vec::Vec<path::PathBuf>,
+    #[cfg(feature = "sqlite")]
+    export: Option<sqlite::Export>,
+}
+
+impl Summary {
+    // rel_path is the path of the directory the entry is in, relative to the
+    // root of the tree
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+    fn add(&mut self, entry: &Entry, rel_path: &path::Path,
+           options: &Options) {
+        match entry.kind {
+            Kind::Directory => self.directories += 1,
+            Kind::File => self.files += 1,
+            Kind::Symlink => self.symlinks += 1,
+            Kind::Other => self.other += 1,
+        }
+        // the rest is only looked at by some options, which the metadata and
+        // path of every entry aren't worth reading for otherwise
+        if entry.kind == Kind::File && options.count_only {
+            self.bytes += entry.meta().size.unwrap_or(0);
+        }
+        if entry.kind == Kind::Symlink && options.gnu {
+            if let Source::Path(path) = entry.source() {
+                self.directory_links += path.is_dir() as u64;
+            }
+        }
+        if entry.kind == Kind::File && options.duplicates {
+            if let (Source::Path(path), Some(size))
+                = (entry.source(), entry.meta().size) {
+                self.regular_files.push((path, size));
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(export) = &mut self.export {
+            let path = slash_path(&rel_path.join(&entry.name));
+            let parent = slash_path(rel_path);
+            let meta = entry.meta();
+            if let Err(error) = export.insert(&path, Some(&parent), entry.kind,
+                                              meta.size, meta.mtime) {
+                export_failed(options, error);
+            }
+        }
+    }
+}
+
+// settings that control how the tree is traversed and displayed, these are
+// determined once from the command args and then passed down the recursion
+struct Options {
+    show_files: bool,
+    // wether entries are printed without any connectors
+    flat: bool,
+    // the connectors for the last entry of a directory, for the other
+    // entries, and for continuing past a level whose last entry was or
+    // wasn't reached yet
+    format_str: vec::Vec<String>,
+    info: bool,
+    global_info: Option<info::InfoFile>,
+    width: usize,
+    line_numbers: bool,
+    format: Option<format::Template>,
+    sort: sort::Sort,
+    filter: Filter,
+    prune: bool,
+    max_depth: Option<usize>,
+    // the device of the root with -x, directories on any other device are
+    // mount points that aren't descended into
+    root_device: Option<u64>,
+    // the filesystem types whose mount points aren't descended into either
+    exclude_fstypes: vec::Vec<String>,
+    // wether directories that don't list anything are marked as empty or as
+    // having their contents hidden
+    show_empty: bool,
+    // wether the list of those mount points, and the report of --gnu, is
+    // left out
+    no_report: bool,
+    // wether the tree is printed the way gnu tree prints it, with --gnu
+    gnu: bool,
+    ellipsis: &'static str,
+    ignore_sources: Option<ignores::Sources>,
+    count_only: bool,
+    porcelain: bool,
+    duplicates: bool,
+    summarize: bool,
+    // wether directories are shown with the totals of the files beneath them
+    du: bool,
+    // wether directories are printed after their contents rather than before
+    post_order: bool,
+    // what the size bars of entries are scaled to, if they're drawn
+    bars: Option<BarScale>,
+    // wether --ascii was given, for what isn't drawn with the connectors
+    ascii: bool,
+    // wether directories are shown with the totals of only the files
+    // directly in them
+    shallow_size: bool,
+    // wether chains of directories that only hold one directory are shown on
+    // one line
+    collapse: bool,
+    // wether entries are printed in the order they're read, as soon as
+    // they're read
+    stream: bool,
+    quoting: format::Quoting,
+    slash: bool,
+    resolve_links: bool,
+    perms: bool,
+    perm_octal: bool,
+    xattrs: bool,
+    xattr_values: bool,
+    type_summary: bool,
+    // decides which directories are descended into, the ones it rejects are
+    // shown with the reason it gives after their name, like --no-recurse
+    // does with [...]
+    descends: Option<Hook>,
+    branch_style: Option<String>,
+    // the point after which entries count as changed and the style they're
+    // highlighted in, only set when using color
+    changed: Option<(time::SystemTime, String)>,
+    // the time ages are measured from and the age at which entries are drawn
+    // fully red with --age-color, only set when using color
+    age_color: Option<(time::SystemTime, time::Duration)>,
+    sqlite: Option<path::PathBuf>,
+    columns: bool,
+    mime: bool,
+    // the name of the file --recursive-output writes into each directory,
+    // and wether one that already exists is overwritten
+    recursive_output: Option<String>,
+    force: bool,
+    ndjson: bool,
+    json: bool,
+    // wether --json includes the extra metadata of --json-meta
+    json_meta: bool,
+    git_status: bool,
+    // wether color is used at all, for styling that's decided while printing
+    color: bool,
+    prescans: Prescans,
+}
+
+impl Options {
+    // the options of a plain tree without files, the way it's printed when
+    // no args are given
+    fn new() -> Options {
+        Options {
+            show_files: false,
+            flat: false,
+            format_str: ["└───", "├───", "    ", "│   "].iter()
+                .map(|connector| String::from(*connector))
+                .collect(),
+            info: false,
+            global_info: None,
+            width: 80,
+            line_numbers: false,
+            format: None,
+            sort: sort::Sort::new(false, false),
+            filter: Filter {
+                newer_than: None,
+                changed_within: None,
+                empty: None,
+                types: None,
+                shows: None,
+                exclude: None,
+                exclude_paths: vec::Vec::new(),
+                context: None,
+                strict: false,
+                dotfiles: true,
+                root: None,
+                #[cfg(feature = "git")]
+                tracked: None,
+            },
+            prune: false,
+            max_depth: None,
+            root_device: None,
+            exclude_fstypes: vec::Vec::new(),
+            show_empty: false,
+            no_report: false,
+            gnu: false,
+            ellipsis: "…",
+            ignore_sources: None,
+            count_only: false,
+            porcelain: false,
+            duplicates: false,
+            summarize: false,
+            du: false,
+            post_order: false,
+            bars: None,
+            ascii: false,
+            shallow_size: false,
+            collapse: false,
+            stream: false,
+            quoting: format::Quoting::Plain,
+            slash: false,
+            resolve_links: false,
+            perms: false,
+            perm_octal: false,
+            xattrs: false,
+            xattr_values: false,
+            type_summary: false,
+            descends: None,
+            branch_style: None,
+            changed: None,
+            age_color: None,
+            sqlite: None,
+            columns: false,
+            mime: false,
+            recursive_output: None,
+            force: false,
+            ndjson: false,
+            json: false,
+            json_meta: false,
+            git_status: false,
+            color: false,
+            prescans: Prescans::default(),
+    }
+    }
+
+    // wether the tree itself is printed, rather than just a summary of it
+    fn prints_tree(&self) -> bool {
+        !self.count_only && !self.duplicates && self.sqlite.is_none()
+            && !self.ndjson && !self.json
+    }
+
+    // wether anything looks at the metadata of the files in the tree, rather
+    // than only at their names and kinds
+    fn uses_file_metadata(&self) -> bool {
+        let filter = &self.filter;
+        self.info || self.format.is_some() || self.sort.uses_metadata()
+            || filter.newer_than.is_some() || filter.changed_within.is_some()
+            || filter.empty.is_some() || filter.types.is_some() || self.gnu
+            || self.count_only || self.duplicates || self.summarize
+            || self.du || self.bars.is_some() || self.shallow_size
+            || self.perms || self.perm_octal || self.type_summary
+            || self.changed.is_some() || self.age_color.is_some()
+            || self.sqlite.is_some() || self.ndjson || self.json || self.color
+    }
+}
+
+// set once ctrl-c is pressed, the traversal checks it before every directory
+// and entry
+static INTERRUPTED: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+// wether ctrl-c has been pressed
+fn is_interrupted() -> bool {
+    INTERRUPTED.load(atomic::Ordering::SeqCst)
+}
+
+// exits with the status shells use for a process ended by SIGINT, after
+// finishing what's been printed so that the output ends on a whole line
+fn exit_interrupted(stdout: &mut Stdout) -> ! {
+    stdout.finish();
+    std::process::exit(130);
+}
+
+// numbers are padded to at least this many digits and grow past it when a
+// tree is longer
+const LINE_NUMBER_WIDTH: usize = 4;
+
+// stdout as everything is written to it, possibly through a pager; a write
+// to it that fails ends the process, see failed
+struct Stdout {
+    sink: Sink,
+}
+
+// where the output of Stdout goes
+enum Sink {
+    // stdout itself when it's a terminal, which is line buffered so that
+    // each line shows up as soon as it's printed
+    Terminal(io::Stdout),
+    // stdout when it isn't a terminal, locked for the whole run and behind a
+    // buffer so that a line doesn't take a lock and a write of it's own
+    Buffered(io::BufWriter<io::StdoutLock<'static>>),
+    // the pager the output is piped through
+    Pager(process::Child, io::LineWriter<process::ChildStdin>),
+    // what's been printed so far when the pager is only used for output that
+    // doesn't fit in the terminal, along with how many lines that is and how
+    // many lines do fit
+    Pending(vec::Vec<u8>, usize, usize),
+}
+
+impl Stdout {
+    // stdout as it's written to without a pager
+    fn new() -> Stdout {
+        let sink = if io::stdout().is_terminal() {
+            Sink::Terminal(io::stdout())
+        } else {
+            Sink::Buffered(io::BufWriter::new(io::stdout().lock()))
+        };
+        Stdout { sink }
+    }
+
+    // stdout piped through the pager, or as it is if that can't be started
+    fn paged() -> Stdout {
+        start_pager().map_or_else(Stdout::new, |sink| Stdout { sink })
+    }
+
+    // stdout that holds back what's printed until it's more than height
+    // lines, and only then starts the pager
+    fn pending(height: usize) -> Stdout {
+        Stdout { sink: Sink::Pending(vec::Vec::new(), 0, height) }
+    }
+
+    // writes a line that isn't part of a tree, like the blank line between
+    // two trees
+    fn line(&mut self, text: &str) {
+        if let Err(error) = writeln!(self, "{}", text) {
+            self.failed(error);
+        }
+    }
+
+    // ends the process after a write failed; the pager is only closed early
+    // by the user quitting it and stdout by whatever it's piped into having
+    // read all it wants, like head, at which point there's nothing left to
+    // print, any other failure is an error
+    fn failed(&mut self, error: io::Error) -> ! {
+        if let Sink::Pager(child, _) = &mut self.sink {
+            let _ = child.wait();
+            std::process::exit(0);
+        }
+        if error.kind() == io::ErrorKind::BrokenPipe {
+            std::process::exit(0);
+        }
+        eprintln!("ERROR: unable to write the output: {}", error);
+        std::process::exit(1);
+    }
+
+    // prints anything that's still held back and waits for the user to quit
+    // the pager, once everything has been printed
+    fn finish(&mut self) {
+        let unpaged = Sink::Terminal(io::stdout());
+        let result = match std::mem::replace(&mut self.sink, unpaged) {
+            Sink::Pager(mut child, stdin) => {
+                drop(stdin);
+                let _ = child.wait();
+                Ok(())
+            },
+            Sink::Pending(held, _, _) => {
+                self.write_all(&held).and_then(|_| self.flush())
+            },
+            Sink::Buffered(mut stdout) => stdout.flush(),
+            Sink::Terminal(mut stdout) => stdout.flush(),
+        };
+        if let Err(error) = result {
+            self.failed(error);
+        }
+    }
+
+    // finishes the output and exits with the given status
+    fn exit(&mut self, code: i32) -> ! {
+        self.finish();
+        std::process::exit(code);
+    }
+}
+
+impl io::Write for Stdout {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let (held, lines, height) = match &mut self.sink {
+            Sink::Terminal(stdout) => return stdout.write(bytes),
+            Sink::Buffered(stdout) => return stdout.write(bytes),
+            Sink::Pager(_, stdin) => return stdin.write(bytes),
+            Sink::Pending(held, lines, height) => (held, lines, height),
+        };
+        held.extend_from_slice(bytes);
+        *lines += bytes.iter().filter(|byte| **byte == b'\n').count();
+        if *lines >= *height {
+            let held = std::mem::take(held);
+            self.sink = start_pager()
+                .unwrap_or_else(|| Sink::Terminal(io::stdout()));
+            self.write_all(&held)?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::Terminal(stdout) => stdout.flush(),
+            Sink::Buffered(stdout) => stdout.flush(),
+            Sink::Pager(_, stdin) => stdin.flush(),
+            Sink::Pending(..) => Ok(()),
+        }
+    }
+}
+
+// the output is finished when run returns, wether it ended with an error or
+// not, so that the tree isn't left in the pager's buffer
+impl Drop for Stdout {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+// starts $PAGER, or less -R if it isn't set, for the output to be piped
+// through; less is told to pass color through and to quit right away if
+// everything fits on one screen unless $LESS says otherwise, and if the pager
+// can't be started the output is printed directly
+fn start_pager() -> Option<Sink> {
+    let pager = env::var("PAGER").ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| String::from("less -R"));
+    let mut words = pager.split_whitespace();
+    let mut command = process::Command::new(words.next()?);
+    command.args(words).stdin(process::Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    match command.spawn() {
+        Ok(mut child) => {
+            let stdin = child.stdin.take()?;
+            Some(Sink::Pager(child, io::LineWriter::new(stdin)))
+        },
+        Err(error) => {
+            eprintln!("ERROR: \"{}\" {}", pager, error);
+            None
+        },
+    }
+}
+
+// the lines of a tree as they're printed into writer, which is stdout, a
+// file written by --recursive-output, or any other sink; once a write fails
+// nothing more is written, and the error is what printing the tree ends with
+struct Output<W: io::Write> {
+    writer: W,
+    // wether lines are written without the escape codes for color, like the
+    // files of --recursive-output are
+    plain: bool,
+    error: Option<io::Error>,
+    // the number of lines printed so far, for --line-numbers, the tree is
+    // printed as it's walked so the total isn't known up front
+    line_number: usize,
+    // the buffer each line of the tree is put together in, it's cleared
+    // rather than dropped between lines so that once it's grown to fit
+    // printing a line doesn't allocate
+    line: String,
+    // the lines held back with --columns, which can only be printed once the
+    // widest value of each column is known, or with --post-order, which can
+    // only be printed once they've been put in order
+    rows: Option<vec::Vec<Row>>,
+    // the text of the held rows, one after the other
+    held: String,
+}
+
+// a line of the tree held in Output::held, the columns are the metadata
+// printed between it's gutter and the rest of it, if any
+struct Row {
+    line: ops::Range<usize>,
+    // where the gutter ends and the tree starts
+    tree: usize,
+    columns: vec::Vec<String>,
+}
+
+impl<W: io::Write> Output<W> {
+    fn new(writer: W) -> Output<W> {
+        Output {
+            writer,
+            plain: false,
+            error: None,
+            line_number: 0,
+            line: String::new(),
+            rows: None,
+            held: String::new(),
+        }
+    }
+
+    // the number of rows held so far
+    fn row_count(&self) -> usize {
+        self.rows.as_ref().map_or(0, vec::Vec::len)
+    }
+
+    // moves held rows after every row that was held after them, which is how
+    // --post-order puts a directory beneath it's contents
+    fn move_rows_to_end(&mut self, rows: ops::Range<usize>) {
+        if let Some(held) = self.rows.as_mut() {
+            held[rows.start..].rotate_left(rows.len());
+        }
+    }
+
+    // prints a line of the tree after it's line number, or holds onto it
+    // with --columns; build puts the line together in the reused buffer, and
+    // what it returns is returned
+    fn emit_line<R>(&mut self, columns: vec::Vec<String>, options: &Options,
+                    build: impl FnOnce(&mut String) -> R) -> R {
+        let mut line = std::mem::take(&mut self.line);
+        line.clear();
+        self.push_gutter(&mut line, options);
+        let tree = line.len();
+        let result = build(&mut line);
+        match self.rows.as_mut() {
+            Some(rows) => {
+                let start = self.held.len();
+                self.held.push_str(&line);
+                rows.push(Row {
+                    line: start..self.held.len(),
+                    tree: start + tree,
+                    columns,
+                });
+            },
+            None => self.write_line(&line),
+        }
+        self.line = line;
+        result
+    }
+
+    // prints the rows held by --columns, each column is right aligned to the
+    // widest value in it, and rows without columns are indented past them
+    fn flush_rows(&mut self) {
+        let rows = match self.rows.take() {
+            Some(value) => value,
+            None => return,
+        };
+        let mut widths: vec::Vec<usize> = vec::Vec::new();
+        for row in &rows {
+            for (i, column) in row.columns.iter().enumerate() {
+                let width = visible_width(column);
+                match widths.get_mut(i) {
+                    Some(widest) => *widest = (*widest).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        let held = std::mem::take(&mut self.held);
+        let mut line = std::mem::take(&mut self.line);
+        for row in rows {
+            line.clear();
+            line.push_str(&held[row.line.start..row.tree]);
+            for (i, width) in widths.iter().enumerate() {
+                let column = row.columns.get(i).map_or("", String::as_str);
+                let padding = width - visible_width(column);
+                line.extend(iter::repeat_n(' ', padding));
+                line.push_str(column);
+                line.push_str(if i+1 == widths.len() { "  " } else { " " });
+            }
+            line.push_str(&held[row.tree..row.line.end]);
+            self.write_line(&line);
+        }
+        self.line = line;
+    }
+
+    // writes a line, without the escape codes for color if the output is
+    // plain
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match self.plain {
+            true => writeln!(self.writer, "{}", unstyled(line)),
+            false => writeln!(self.writer, "{}", line),
+        };
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+
+    // puts the right aligned number of the line about to be printed in line
+    // followed by a separator, like grep -n, or nothing without
+    // --line-numbers
+    fn push_gutter(&mut self, line: &mut String, options: &Options) {
+        if !options.line_numbers {
+            return;
+        }
+        self.line_number += 1;
+        let _ = fmt::Write::write_fmt(line, format_args!(
+            "{:>width$}: ", self.line_number, width = LINE_NUMBER_WIDTH));
+    }
+
+    // how many columns the gutter of the next line takes up
+    fn gutter_width(&self, options: &Options) -> usize {
+        if !options.line_numbers {
+            return 0;
+        }
+        (self.line_number+1).to_string().len().max(LINE_NUMBER_WIDTH) + 2
+    }
+
+    // prints the rows that are still held back, and returns the error of the
+    // write that failed if one did
+    fn finish(mut self) -> io::Result<()> {
+        self.flush_rows();
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// Why scanning, walking, or printing a tree stopped short: reading the tree
+/// or writing it out failed, or ctrl-c was pressed, which only the command
+/// listens for.
+#[derive(Debug)]
+pub enum TreeError {
+    Traversal(TraversalError),
+    Write(io::Error),
+    Interrupted,
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::Traversal(error) => error.fmt(f),
+            TreeError::Write(error) => {
+                write!(f, "couldn't write the tree: {}", error)
+            },
+            TreeError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TreeError::Traversal(error) => Some(error),
+            TreeError::Write(error) => Some(error),
+            TreeError::Interrupted => None,
+        }
+    }
+}
+
+impl From<TraversalError> for TreeError {
+    fn from(error: TraversalError) -> TreeError {
+        TreeError::Traversal(error)
+    }
+}
+
+/// Runs the tree command with the args of the process, printing to stdout;
+/// this is all the binary does.
+pub fn run() -> Result<()> {
+    color_eyre::install()?;
+    if env::var("RUST_SPANTRACE").is_err() {
+        env::set_var("RUST_SPANTRACE", "0");
+    }
+
+    // define and parse command args using clap library
+    let command = clap::Command::new("tree")
+        .author("Jenna Fligor <jenna@fligor.net>")
+        .version(VERSION)
+        .about("\nGraphically displays the directory structure of a path")
+        .after_help("Exits with 0 when the tree was printed, 1 when it \
+                     couldn't be, and 130 when ctrl-c was pressed, in which \
+                     case the tree is printed up to where it got; --strict, \
+                     --diff-snapshot and --first-only give other statuses \
+                     too, see their help")
+        .arg(clap::Arg::new("path")
+            .takes_value(true)
+            .multiple_values(true)
+            .help("paths to root directories of trees (defaults to current \
+                   directory), quoted glob patterns like \"src/*\" are \
+                   expanded to the matching directories"))
+        .arg(clap::Arg::new("files")
+            .short('f')
+            .long("files")
+            .takes_value(false)
+            .help("Displays the names of the files in each directory"))
+        .arg(clap::Arg::new("level")
+            .short('L')
+            .long("level")
+            .takes_value(true)
+            .value_name("level")
+            .help("Descends at most level directories deep, directories with \
+                   contents past the limit are shown with an ellipsis"))
+        .arg(clap::Arg::new("ascii")
+            .short('a')
+            .long("ascii")
+            .takes_value(false)
+            .help("Uses ASCII instead of extended characters"))
+        .arg(clap::Arg::new("style")
+            .long("style")
+            .takes_value(true)
+            .value_name("style")
+            .possible_values(STYLES.map(|(name, _)| name))
+            .help("Draws the connectors with plain, rounded, double, or \
+                   heavy lines, plain by default; with --ascii this is \
+                   ignored, and connectors given on their own are drawn as \
+                   they're given"))
+        .arg(clap::Arg::new("list-styles")
+            .long("list-styles")
+            .takes_value(false)
+            .help("Prints a sample of each --style and exits"))
+        .arg(clap::Arg::new("gnu")
+            .long("gnu")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "type-summary",
+                                  "resolve-links"])
+            .help("Prints the tree the way GNU tree 2.x does, for scripts \
+                   that read its output: the connectors are drawn like \
+                   its own, files are shown unless -d is given and dotfiles \
+                   unless -a is, the roots are shown as they were given, \
+                   symlinks with where they point, and the tree ends with a \
+                   report like \"3 directories, 5 files\" unless \
+                   --noreport is given; -a, -d, -i, -v, -n and -C mean what \
+                   they do in GNU tree, flags of it that don't exist here \
+                   are an error, and it exits with 2 if a directory \
+                   couldn't be opened"))
+        .arg(clap::Arg::new("conn-last")
+            .long("conn-last")
+            .takes_value(true)
+            .value_name("text")
+            .help("Draws the connector in front of the last entry of a \
+                   directory with text instead, └─── by default"))
+        .arg(clap::Arg::new("conn-tee")
+            .long("conn-tee")
+            .takes_value(true)
+            .value_name("text")
+            .help("Draws the connector in front of every other entry with \
+                   text instead, ├─── by default"))
+        .arg(clap::Arg::new("conn-space")
+            .long("conn-space")
+            .takes_value(true)
+            .value_name("text")
+            .help("Indents the entries beneath the last entry of a \
+                   directory with text instead, four spaces by default; it \
+                   should be as wide as --conn-last for the tree to line up"))
+        .arg(clap::Arg::new("conn-vert")
+            .long("conn-vert")
+            .takes_value(true)
+            .value_name("text")
+            .help("Draws the line continuing down past the entries beneath \
+                   any other entry with text instead, │ followed by three \
+                   spaces by default; it should be as wide as --conn-tee"))
+        .arg(clap::Arg::new("info")
+            .long("info")
+            .takes_value(false)
+            .help("Displays comments from .info files beside matching \
+                   entries"))
+        .arg(clap::Arg::new("infofile")
+            .long("infofile")
+            .takes_value(true)
+            .value_name("path")
+            .help("Reads comments from the given info file in addition to \
+                   each directory's .info file (implies --info)"))
+        .arg(clap::Arg::new("interactive")
+            .short('i')
+            .long("interactive")
+            .takes_value(false)
+            .help("Browses the tree interactively, use the arrow keys to \
+                   move, enter or right and left to expand or collapse a \
+                   directory, / to search the visible entries, n and N for \
+                   the next and previous match, q to quit, and y to quit and \
+                   print the path of the selected entry"))
+        .arg(clap::Arg::new("recursive-output")
+            .long("recursive-output")
+            .takes_value(true)
+            .value_name("filename")
+            .conflicts_with_all(&["interactive", "load", "save", "count",
+                                  "duplicates", "sqlite", "ndjson"])
+            .help("Also writes the tree of every directory beneath the root, \
+                   including the root itself, into a file with the given name \
+                   in that directory; -L limits the depth of each of these \
+                   trees, and the files are left out of every listing"))
+        .arg(clap::Arg::new("force")
+            .long("force")
+            .takes_value(false)
+            .requires("recursive-output")
+            .help("Overwrites existing files with --recursive-output"))
+        .arg(clap::Arg::new("quiet")
+            .long("quiet")
+            .takes_value(false)
+            .requires("recursive-output")
+            .help("Doesn't print the tree of the root with \
+                   --recursive-output, only writes the files"))
+        .arg(clap::Arg::new("save")
+            .long("save")
+            .takes_value(true)
+            .value_name("file")
+            .conflicts_with("interactive")
+            .help("Saves a snapshot of the whole tree (names, types, sizes, \
+                   and modification times) to file"))
+        .arg(clap::Arg::new("load")
+            .long("load")
+            .alias("from-json")
+            .takes_value(true)
+            .value_name("file")
+            .conflicts_with_all(&["path", "interactive", "save"])
+            .help("Displays the tree saved in a snapshot file, written by \
+                   --save or --json, instead of reading the filesystem, or \
+                   the tree of a document written by GNU tree's -J; the \
+                   file is read from stdin when it's -, and --from-json is \
+                   another name for this"))
+        .arg(clap::Arg::new("diff-snapshot")
+            .long("diff-snapshot")
+            .takes_value(true)
+            .value_name("file")
+            .conflicts_with_all(&["load", "save", "interactive", "json",
+                                  "json-meta", "ndjson", "sqlite", "count",
+                                  "duplicates", "summarize", "type-summary",
+                                  "collapse"])
+            .help("Compares the tree against a snapshot file, read like \
+                   --load does, and prints only what differs: + for entries \
+                   that were added, - for ones that were removed, ~ for ones \
+                   whose type changed, or whose size or modification time \
+                   did when the snapshot has them, and ? for entries of the \
+                   snapshot that are hidden by the filter or the depth limit \
+                   and so weren't compared; exits with 1 when anything \
+                   differs and 0 otherwise"))
+        .arg(clap::Arg::new("format")
+            .long("format")
+            .takes_value(true)
+            .value_name("template")
+            .help("Displays each entry using a template instead of just its \
+                   name, the placeholders {name}, {path}, {size}, {size:h}, \
+                   {mtime}, {mtime:<strftime format>}, {perms}, {type}, and \
+                   {depth} are replaced with the entry's details, and \\t \
+                   and \\n with a tab and newline"))
+        .arg(clap::Arg::new("flat")
+            .long("flat")
+            .takes_value(false)
+            .help("Prints each entry without the connectors in front of it, \
+                   and without the placeholders for directories whose \
+                   contents aren't shown, for output that's read by other \
+                   programs, like --flat --format '{path}\\t{size}'"))
+        .arg(clap::Arg::new("relative-time")
+            .long("relative-time")
+            .takes_value(false)
+            .requires("format")
+            .help("Shows {mtime} in --format as how long ago it was, e.g. 3 \
+                   days ago, padded to a fixed width; {mtime:<strftime \
+                   format>} still shows a date"))
+        .arg(clap::Arg::new("quote")
+            .short('Q')
+            .takes_value(false)
+            .help("Wraps each name in double quotes, escaping any quotes and \
+                   backslashes in it"))
+        .arg(clap::Arg::new("shell-escape")
+            .long("shell-escape")
+            .takes_value(false)
+            .conflicts_with("quote")
+            .help("Writes each name the way a shell needs it to be typed, \
+                   single quoting any that contain special characters \
+                   (PowerShell quoting on Windows)"))
+        .arg(clap::Arg::new("slash")
+            .long("slash")
+            .takes_value(false)
+            .help("Appends a / to the name of each directory"))
+        .arg(clap::Arg::new("print-root-path")
+            .long("print-root-path")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Prints the full canonical path of the root on the first \
+                   line instead of just its name"))
+        .arg(clap::Arg::new("follow")
+            .short('l')
+            .long("follow")
+            .takes_value(false)
+            .help("Follows symlinks to directories as if they were the \
+                   directories they point to, a directory that's one of its \
+                   own ancestors is still only shown once"))
+        .arg(clap::Arg::new("max-symlinks")
+            .long("max-symlinks")
+            .takes_value(true)
+            .value_name("n")
+            .requires("follow")
+            .help("Stops following symlinks once n of them have been, \
+                   every time one is read counts, 1000 by default; the \
+                   rest are shown as symlinks with [symlink limit reached], \
+                   and the number followed is given with --count and the \
+                   type summary"))
+        .arg(clap::Arg::new("resolve-links")
+            .long("resolve-links")
+            .takes_value(false)
+            .help("Shows where each symlink points, following chains of \
+                   links to the end and marking targets that are missing \
+                   with [broken] and chains that loop with [loop]"))
+        .arg(clap::Arg::new("line-numbers")
+            .long("line-numbers")
+            .takes_value(false)
+            .help("Prefixes each line of the tree, starting with the root, \
+                   with its line number"))
+        .arg(clap::Arg::new("columns")
+            .long("columns")
+            .takes_value(false)
+            .help("Lines up the metadata of each entry, like the permissions \
+                   from -p or the sizes from --summarize, in right aligned \
+                   columns before the tree instead of in brackets after the \
+                   connectors; the tree is only printed once it's been \
+                   walked"))
+        .arg(clap::Arg::new("pager")
+            .long("pager")
+            .takes_value(false)
+            .overrides_with("no-pager")
+            .conflicts_with("interactive")
+            .help("Pipes the tree through $PAGER, or less -R if it isn't \
+                   set, by default this is only done when the tree is printed \
+                   to a terminal and doesn't fit in it"))
+        .arg(clap::Arg::new("no-pager")
+            .long("no-pager")
+            .takes_value(false)
+            .overrides_with("pager")
+            .help("Always prints the tree directly, without a pager"))
+        .arg(clap::Arg::new("mime")
+            .long("mime")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Displays the mime type of each file after its name, \
+                   recognized from the start of its contents or otherwise \
+                   from its extension; directories and other entries that \
+                   aren't files get inode types like inode/directory"))
+        .arg(clap::Arg::new("perms")
+            .short('p')
+            .takes_value(false)
+            .help("Displays the permissions of each entry, like ls -l"))
+        .arg(clap::Arg::new("git-status")
+            .long("git-status")
+            .takes_value(false)
+            .help("Displays the git status of each file, in the two letter \
+                   form of git status --short, when the tree is inside a git \
+                   repository"))
+        .arg(clap::Arg::new("git-tracked")
+            .long("git-tracked")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Only displays files that are tracked by git, directories \
+                   are still shown, combine with --prune to drop the ones \
+                   without tracked files"))
+        .arg(clap::Arg::new("perm-octal")
+            .long("perm-octal")
+            .visible_alias("protections")
+            .takes_value(false)
+            .help("Displays the permissions of each entry in octal, with \
+                   the setuid, setgid, and sticky bits in the leading digit, \
+                   e.g. 0755 or 2755; after the -p permissions when both are \
+                   shown, and as dashes where there are no unix mode bits"))
+        .arg(clap::Arg::new("xattrs")
+            .long("xattrs")
+            .takes_value(false)
+            .help("Lists the names of each entry's extended attributes \
+                   beneath it (unix only, ignored elsewhere)"))
+        .arg(clap::Arg::new("xattr-values")
+            .long("xattr-values")
+            .takes_value(false)
+            .help("Lists extended attributes along with their values, \
+                   non-printable bytes are escaped (implies --xattrs)"))
+        .arg(clap::Arg::new("color")
+            .long("color")
+            .takes_value(true)
+            .value_name("when")
+            .possible_values(["auto", "always", "never"])
+            .default_value("auto")
+            .help("Controls when color is used, auto only uses it when \
+                   writing to a terminal and NO_COLOR isn't set"))
+        .arg(clap::Arg::new("dim-branches")
+            .long("dim-branches")
+            .takes_value(false)
+            .help("Draws the tree connectors dimmed when using color"))
+        .arg(clap::Arg::new("branch-color")
+            .long("branch-color")
+            .takes_value(true)
+            .value_name("color")
+            .possible_values(format::COLORS)
+            .help("Draws the tree connectors in the given color when using \
+                   color"))
+        .arg(clap::Arg::new("sort")
+            .long("sort")
+            .takes_value(true)
+            .value_name("keys")
+            .help("Sorts entries by a comma separated list of keys (name, \
+                   mtime, ctime, size, version, extension or ext, which \
+                   compares what follows the last dot regardless of case and \
+                   puts names without one first), each optionally suffixed \
+                   with :desc, later keys only break ties between earlier \
+                   ones and name is always the final tie breaker; with \
+                   --dirsfirst the keys order entries within the directory \
+                   and file groups. Two orders can be given on their own \
+                   instead: name-mixed, the default, which sorts by name \
+                   with directories among the files and can't be combined \
+                   with --dirsfirst, and none, which keeps the order the \
+                   filesystem returns entries in. mtime is whichever \
+                   timestamp --time chose, ctime is the time the metadata \
+                   changed and is only known on unix, and -t, -r, and \
+                   --dirsfirst are shorthands that can be combined with \
+                   these"))
+        .arg(clap::Arg::new("time")
+            .long("time")
+            .takes_value(true)
+            .value_name("source")
+            .possible_values(["mtime", "ctime", "atime", "btime"])
+            .help("Chooses which timestamp {mtime}, sorting by mtime, and the \
+                   time filters use: the modification time (the default), \
+                   the change time (unix only), the access time, or the \
+                   birth time, which falls back to the modification time \
+                   where it isn't recorded"))
+        .arg(clap::Arg::new("timesort")
+            .short('t')
+            .takes_value(false)
+            .conflicts_with("sort")
+            .help("Sorts entries by modification time, oldest first (same \
+                   as --sort mtime)"))
+        .arg(clap::Arg::new("reverse")
+            .short('r')
+            .long("reverse")
+            .takes_value(false)
+            .help("Reverses the sort order"))
+        .arg(clap::Arg::new("normalize")
+            .long("normalize")
+            .takes_value(false)
+            .help("Compares names in unicode normalization form C when \
+                   matching them against patterns and when sorting, so that \
+                   accented names match and sort the same wether they're \
+                   stored composed or decomposed; names are still displayed \
+                   as they are"))
+        .arg(clap::Arg::new("locale-sort")
+            .long("locale-sort")
+            .takes_value(false)
+            .help("Compares names using the collation rules of the current \
+                   locale (LC_COLLATE) instead of byte by byte"))
+        .arg(clap::Arg::new("dirsfirst")
+            .long("dirsfirst")
+            .takes_value(false)
+            .help("Lists directories before files"))
+        .arg(clap::Arg::new("newer-than-file")
+            .long("newer-than-file")
+            .takes_value(true)
+            .value_name("path")
+            .help("Only displays files modified more recently than the given \
+                   reference file, like find's -newer"))
+        .arg(clap::Arg::new("changed-since")
+            .long("changed-since")
+            .takes_value(true)
+            .value_name("path")
+            .conflicts_with("newer-than-file")
+            .help("Only displays files modified more recently than the given \
+                   reference file and the directories they're in, like \
+                   --newer-than-file with --prune; with --time another of \
+                   the times is compared instead, that of the reference \
+                   file too"))
+        .arg(clap::Arg::new("changed-within")
+            .long("changed-within")
+            .takes_value(true)
+            .value_name("duration")
+            .help("Highlights entries modified within the given duration when \
+                   using color, a duration is a number followed by s, m, h, \
+                   d, or w, like 90m or 2d, and several can be combined like \
+                   1h30m"))
+        .arg(clap::Arg::new("changed-color")
+            .long("changed-color")
+            .takes_value(true)
+            .value_name("color")
+            .possible_values(format::COLORS)
+            .requires("changed-within")
+            .help("Highlights changed entries in the given color rather than \
+                   in bold"))
+        .arg(clap::Arg::new("age-color")
+            .long("age-color")
+            .takes_value(false)
+            .help("Colors the names of entries by their age when using \
+                   color, from green for ones modified just now through \
+                   yellow to red for ones at least --age-scale days old; \
+                   changed entries are still highlighted with \
+                   --changed-color"))
+        .arg(clap::Arg::new("age-scale")
+            .long("age-scale")
+            .takes_value(true)
+            .value_name("days")
+            .requires("age-color")
+            .help("How many days old an entry has to be for --age-color to \
+                   draw it fully red, 365 by default"))
+        .arg(clap::Arg::new("only-changed")
+            .long("only-changed")
+            .takes_value(false)
+            .requires("changed-within")
+            .help("Only displays files modified within the --changed-within \
+                   duration and the directories they're in"))
+        .arg(clap::Arg::new("empty")
+            .long("empty")
+            .takes_value(false)
+            .help("Only displays zero byte files, combine with --prune to \
+                   only display the directories that are empty or lead to \
+                   something empty; a directory is empty if no file that \
+                   passes the other filters is beneath it"))
+        .arg(clap::Arg::new("empty-dirs-only")
+            .long("empty-dirs-only")
+            .takes_value(false)
+            .conflicts_with("empty")
+            .help("Like --empty but only looks for empty directories, no \
+                   files are displayed"))
+        .arg(clap::Arg::new("strict")
+            .long("strict")
+            .takes_value(false)
+            .help("Aborts with an error naming the path on the first \
+                   directory or entry that can't be read, instead of marking \
+                   it and carrying on"))
+        .arg(clap::Arg::new("timeout")
+            .long("timeout")
+            .takes_value(true)
+            .value_name("secs")
+            .conflicts_with("stream")
+            .help("Gives up on reading a directory, along with the metadata \
+                   of its entries, once it's taken longer than secs, which \
+                   can be a fraction; such a directory's contents are shown \
+                   as [timed out], the same way as if they couldn't be read, \
+                   and the directories that timed out are listed after the \
+                   tree. A read that was given up on can't be cancelled, so \
+                   it's left running in the background until tree exits"))
+        .arg(clap::Arg::new("threads")
+            .long("threads")
+            .takes_value(true)
+            .value_name("n")
+            .help("Reads the metadata of the entries of large directories \
+                   with n threads, which helps on filesystems where each \
+                   read is slow, like network mounts; the tree is still \
+                   sorted and printed in order, small directories are \
+                   always read on one thread, and no more than 4 threads \
+                   per core are used"))
+        .arg(clap::Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .takes_value(false)
+            .multiple_occurrences(true)
+            .help("Logs why each entry that isn't shown was skipped to \
+                   stderr, like the -I pattern or ignore file rule that hid \
+                   it or the filter it didn't pass; given twice it also logs \
+                   the ignore files read and every entry that's listed"))
+        .arg(clap::Arg::new("prune")
+            .long("prune")
+            .takes_value(false)
+            .help("Omits directories that don't contain any files, after \
+                   filtering, anywhere beneath them"))
+        .arg(clap::Arg::new("stream")
+            .long("stream")
+            .takes_value(false)
+            .conflicts_with_all(&["sort", "timesort", "reverse", "dirsfirst",
+                                  "locale-sort", "prune", "collapse",
+                                  "summarize"])
+            .help("Prints each entry as soon as it's read instead of once \
+                   the whole directory has been read and sorted, so the \
+                   entries are in whatever order the filesystem returns \
+                   them; this gets output started right away on slow or \
+                   huge directories, at the cost of any order, and of \
+                   keeping a directory open while the ones beneath it are \
+                   read"))
+        .arg(clap::Arg::new("one-filesystem")
+            .short('x')
+            .long("one-filesystem")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Stays on the filesystem of the root, directories that \
+                   other filesystems are mounted on are shown but not \
+                   descended into, and are listed after the tree"))
+        .arg(clap::Arg::new("exclude-fstype")
+            .long("exclude-fstype")
+            .takes_value(true)
+            .value_name("list")
+            .conflicts_with("load")
+            .help("Doesn't descend into directories that a filesystem of \
+                   one of the comma separated types, like nfs,cifs, is \
+                   mounted on, these are shown and listed the same way as \
+                   with -x; a type also matches its subtypes, so fuse \
+                   matches fuse.sshfs, and the types are read from \
+                   /proc/self/mountinfo, so this only works on linux"))
+        .arg(clap::Arg::new("local-only")
+            .long("local-only")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Like --exclude-fstype with network filesystems like nfs \
+                   and cifs, fuse filesystems, and pseudo filesystems like \
+                   proc and sysfs, for listing / without touching any of \
+                   them"))
+        .arg(clap::Arg::new("show-empty")
+            .long("show-empty")
+            .takes_value(false)
+            .help("Marks directories in the tree that don't list anything \
+                   with [empty] if there's nothing in them at all, or with \
+                   [contents hidden] if everything in them was filtered out \
+                   or ignored, which without -f includes their files; \
+                   unreadable directories get neither, and the number of \
+                   empty directories is given after the tree"))
+        .arg(clap::Arg::new("no-report")
+            .long("no-report")
+            .takes_value(false)
+            .help("Leaves out the list of the mount points -x didn't \
+                   descend into and of the directories that --timeout gave \
+                   up on, the number of empty directories of --show-empty, \
+                   and the report of --gnu"))
+        .arg(clap::Arg::new("collapse")
+            .long("collapse")
+            .takes_value(false)
+            .alias("collapse-dirs")
+            .help("Shows chains of directories that each only contain one \
+                   other directory on one line, like com/example/foo, the \
+                   contents of the last one go beneath it; the filter is \
+                   applied first, so without -f, or with a pattern that \
+                   hides them, files don't keep a directory from being part \
+                   of a chain, and every directory of a chain is counted. \
+                   Only the printed tree is collapsed, --json, --ndjson, and \
+                   --sqlite still have each directory as an entry of its \
+                   own"))
+        .arg(clap::Arg::new("exclude")
+            .short('I')
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("pattern")
+            .help("Hides entries whose names match the glob pattern, can be \
+                   repeated, several patterns can be separated with | (\\| \
+                   matches a literal |) or written with braces like \
+                   *.{rs,toml}, matching directories aren't descended \
+                   into; a pattern starting with ! shows names an earlier \
+                   pattern hid, the last pattern that matches decides; a \
+                   pattern with a / in it matches the path relative to the \
+                   root instead of the name, where ** matches any number of \
+                   directories, like target/** or src/**/*.rs"))
+        .arg(clap::Arg::new("exclude-path")
+            .long("exclude")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("path")
+            .help("Hides the entry at the path, relative to the root or \
+                   absolute beneath it, and doesn't descend into it if it's \
+                   a directory; unlike -I only that one path matches, not \
+                   every entry with the same name, it can be repeated, and \
+                   a path that doesn't exist gets a warning"))
+        .arg(clap::Arg::new("include")
+            .short('P')
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("pattern")
+            .help("Only displays files whose names match the glob pattern, \
+                   written and repeated like the patterns of -I, which take \
+                   precedence; directories are still shown, use --prune to \
+                   hide the ones without any matches beneath them"))
+        .arg(clap::Arg::new("type")
+            .long("type")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("type")
+            .possible_values(["f", "d", "l", "x"])
+            .help("Only displays entries of the type, which is f for regular \
+                   files, d for directories, l for symlinks, or x for \
+                   executable files, and can be repeated to show more than \
+                   one; directories are still shown and descended into when \
+                   d isn't given, use --prune to hide the ones without \
+                   anything of the type beneath them, and d on its own \
+                   shows only directories"))
+        .arg(clap::Arg::new("first-only")
+            .long("first-only")
+            .takes_value(false)
+            .requires("include")
+            .conflicts_with_all(&["interactive", "load", "save",
+                                  "diff-snapshot", "recursive-output", "json",
+                                  "json-meta", "ndjson", "sqlite", "count",
+                                  "porcelain", "duplicates", "summarize"])
+            .help("Stops at the first file that -P matches and only prints \
+                   the branch from the root down to it, without looking at \
+                   the rest of the tree; entries are searched in the order \
+                   they'd be shown, or with --stream as they're read, and \
+                   the exit status is 1 if nothing matched"))
+        .arg(clap::Arg::new("no-recurse")
+            .long("no-recurse")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .value_name("pattern")
+            .help("Displays directories whose names match the glob pattern \
+                   with a [...] marker but doesn't descend into them, unlike \
+                   -I which hides them entirely; patterns are written like \
+                   those of -I, including ones matching paths"))
+        .arg(clap::Arg::new("exclude-vcs")
+            .long("exclude-vcs")
+            .takes_value(false)
+            .help("Hides version control directories (.git, .hg, .svn, \
+                   .bzr, CVS, and .jj) the same way as -I"))
+        .arg(clap::Arg::new("dockerignore")
+            .long("dockerignore")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Hides entries excluded by the .dockerignore in the root, \
+                   with Docker's rules: every pattern is matched against the \
+                   path relative to the root, so *.md only matches in the \
+                   root itself and **/*.md matches anywhere, a pattern \
+                   starting with ! shows paths an earlier one hid, and the \
+                   last pattern that matches decides"))
+        .arg(clap::Arg::new("context-preview")
+            .long("context-preview")
+            .takes_value(false)
+            .conflicts_with("load")
+            .help("Shows what a Docker build context of the root would \
+                   contain, like --dockerignore but also hiding the .git \
+                   directory of the root"))
+        .arg(clap::Arg::new("ignore-vcs")
+            .long("ignore-vcs")
+            .takes_value(false)
+            .help("Hides entries excluded by .gitignore, .ignore, and \
+                   .git/info/exclude files, rules in nested files and \
+                   negated patterns override the rules of parent \
+                   directories"))
+        .arg(clap::Arg::new("ignore-files")
+            .long("respect-ignore-files")
+            .takes_value(false)
+            .help("Hides entries excluded by .ignore and .fdignore files, \
+                   and by the global fd ignore file ~/.config/fd/ignore, \
+                   with the same precedence rules as --ignore-vcs"))
+        .arg(clap::Arg::new("no-ignore")
+            .long("no-ignore")
+            .takes_value(false)
+            .help("Disables all ignore file processing"))
+        .arg(clap::Arg::new("count")
+            .long("count")
+            .alias("count-only")
+            .takes_value(false)
+            .conflicts_with("interactive")
+            .help("Prints the number of directories, files, symlinks, and \
+                   other entries, and the total size in bytes of the files, \
+                   instead of the tree, every filter and the depth limit \
+                   still apply and files are only counted with -f"))
+        .arg(clap::Arg::new("porcelain")
+            .long("porcelain")
+            .takes_value(false)
+            .requires("count")
+            .help("Prints the totals from --count on a single line of \
+                   key=value pairs that is stable across versions, with the \
+                   size of the files in bytes as size="))
+        .arg(clap::Arg::new("type-summary")
+            .long("type-summary")
+            .takes_value(false)
+            .conflicts_with_all(&["count", "summarize"])
+            .help("Prints the number of directories, files, symlinks, and \
+                   other entries after the tree, files are only counted with \
+                   -f"))
+        .arg(clap::Arg::new("sqlite")
+            .long("sqlite")
+            .takes_value(true)
+            .value_name("file")
+            .conflicts_with_all(&["interactive", "count", "duplicates",
+                                  "summarize", "type-summary"])
+            .help("Writes one row per entry into the entries table of a \
+                   SQLite database instead of printing the tree, with the \
+                   columns path, parent, type, size, and mtime; the table \
+                   and an index on parent are created if needed, and the \
+                   rows of an earlier export are replaced"))
+        .arg(clap::Arg::new("ndjson")
+            .long("ndjson")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "count", "duplicates",
+                                  "summarize", "type-summary", "sqlite",
+                                  "save"])
+            .help("Writes one JSON object per line for each entry as it's \
+                   found instead of printing the tree, with the fields path, \
+                   depth, type, and size; nesting is given by the path and \
+                   depth rather than by containment, a directory that can't \
+                   be read is followed by an object of type error with the \
+                   reason, and the last line is an object of type report \
+                   with the number of errors"))
+        .arg(clap::Arg::new("json")
+            .long("json")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "count", "duplicates",
+                                  "summarize", "type-summary", "sqlite",
+                                  "save", "ndjson"])
+            .help("Writes the tree as a single JSON document instead of \
+                   printing it, in the snapshot format of --save so that it \
+                   can be given to --load; every directory holds its \
+                   contents in children, which means the whole tree is kept \
+                   in memory until it's written, for large trees --ndjson \
+                   streams the entries instead"))
+        .arg(clap::Arg::new("json-meta")
+            .long("json-meta")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "count", "duplicates",
+                                  "summarize", "type-summary", "sqlite",
+                                  "save", "ndjson"])
+            .help("Like --json, but each entry also has the fields \
+                   modified, an RFC 3339 timestamp in the local time zone, \
+                   permissions, the mode as four octal digits like 0755, \
+                   and is_symlink; fields that aren't known, like the \
+                   permissions on windows, are left out"))
+        .arg(clap::Arg::new("duplicates")
+            .long("duplicates")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "save", "load", "count"])
+            .help("Lists groups of files with identical contents instead of \
+                   the tree, grouped under their shared sha256 hash with the \
+                   space the extra copies take up, and the total of that \
+                   after the groups; only files that share their size with \
+                   another file are read, with --threads in parallel, and \
+                   hardlinks of the same file are listed on their own \
+                   instead of as duplicates"))
+        .arg(clap::Arg::new("summarize")
+            .long("summarize")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "count", "duplicates",
+                                  "format", "level"])
+            .help("Lists only the entries of the root along with the total \
+                   size of the files beneath each one, largest first unless \
+                   another order is chosen; a * marks totals that are a lower \
+                   bound because part of the directory couldn't be read"))
+        .arg(clap::Arg::new("du")
+            .long("du")
+            .takes_value(false)
+            .conflicts_with("summarize")
+            .help("Shows the total size and number of the files beneath each \
+                   directory next to it, like [1.2M in 48 files], counting \
+                   only regular files that pass the filter, not symlinks, \
+                   and each directory once even if it's reached twice; \
+                   directories whose contents aren't shown don't get one, \
+                   and a \u{2265} marks totals that are a lower bound \
+                   because part of the directory couldn't be read"))
+        .arg(clap::Arg::new("shallow-size")
+            .long("shallow-size")
+            .takes_value(false)
+            .conflicts_with_all(&["summarize", "du"])
+            .help("Shows the total size and number of only the files \
+                   directly in each directory next to it, like [1.2K in 3 \
+                   files, shallow], without looking into subdirectories \
+                   the way --du does; which directories get one, and what \
+                   the \u{2265} means, is the same as with --du"))
+        .arg(clap::Arg::new("merge")
+            .long("merge")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "save", "load",
+                                  "diff-snapshot", "recursive-output",
+                                  "first-only", "sqlite", "ndjson", "json",
+                                  "json-meta", "gnu", "one-filesystem",
+                                  "dockerignore", "context-preview",
+                                  "exclude-path", "git-status"])
+            .help("Prints the paths as the entries of a single tree under a \
+                   <multiple> root, sorted together like the entries of any \
+                   directory, with one summary for all of them; paths that \
+                   share a name are shown the way they were given instead"))
+        .arg(clap::Arg::new("bars")
+            .long("bars")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "summarize",
+                                  "diff-snapshot"])
+            .help("Draws a bar after each entry whose size is known, which \
+                   is files and with --du or --shallow-size directories, \
+                   and isn't empty, scaled to the largest entry of the same \
+                   directory so that small files deep in the tree can still \
+                   be compared to each other; bars are drawn with # in \
+                   ASCII, and are shortened or left out where the line \
+                   wouldn't fit in the terminal"))
+        .arg(clap::Arg::new("bars-global")
+            .long("bars-global")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "summarize",
+                                  "diff-snapshot"])
+            .help("Like --bars, but every bar is scaled to the largest \
+                   entry anywhere in the tree, for comparing entries of \
+                   different directories"))
+        .arg(clap::Arg::new("post-order")
+            .long("post-order")
+            .takes_value(false)
+            .conflicts_with_all(&["interactive", "json", "json-meta",
+                                  "ndjson", "summarize", "diff-snapshot",
+                                  "first-only", "line-numbers", "info",
+                                  "infofile", "xattrs"])
+            .help("Prints each directory after its contents instead of \
+                   before them, so the leaves come first and the root is \
+                   the last line, with the connectors turned upside down to \
+                   branch up to the entries of a directory; this is meant \
+                   for reporting sizes with --du, where a directory's total \
+                   comes right after what it adds up"));
+
+    // with --gnu the flags of gnu tree are rewritten into the ones that mean
+    // the same here before anything is parsed
+    let mut argv: vec::Vec<ffi::OsString> = env::args_os().collect();
+    let profile = if gnu::is_requested(&argv) {
+        match gnu::translate(argv, &value_options(&command)) {
+            Ok((translated, profile)) => {
+                argv = translated;
+                Some(profile)
+            },
+            Err(message) => {
+                eprintln!("ERROR: {} with --gnu", message);
+                std::process::exit(1);
+            },
+        }
+    } else {
+        None
+    };
+    let args = command.get_matches_from(argv);
+
+    if args.is_present("list-styles") {
+        print_styles(&mut Stdout::new());
+        return Ok(());
+    }
+
+    // a ctrl-c stops the traversal at the next entry instead of killing the
+    // process partway through a line, a second one while it's still running
+    // exits right away, without waiting for what's buffered to be printed
+    let _ = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, atomic::Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    });
+
+    // set str used for formatting based on wether the ascii flag was set,
+    // then draw them in the chosen style and replace any of them that were
+    // given on their own
+    let (mut format_str, ellipsis) = match (args.is_present("ascii"),
+                                            profile.is_some()) {
+        (true, true) => (Vec::from(gnu::ASCII_CONNECTORS), "..."),
+        (false, true) => (Vec::from(gnu::CONNECTORS), "…"),
+        (true, false) => (Vec::from(["\\---","+---","    ","|   "]), "..."),
+        (false, false) => (Vec::from(["└───","├───","    ","│   "]), "…"),
+    };
+    // with --post-order entries branch up from beneath them, so the connector
+    // to the first entry of a directory is the one that's turned upside down
+    if args.is_present("post-order") {
+        format_str[0] = match format_str[0] {
+            "└───" => "┌───",
+            "\\---" => "/---",
+            "└── " => "┌── ",
+            _ => ",-- ",
+        };
+    }
+    let style = STYLES.iter()
+        .find(|(name, _)| args.value_of("style") == Some(*name))
+        .map_or(STYLES[0].1, |(_, style)| *style);
+    let overrides = ["conn-last", "conn-tee", "conn-space", "conn-vert"];
+    let format_str = format_str.iter().zip(overrides)
+        .map(|(default, name)| match args.value_of(name) {
+            Some("") => {
+                eprintln!("ERROR: --{} can't be empty", name);
+                std::process::exit(1);
+            },
+            Some(connector) => String::from(connector),
+            None => restyle(default, &style),
+        })
+        .collect();
+
+    // the style the connectors are drawn in, made up of the ansi attributes
+    // for dimming and the chosen color
+    let color = match args.value_of("color") {
+        Some("always") => true,
+        Some("never") => false,
+        _ => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    let mut branch_attributes = vec::Vec::new();
+    if args.is_present("dim-branches") {
+        branch_attributes.push("2");
+    }
+    if let Some(name) = args.value_of("branch-color") {
+        branch_attributes.extend(format::color_code(name));
+    }
+    let branch_style = if color && !branch_attributes.is_empty() {
+        Some(branch_attributes.join(";"))
+    } else {
+        None
+    };
+
+    let quoting = if args.is_present("quote") {
+        format::Quoting::Double
+    } else if args.is_present("shell-escape") {
+        format::Quoting::Shell
+    } else {
+        format::Quoting::Plain
+    };
+
+    // the depth limit has to allow at least the root's own entries
+    let max_depth = match args.value_of("level") {
+        Some(level) => match level.parse::<usize>() {
+            Ok(value) if value > 0 => Some(value),
+            _ => {
+                eprintln!("ERROR: \"{}\" Is not a valid level, expected a \
+                           positive number", level);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    if args.is_present("follow") {
+        let limit = match args.value_of("max-symlinks") {
+            Some(limit) => match limit.parse::<u64>() {
+                Ok(value) => value,
+                Err(_) => {
+                    eprintln!("ERROR: \"{}\" Is not a valid number of \
+                               symlinks", limit);
+                    std::process::exit(1);
+                },
+            },
+            None => DEFAULT_MAX_SYMLINKS,
+        };
+        let _ = FOLLOW_LIMIT.set(limit);
+    }
+
+    // a number of seconds too large for a duration is as invalid as one
+    // that isn't a number at all
+    if let Some(given) = args.value_of("timeout") {
+        let timeout = given.parse::<f64>().ok()
+            .filter(|value| *value > 0.0)
+            .and_then(|value| time::Duration::try_from_secs_f64(value).ok());
+        let timeout = match timeout {
+            Some(value) => value,
+            None => {
+                eprintln!("ERROR: \"{}\" Is not a valid number of seconds, \
+                           expected a positive number", given);
+                std::process::exit(1);
+            },
+        };
+        let _ = READ_TIMEOUT.set(timeout);
+    }
+
+    // the threads are only used by read_entries, so they can be started here
+    // once for the whole run; reads that wait on the network leave a core
+    // idle, so a few threads per core still help, but past that each one
+    // only adds a stack and contention
+    if let Some(threads) = args.value_of("threads") {
+        let threads = match threads.parse::<usize>() {
+            Ok(value) if value > 0 => value,
+            _ => {
+                eprintln!("ERROR: \"{}\" Is not a valid number of threads, \
+                           expected a positive number", threads);
+                std::process::exit(1);
+            },
+        };
+        let cores = thread::available_parallelism()
+            .map_or(1, num::NonZeroUsize::get);
+        let threads = threads.min(cores * THREADS_PER_CORE);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads)
+            .build_global();
+        if threads > 1 && pool.is_ok() {
+            PARALLEL_STATS.store(true, atomic::Ordering::SeqCst);
+        }
+    }
+
+    // a missing or malformed global info file is ignored the same way a
+    // directory's own .info file would be
+    let global_info = args.value_of("infofile")
+        .and_then(|infofile| info::InfoFile::load(path::Path::new(infofile)));
+
+    // the width used for wrapping info comments and fitting bars, falls back
+    // to the standard 80 columns if the terminal doesn't advertise its width
+    let width = env::var("COLUMNS").ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80);
+
+    // the format template is validated before anything is printed so that a
+    // typo doesn't produce a partial tree
+    let format = match args.value_of("format") {
+        Some(template) => match format::Template::parse(template) {
+            Ok(mut value) => {
+                if args.is_present("relative-time") {
+                    value.use_relative_time();
+                }
+                Some(value)
+            },
+            Err(error) => {
+                eprintln!("ERROR: {}", error);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    logger::init(args.occurrences_of("verbose"));
+
+    // this has to be chosen before any entry is read
+    let source = match args.value_of("time") {
+        Some("ctime") => TimeSource::Changed,
+        Some("atime") => TimeSource::Accessed,
+        Some("btime") => TimeSource::Born,
+        _ => TimeSource::Modified,
+    };
+    if cfg!(not(unix)) && matches!(source, TimeSource::Changed) {
+        eprintln!("ERROR: \"--time ctime\" Is only available on unix");
+        std::process::exit(1);
+    }
+    let _ = TIME_SOURCE.set(source);
+
+    // -t is shorthand for sorting by mtime, and -r reverses every key of
+    // whichever order is chosen; summaries default to largest first
+    let sort_spec = if args.is_present("timesort") {
+        Some("mtime")
+    } else if args.is_present("summarize") && !args.is_present("sort") {
+        Some("size:desc")
+    } else {
+        args.value_of("sort")
+    };
+    let reverse = args.is_present("reverse");
+    let dirs_first = args.is_present("dirsfirst");
+    let mut sort = match sort_spec {
+        Some(spec) => match sort::Sort::parse(spec, reverse, dirs_first) {
+            Ok(value) => value,
+            Err(error) => {
+                eprintln!("ERROR: {}", error);
+                std::process::exit(1);
+            },
+        },
+        None => sort::Sort::new(reverse, dirs_first),
+    };
+    if args.is_present("locale-sort") {
+        sort.use_locale();
+    }
+    if args.is_present("normalize") {
+        sort.normalize_names();
+    }
+
+    // the reference file is only looked at once, entries are then compared
+    // against its time from --time
+    let changed_since_file = args.value_of("changed-since");
+    let reference = match changed_since_file {
+        Some(file) => {
+            Some((file, "it didn't change since the --changed-since \
+                         reference"))
+        },
+        None => args.value_of("newer-than-file").map(|file| {
+            (file, "it isn't newer than the --newer-than-file reference")
+        }),
+    };
+    let newer_than = match reference {
+        Some((reference, rejection)) => {
+            let reference_time = fs::metadata(reference)
+                .map(|metadata| entry_time(&metadata));
+            match reference_time {
+                Ok(Some(value)) => Some(Reference { time: value, rejection }),
+                Ok(None) => {
+                    eprintln!("ERROR: \"{}\" unable to read {}", reference,
+                              time_source().name());
+                    std::process::exit(1);
+                },
+                Err(error) => {
+                    eprintln!("ERROR: \"{}\" unable to read {}: {}",
+                              reference, time_source().name(), error);
+                    std::process::exit(1);
+                },
+            }
+        },
+        None => None,
+    };
+    // entries count as changed if they were modified at or after this point
+    let changed_within = args.value_of("changed-within").map(|text| {
+        match parse_duration(text) {
+            Ok(duration) => {
+                time::SystemTime::now().checked_sub(duration)
+                    .unwrap_or(time::UNIX_EPOCH)
+            },
+            Err(error) => {
+                eprintln!("ERROR: \"{}\" {}", text, error);
+                std::process::exit(1);
+            },
+        }
+    });
+    let changed_style = match args.value_of("changed-color") {
+        Some(name) => format::color_code(name).map(String::from),
+        None => Some(String::from("1")),
+    };
+    let changed = match (changed_within, changed_style) {
+        (Some(since), Some(style)) if color => Some((since, style)),
+        _ => None,
+    };
+    let only_changed = args.is_present("only-changed");
+
+    let age_scale = match args.value_of("age-scale") {
+        Some(days) => match days.parse::<u64>() {
+            Ok(value) if value > 0 => value,
+            _ => {
+                eprintln!("ERROR: \"{}\" Is not a valid number of days, \
+                           expected a positive number", days);
+                std::process::exit(1);
+            },
+        },
+        None => format::DEFAULT_AGE_SCALE,
+    };
+    let age_color = if color && args.is_present("age-color") {
+        let scale = time::Duration::from_secs(age_scale * 24 * 60 * 60);
+        Some((time::SystemTime::now(), scale))
+    } else {
+        None
+    };
+
+    let empty = if args.is_present("empty-dirs-only") {
+        Some(Empty::DirsOnly)
+    } else if args.is_present("empty") {
+        Some(Empty::FilesAndDirs)
+    } else {
+        None
+    };
+    // the -I patterns and the version control preset are combined into one
+    // set of patterns that entry names are matched against
+    let vcs_dirs: &[&str] = if args.is_present("exclude-vcs") {
+        &ignores::VCS_DIRS
+    } else {
+        &[]
+    };
+    // the files written by --recursive-output would otherwise show up in the
+    // trees written after them, and in every tree on the next run
+    let recursive_output = args.value_of("recursive-output").map(String::from);
+    if let Some(file_name) = &recursive_output {
+        if path::Path::new(file_name).file_name()
+            != Some(ffi::OsStr::new(file_name)) {
+            eprintln!("ERROR: \"{}\" Is not a file name", file_name);
+            std::process::exit(1);
+        }
+    }
+    let index_pattern = recursive_output.as_deref().map(globset::escape);
+    let mut excluded = vcs_dirs.to_vec();
+    excluded.extend(index_pattern.as_deref());
+    let exclude = pattern_set(&args, "exclude", &excluded);
+    // -P and --no-recurse are hooks of the filter and the walk, the same as
+    // any other predicate would be
+    let shows = pattern_set(&args, "include", &[]).map(|include| {
+        Box::new(move |entry: &Entry, rel_path: Option<&path::Path>| {
+            if include.is_match(&entry.name, rel_path) {
+                Decision::Defer
+            } else {
+                Decision::Reject("it doesn't match any -P pattern")
+            }
+        }) as Hook
+    });
+    let descends = pattern_set(&args, "no-recurse", &[]).map(|no_recurse| {
+        Box::new(move |entry: &Entry, rel_path: Option<&path::Path>| {
+            if no_recurse.is_match(&entry.name, rel_path) {
+                Decision::Reject("[...]")
+            } else {
+                Decision::Defer
+            }
+        }) as Hook
+    });
+
+    let types = args.values_of("type").map(|values| {
+        let values: vec::Vec<&str> = values.collect();
+        Types {
+            files: values.contains(&"f"),
+            symlinks: values.contains(&"l"),
+            executables: values.contains(&"x"),
+        }
+    });
+
+    let filter = Filter {
+        newer_than,
+        changed_within: changed_within.filter(|_| only_changed),
+        empty,
+        types,
+        shows,
+        exclude,
+        exclude_paths: vec::Vec::new(),
+        context: None,
+        strict: args.is_present("strict"),
+        dotfiles: profile.as_ref().is_none_or(|profile| profile.all),
+        root: None,
+        #[cfg(feature = "git")]
+        tracked: None,
+    };
+
+    // no-ignore wins over every flag that turns on ignore files
+    let ignore_sources = ignores::Sources {
+        vcs: args.is_present("ignore-vcs"),
+        files: args.is_present("ignore-files"),
+    };
+    let ignore_sources = if (ignore_sources.vcs || ignore_sources.files)
+        && !args.is_present("no-ignore") {
+        Some(ignore_sources)
+    } else {
+        None
+    };
+
+    let mut exclude_fstypes: vec::Vec<String> = args.value_of("exclude-fstype")
+        .into_iter()
+        .flat_map(|list| list.split(','))
+        .filter(|fstype| !fstype.is_empty())
+        .map(String::from)
+        .collect();
+    if args.is_present("local-only") {
+        exclude_fstypes.extend(mounts::NON_LOCAL.iter().copied()
+                                   .map(String::from));
+    }
+
+    // finding duplicates and summarizing look at every file, even without -f
+    let options = Options {
+        show_files: args.is_present("files") || args.is_present("duplicates")
+            || args.is_present("summarize") || args.is_present("first-only")
+            || args.is_present("changed-since")
+            || args.values_of("type")
+                .is_some_and(|mut values| values.any(|value| value != "d"))
+            || profile.as_ref().is_some_and(|profile| !profile.dirs_only),
+        format_str,
+        flat: args.is_present("flat"),
+        info: args.is_present("info") || args.is_present("infofile"),
+        global_info,
+        width,
+        line_numbers: args.is_present("line-numbers"),
+        format,
+        sort,
+        filter,
+        // the directories that only-changed and changed-since keep are the
+        // ones anything changed beneath, which is what pruning finds
+        prune: args.is_present("prune") || only_changed
+            || changed_since_file.is_some(),
+        max_depth,
+        root_device: None,
+        exclude_fstypes,
+        no_report: args.is_present("no-report"),
+        show_empty: args.is_present("show-empty"),
+        gnu: profile.is_some(),
+        ellipsis,
+        ignore_sources,
+        count_only: args.is_present("count"),
+        porcelain: args.is_present("porcelain"),
+        duplicates: args.is_present("duplicates"),
+        summarize: args.is_present("summarize"),
+        du: args.is_present("du"),
+        post_order: args.is_present("post-order"),
+        bars: if args.is_present("bars-global") {
+            Some(BarScale::Global)
+        } else if args.is_present("bars") {
+            Some(BarScale::Directory)
+        } else {
+            None
+        },
+        ascii: args.is_present("ascii"),
+        shallow_size: args.is_present("shallow-size"),
+        collapse: args.is_present("collapse") && !args.is_present("json")
+            && !args.is_present("json-meta") && !args.is_present("ndjson")
+            && !args.is_present("sqlite"),
+        stream: args.is_present("stream"),
+        quoting,
+        slash: args.is_present("slash"),
+        resolve_links: args.is_present("resolve-links"),
+        perms: args.is_present("perms"),
+        perm_octal: cfg!(unix) && args.is_present("perm-octal"),
+        xattrs: args.is_present("xattrs") || args.is_present("xattr-values"),
+        xattr_values: args.is_present("xattr-values"),
+        type_summary: args.is_present("type-summary"),
+        descends,
+        branch_style,
+        changed,
+        age_color,
+        sqlite: args.value_of("sqlite").map(path::PathBuf::from),
+        columns: args.is_present("columns"),
+        mime: args.is_present("mime"),
+        recursive_output,
+        force: args.is_present("force"),
+        ndjson: args.is_present("ndjson"),
+        json: args.is_present("json") || args.is_present("json-meta"),
+        json_meta: args.is_present("json-meta"),
+        git_status: args.is_present("git-status"),
+        color,
+        prescans: Prescans::default(),
+    };
+
+    if options.sqlite.is_some() && !cfg!(feature = "sqlite") {
+        eprintln!("ERROR: --sqlite isn't supported by this build, it needs \
+                   the sqlite feature");
+        std::process::exit(1);
+    }
+    if options.git_status && !cfg!(feature = "git") {
+        eprintln!("ERROR: --git-status isn't supported by this build, it \
+                   needs the git feature");
+        std::process::exit(1);
+    }
+    if args.is_present("git-tracked") && !cfg!(feature = "git") {
+        eprintln!("ERROR: --git-tracked isn't supported by this build, it \
+                   needs the git feature");
+        std::process::exit(1);
+    }
+
+    // the metadata of files is only read along with the listing when
+    // something looks at it, otherwise the listing is all that's read of
+    // them; with --timeout it still is, so that it's read on the reader
+    // thread the timeout covers
+    if !options.uses_file_metadata() && READ_TIMEOUT.get().is_none()
+        && !args.is_present("save") && !args.is_present("diff-snapshot")
+        && !args.is_present("interactive") {
+        FILE_METADATA.store(false, atomic::Ordering::SeqCst);
+    }
+
+    // the tree is piped through a pager with --pager, and by default when
+    // it's printed to a terminal and turns out not to fit in it; everything
+    // else goes to stdout, which is buffered unless it's a terminal where
+    // each line should show up as soon as it's printed
+    let pages = options.prints_tree() && !args.is_present("no-pager")
+        && !args.is_present("interactive");
+    let pending = terminal::size().ok()
+        .filter(|_| pages && io::stdout().is_terminal())
+        .map(|(_, height)| Stdout::pending(height as usize));
+    let mut stdout = if pages && args.is_present("pager") {
+        Stdout::paged()
+    } else {
+        pending.unwrap_or_else(Stdout::new)
+    };
+
+    // a loaded snapshot is rendered as is, without looking at the filesystem
+    if let Some(file) = args.value_of("load") {
+        let root = match snapshot::load(path::Path::new(file)) {
+            Ok(value) => value,
+            Err(error) => {
+                eprintln!("ERROR: \"{}\" {}", file, error);
+                std::process::exit(1);
+            },
+        };
+        print_tree(&root.name, &Source::Node(&root), &ignores::Rules::none(),
+                   &options, &mut stdout);
+        return Ok(());
+    }
+
+    // get the search paths either from the optional positional arguments or
+    // from getting the current working directory
+    let paths: vec::Vec<path::PathBuf> = match args.values_of("path") {
+        Some(path_args) => path_args.flat_map(expand_path).collect(),
+        None => match env::current_dir() {
+            Ok(value) => vec::Vec::from([value]),
+            Err(error) => {
+                eprintln!("ERROR: \".\" {}", error);
+                std::process::exit(1);
+            },
+        },
+    };
+
+    let exclude_paths: vec::Vec<path::PathBuf> = args.values_of("exclude-path")
+        .into_iter()
+        .flatten()
+        .map(|given| exclude_path(given, &paths))
+        .collect();
+
+    // the index of every repository the roots are in is read once up front
+    #[cfg(feature = "git")]
+    let options = if args.is_present("git-tracked") {
+        let tracked = match git::Tracked::load(&paths) {
+            Ok(value) => value,
+            Err(path) => {
+                eprintln!("ERROR: \"{}\" Is not inside a git repository",
+                          path.to_string_lossy());
+                std::process::exit(1);
+            },
+        };
+        let filter = Filter { tracked: Some(tracked), ..options.filter };
+        Options { filter, ..options }
+    } else {
+        options
+    };
+
+    // the interactive browser, snapshots, and exports only have room for one
+    // root
+    if paths.len() > 1 && (args.is_present("interactive")
+                           || args.is_present("save")
+                           || args.is_present("sqlite")
+                           || args.is_present("ndjson")
+                           || args.is_present("json")
+                           || args.is_present("json-meta")
+                           || args.is_present("diff-snapshot")) {
+        eprintln!("ERROR: --interactive, --save, --sqlite, --ndjson, --json, \
+                   and --diff-snapshot only support a single path");
+        std::process::exit(1);
+    }
+
+    // with --merge every root is an entry of one synthetic root, and the
+    // global ignore file is the one of the first
+    if args.is_present("merge") {
+        let roots = merged_roots(&paths, args.is_present("print-root-path"))?;
+        let base_rules = match (options.ignore_sources, paths.first()) {
+            (Some(sources), Some(path)) if sources.files => {
+                ignores::Rules::global(&path.canonicalize()?)
+            },
+            _ => ignores::Rules::none(),
+        };
+        print_tree(MERGED_ROOT, &Source::Roots(&roots), &base_rules,
+                   &options, &mut stdout);
+        return Ok(());
+    }
+
+    let mut options = options;
+    let quiet = args.is_present("quiet");
+    let mut indexes = Indexes::default();
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 && !quiet && !options.gnu && !args.is_present("first-only") {
+            stdout.line("");
+        }
+
+        // gnu tree shows a root as it was given, and one that can't be
+        // listed as a directory that couldn't be opened
+        let given = match args.values_of("path") {
+            Some(_) => path.to_string_lossy().into_owned(),
+            None => String::from("."),
+        };
+        if options.gnu && !path.is_dir() {
+            if !quiet {
+                stdout.line(&format!("{}  {}", given, gnu::OPEN_ERROR));
+            }
+            gnu::failed();
+            continue;
+        }
+
+        // resolve the search path, this ensures the path valid as well as
+        // following any symlinks
+        let path = path.canonicalize()?;
+        options.filter.root = Some(path.clone());
+        options.filter.exclude_paths = root_exclude_paths(&path,
+                                                          &exclude_paths);
+        options.filter.context = context_patterns(&path, &args);
+        options.root_device = if args.is_present("one-filesystem") {
+            dir_id(&Source::Path(path.clone())).map(|(device, _)| device)
+        } else {
+            None
+        };
+
+        // extract important metadata, like for example, is what this path
+        // refers to a directory
+        let metadata = path.metadata()?;
+
+        // directory sanity check
+        if !metadata.is_dir() {
+            stdout.finish();
+            eprintln!("ERROR: \"{}\" Is not a directory",
+                      path.to_string_lossy());
+            std::process::exit(1);
+        }
+
+        // get filename, fallback to full path; the name comes from the
+        // canonical path, which never ends in a separator or a . component,
+        // so passing src/ shows the same name as passing src, and only the
+        // root of a filesystem, which has no filename, falls back
+        let name = match path.file_name() {
+            _ if options.gnu && !args.is_present("print-root-path") => given,
+            Some(name) if !args.is_present("print-root-path") => {
+                name.to_string_lossy().into_owned()
+            },
+            _ => path.to_string_lossy().into_owned(),
+        };
+
+        // the interactive browser loads directories on demand instead of
+        // printing the whole tree up front, the only thing it prints is the
+        // entry the user picked
+        if args.is_present("interactive") {
+            if let Some(picked) = interactive::run(&path, &name, &options)? {
+                stdout.line(&picked.to_string_lossy());
+            }
+            return Ok(());
+        }
+
+        // when saving a snapshot the whole tree is traversed once up front,
+        // and the tree that gets printed is rendered from the saved snapshot
+        if let Some(file) = args.value_of("save") {
+            let root = snapshot::scan(&path, &name, &metadata);
+            if is_interrupted() {
+                exit_interrupted(&mut stdout);
+            }
+            let root = match root {
+                Ok(value) => value,
+                Err(error) => print_failed(&mut stdout,
+                                           TreeError::Traversal(error)),
+            };
+            let root = match snapshot::save(path::Path::new(file), root) {
+                Ok(value) => value,
+                Err(error) => {
+                    eprintln!("ERROR: \"{}\" {}", file, error);
+                    std::process::exit(1);
+                },
+            };
+            print_tree(&root.name, &Source::Node(&root),
+                       &ignores::Rules::none(), &options, &mut stdout);
+            return Ok(());
+        }
+
+        // the global ignore file sits below every file in the tree
+        let base_rules = match options.ignore_sources {
+            Some(sources) if sources.files => ignores::Rules::global(&path),
+            _ => ignores::Rules::none(),
+        };
+        let root = Source::Path(path);
+        let rules = child_rules(&root, &base_rules, &options);
+        if let Some(file) = args.value_of("diff-snapshot") {
+            let differs = print_differences(file, &name, &root, &rules,
+                                            &options, &mut stdout);
+            stdout.exit(if differs { 1 } else { 0 });
+        }
+        if args.is_present("first-only") {
+            if print_first_match(&name, &root, &rules, &options,
+                                 &mut stdout) {
+                stdout.exit(0);
+            }
+            continue;
+        }
+        if !quiet {
+            print_tree(&name, &root, &rules, &options, &mut stdout);
+        }
+        if let (Some(file_name), Source::Path(path)) =
+            (&options.recursive_output, &root) {
+            write_indexes(path, &rules, file_name, &options, &mut indexes);
+            if is_interrupted() {
+                exit_interrupted(&mut stdout);
+            }
+        }
+    }
+
+    if options.recursive_output.is_some() {
+        if !quiet {
+            stdout.line("");
+            stdout.line(&format!("{} files written, {} failed",
+                                 indexes.written, indexes.failed));
+        }
+        if indexes.failed > 0 {
+            stdout.exit(1);
+        }
+    }
+
+    // none of the roots had a match for --first-only
+    if args.is_present("first-only") {
+        stdout.exit(1);
+    }
+
+    if options.gnu {
+        if options.prints_tree() && !options.no_report && !quiet {
+            stdout.line("");
+            stdout.line(&gnu::report(options.show_files));
+        }
+        if gnu::exit_code() != 0 {
+            stdout.exit(gnu::exit_code());
+        }
+    }
+
+    Ok(())
+}
+
+// the name of the root --merge puts the roots under
+const MERGED_ROOT: &str = "<multiple>";
+
+// the roots given with --merge as the entries of the synthetic root, named
+// like the root of a tree of their own would be; a root given more than once
+// is only listed once, and roots that would share a name are named by the
+// path they were given as instead
+fn merged_roots(paths: &[path::PathBuf], print_root_path: bool)
+    -> Result<vec::Vec<Entry<'static>>> {
+    let mut roots: vec::Vec<(path::PathBuf, &path::Path)> = vec::Vec::new();
+    for given in paths {
+        let path = given.canonicalize()?;
+        if !path.is_dir() {
+            eprintln!("ERROR: \"{}\" Is not a directory",
+                      path.to_string_lossy());
+            std::process::exit(1);
+        }
+        if roots.iter().all(|(root, _)| *root != path) {
+            roots.push((path, given));
+        }
+    }
+    let name = |path: &path::Path| match path.file_name() {
+        Some(name) if !print_root_path => name.to_os_string(),
+        _ => path.as_os_str().to_os_string(),
+    };
+    let mut entries = vec::Vec::with_capacity(roots.len());
+    for (path, given) in roots.iter() {
+        let shared = roots.iter()
+            .filter(|(other, _)| name(other) == name(path))
+            .count() > 1;
+        let metadata = path.metadata()?;
+        entries.push(Entry {
+            name: match shared {
+                true => given.as_os_str().to_os_string(),
+                false => name(path),
+            },
+            kind: Kind::Directory,
+            follow: None,
+            meta: sync::OnceLock::from(Box::new(meta_of(&metadata,
+                                                        Kind::Directory))),
+            origin: Origin::Source(Source::Path(path.clone())),
+        });
+    }
+    Ok(entries)
+}
+
+// how many of the files for --recursive-output were written or failed
+#[derive(Default)]
+struct Indexes {
+    written: u64,
+    failed: u64,
+    // the canonical paths of the directories already written to, so that a
+    // symlink loop doesn't write the same ones forever
+    visited: collections::HashSet<path::PathBuf>,
+}
+
+// writes the tree of dir into the file with the given name in it, then does
+// the same for each directory beneath it; rules are the ignore rules for the
+// contents of dir, directories are found with the same filters and ignore
+// rules as the tree, but they're followed all the way down regardless of -L;
+// once ctrl-c is pressed it stops, and exiting is left to the caller
+fn write_indexes(dir: &path::Path, rules: &ignores::Rules, file_name: &str,
+                 options: &Options, indexes: &mut Indexes) {
+    if is_interrupted() {
+        return;
+    }
+    let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    if !indexes.visited.insert(canonical) {
+        return;
+    }
+
+    let file = dir.join(file_name);
+    let error = match write_index(dir, &file, rules, options) {
+        Ok(()) => None,
+        Err(TreeError::Interrupted) => return,
+        Err(TreeError::Traversal(error)) => Some(error.to_string()),
+        Err(TreeError::Write(error)) => Some(error.to_string()),
+    };
+    match error {
+        None => indexes.written += 1,
+        Some(error) => {
+            eprintln!("ERROR: \"{}\" {}", file.to_string_lossy(), error);
+            indexes.failed += 1;
+        },
+    }
+
+    // directories that can't be read were already reported in their parent's
+    // tree
+    let source = Source::Path(dir.to_path_buf());
+    let entries = match list(&source, rules, &options.filter, &options.sort,
+                             false) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    for entry in entries {
+        if let (Kind::Directory, Source::Path(path)) =
+            (entry.kind, &entry.source()) {
+            let rules = child_rules(&entry.source(), rules, options);
+            write_indexes(path, &rules, file_name, options, indexes);
+        }
+    }
+}
+
+// prints the tree of dir into file instead of stdout, an existing file is only
+// replaced with --force
+fn write_index(dir: &path::Path, file: &path::Path, rules: &ignores::Rules,
+               options: &Options) -> Result<(), TreeError> {
+    let opened = if options.force {
+        fs::File::create(file)
+    } else {
+        fs::OpenOptions::new().write(true).create_new(true).open(file)
+    };
+    let opened = match opened {
+        Ok(value) => value,
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(TreeError::Write(io::Error::new(
+                error.kind(), "Already exists, use --force to overwrite it")));
+        },
+        Err(error) => return Err(TreeError::Write(error)),
+    };
+    let name = match dir.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => dir.to_string_lossy().into_owned(),
+    };
+
+    let out = Output {
+        plain: true,
+        ..Output::new(io::BufWriter::new(opened))
+    };
+    try_print_tree(&name, &Source::Path(dir.to_path_buf()), rules, options,
+                   out)
+}
+
+// the spellings of the options that take a value, like -L and --level, which
+// is what --gnu leaves the values of as they are; clap doesn't give out the
+// hidden aliases, and --from-json is the only one that takes a value
+fn value_options(command: &clap::Command) -> vec::Vec<String> {
+    let mut options = vec::Vec::from([String::from("--from-json")]);
+    for arg in command.get_arguments().filter(|arg| arg.is_takes_value_set()) {
+        options.extend(arg.get_short_and_visible_aliases().into_iter()
+            .flatten()
+            .map(|short| format!("-{}", short)));
+        options.extend(arg.get_long_and_visible_aliases().into_iter()
+            .flatten()
+            .map(|long| format!("--{}", long)));
+    }
+    options
+}
+
+// compiles every pattern given for a repeatable argument, each of which can
+// hold several patterns separated by an unescaped |, along with any extra
+// patterns; the extra ones come first so that a negated pattern given by the
+// user can re-include something they match
+fn pattern_set(args: &clap::ArgMatches, name: &str, extra: &[&str])
+    -> Option<ignores::Patterns> {
+    let mut patterns: vec::Vec<String> = extra.iter()
+        .map(|pattern| String::from(*pattern))
+        .collect();
+    if let Some(values) = args.values_of(name) {
+        patterns.extend(values.flat_map(ignores::split_alternatives)
+            .flat_map(ignores::expand_braces));
+    }
+    if patterns.is_empty() {
+        return None;
+    }
+    let normalize = args.is_present("normalize");
+    let patterns: vec::Vec<&str> = patterns.iter().map(String::as_str)
+        .collect();
+    match ignores::Patterns::new(&patterns, normalize) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            eprintln!("ERROR: {}", error);
+            std::process::exit(1);
+        },
+    }
+}
+
+// the patterns of root's .dockerignore with --dockerignore, and with
+// --context-preview a pattern hiding the .git directory of root, which comes
+// last so that no pattern in the file can show it again
+fn context_patterns(root: &path::Path, args: &clap::ArgMatches)
+    -> Option<ignores::Patterns> {
+    let preview = args.is_present("context-preview");
+    if !preview && !args.is_present("dockerignore") {
+        return None;
+    }
+    let mut patterns = match ignores::dockerignore(root) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("ERROR: \"{}\" {}",
+                      root.join(".dockerignore").to_string_lossy(), error);
+            std::process::exit(1);
+        },
+    };
+    if preview {
+        patterns.push(String::from(".git"));
+    }
+    let normalize = args.is_present("normalize");
+    let patterns: vec::Vec<&str> = patterns.iter().map(String::as_str)
+        .collect();
+    match ignores::Patterns::anchored(&patterns, normalize) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            eprintln!("ERROR: \"{}\" {}",
+                      root.join(".dockerignore").to_string_lossy(), error);
+            std::process::exit(1);
+        },
+    }
+}
+
+// parses a duration made up of one or more numbers each followed by a unit, s
+// for seconds, m for minutes, h for hours, d for days, or w for weeks, like
+// 90m, 2d, or 1h30m
+fn parse_duration(text: &str) -> Result<time::Duration, String> {
+    let invalid = || {
+        String::from("Is not a valid duration, expected a number followed by \
+                      s, m, h, d, or w, like 90m or 2d")
+    };
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60*60,
+            'd' => 24*60*60,
+            'w' => 7*24*60*60,
+            _ => return Err(invalid()),
+        };
+        let count: u64 = number.parse().map_err(|_| invalid())?;
+        seconds = count.checked_mul(unit)
+            .and_then(|value| seconds.checked_add(value))
+            .ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() || text.is_empty() {
+        return Err(invalid());
+    }
+    Ok(time::Duration::from_secs(seconds))
+}
+
+// turns a path argument into the roots it names, for shells that don't expand
+// wildcards themselves a path that doesn't exist but contains glob
+// metacharacters is matched against the filesystem, keeping only directories;
+// a path that exists is always taken literally even if it contains them
+fn expand_path(arg: &str) -> vec::Vec<path::PathBuf> {
+    let literal = path::PathBuf::from(arg);
+    if literal.exists() || !arg.contains(['*', '?', '[']) {
+        return vec::Vec::from([literal]);
+    }
+
+    let matches = match glob::glob(arg) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("ERROR: \"{}\" Is not a valid pattern: {}", arg, error);
+            std::process::exit(1);
+        },
+    };
+    let mut paths: vec::Vec<path::PathBuf> = matches
+        .filter_map(|path| path.ok())
+        .filter(|path| path.is_dir())
+        .collect();
+    if paths.is_empty() {
+        eprintln!("ERROR: \"{}\" No directories match this pattern", arg);
+        std::process::exit(1);
+    }
+    paths.sort();
+    paths
+}
+
+// a path given to --exclude the way it's compared against the paths of
+// entries, a relative one loses it's . components and trailing separators and
+// has it's .. components resolved, and an absolute one is canonicalized if it
+// exists; a path that leads out of every root is an error
+fn exclude_path(given: &str, roots: &[path::PathBuf]) -> path::PathBuf {
+    let path = path::Path::new(given);
+    if path.is_absolute() {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if roots.iter().any(|root| {
+            root.canonicalize().is_ok_and(|root| path.starts_with(root))
+        }) {
+            return path;
+        }
+    } else {
+        let mut normal = path::PathBuf::new();
+        let mut outside = false;
+        for component in path.components() {
+            match component {
+                path::Component::ParentDir => outside |= !normal.pop(),
+                path::Component::Normal(name) => normal.push(name),
+                _ => {},
+            }
+        }
+        if !outside {
+            return normal;
+        }
+    }
+    eprintln!("ERROR: \"{}\" Is outside of the tree", given);
+    std::process::exit(1);
+}
+
+// the paths of --exclude relative to root, absolute ones beneath another root
+// are left out, and the ones that don't exist get a warning since they're
+// most likely a typo
+fn root_exclude_paths(root: &path::Path, paths: &[path::PathBuf])
+    -> vec::Vec<path::PathBuf> {
+    paths.iter()
+        .filter_map(|path| {
+            let rel_path = match path.strip_prefix(root) {
+                Ok(value) => value.to_path_buf(),
+                Err(_) if path.is_absolute() => return None,
+                Err(_) => path.clone(),
+            };
+            if fs::symlink_metadata(root.join(&rel_path)).is_err() {
+                eprintln!("WARNING: \"{}\" doesn't exist in \"{}\", \
+                           --exclude doesn't hide anything for it",
+                          rel_path.to_string_lossy(), root.to_string_lossy());
+            }
+            Some(rel_path)
+        })
+        .collect()
+}
+
+// print root folder name with no prefix and start recursive subtree print, or
+// in the summary modes just traverse the tree and print the summary; errors
+// only stop the traversal in strict mode, where they're fatal
+fn print_tree(name: &str, root: &Source, rules: &ignores::Rules,
+              options: &Options, stdout: &mut Stdout) {
+    let out = Output::new(&mut *stdout);
+    if let Err(error) = try_print_tree(name, root, rules, options, out) {
+        print_failed(stdout, error);
+    }
+}
+
+// ends the process after printing a tree failed, an error reading the tree
+// that came from ctrl-c having been pressed ends it like ctrl-c does
+fn print_failed(stdout: &mut Stdout, error: TreeError) -> ! {
+    match error {
+        TreeError::Traversal(_) | TreeError::Interrupted
+            if is_interrupted() => exit_interrupted(stdout),
+        TreeError::Traversal(error) => {
+            stdout.finish();
+            eprintln!("ERROR: {}", error);
+            std::process::exit(1);
+        },
+        TreeError::Write(error) => stdout.failed(error),
+        TreeError::Interrupted => exit_interrupted(stdout),
+    }
+}
+
+// finds the first file in the tree that passes the filter, for --first-only,
+// and prints the branch from the root down to it with each directory on the
+// way as the only entry of the one above it; the walk is stopped as soon as
+// it's found, and nothing is printed if there isn't one, the returned value
+// is wether there was
+fn print_first_match(name: &str, root: &Source, rules: &ignores::Rules,
+                     options: &Options, stdout: &mut Stdout) -> bool {
+    let mut found = None;
+    for item in walk::TreeIter::over(root.clone(), rules.clone(), options) {
+        match item {
+            Ok(item) if item.entry.kind == Kind::Directory => {},
+            Ok(item) => {
+                found = Some((item.path(), item.entry.kind));
+                break;
+            },
+            // outside of strict mode the contents of an unreadable directory
+            // are skipped, the same as they are when printing the tree
+            Err(error) if error.ends_walk(options.filter.strict) => {
+                print_failed(stdout, TreeError::Traversal(error));
+            },
+            Err(_) => {},
+        }
+    }
+    if is_interrupted() {
+        exit_interrupted(stdout);
+    }
+    let (path, kind) = match found {
+        Some(value) => value,
+        None => return false,
+    };
+
+    let mut out = Output::new(&mut *stdout);
+    out.emit_line(vec::Vec::new(), options, |line| {
+        push_name(line, name, Kind::Directory, options);
+    });
+    let components: vec::Vec<_> = path.iter().collect();
+    for (i, component) in components.iter().enumerate() {
+        let prefix = vec::Vec::from_iter(iter::repeat_n(true, i+1));
+        let kind = if i+1 == components.len() { kind } else { Kind::Directory };
+        out.emit_line(vec::Vec::new(), options, |line| {
+            push_connectors(line, connectors(&prefix, true, options), options);
+            push_name(line, &component.to_string_lossy(), kind, options);
+        });
+    }
+    if let Err(error) = out.finish() {
+        stdout.failed(error);
+    }
+    true
+}
+
+fn try_print_tree<W: io::Write>(name: &str, root: &Source,
+                                rules: &ignores::Rules, options: &Options,
+                                mut out: Output<W>)
+    -> Result<(), TreeError> {
+    // the totals of --du are added up for the whole tree before any of it is
+    // printed, everything printed after this only looks them up
+    let totals = if options.du || options.summarize {
+        disk_usage(root, rules, options)?
+    } else {
+        Totals::new()
+    };
+    if is_interrupted() {
+        return Err(TreeError::Interrupted);
+    }
+    if options.prints_tree() {
+        if options.columns || options.post_order {
+            out.rows = Some(vec::Vec::new());
+        }
+        let usage = if options.du {
+            Some(usage_note(root_usage(&totals)))
+        } else if options.shallow_size {
+            Some(shallow_note(direct_usage(root, rules, options)?))
+        } else {
+            None
+        };
+        let open_error = match root {
+            Source::Path(path) if options.gnu
+                && fs::read_dir(path).is_err() => Some(gnu::OPEN_ERROR),
+            _ => None,
+        };
+        out.emit_line(vec::Vec::new(), options, |line| {
+            push_name(line, name, Kind::Directory, options);
+            if let Some(note) = usage.as_deref().or(open_error) {
+                line.push_str("  ");
+                line.push_str(note);
+            }
+        });
+    }
+    if options.summarize {
+        print_sizes(&mut out, root, rules, &totals, options)?;
+        return out.finish().map_err(TreeError::Write);
+    }
+    if options.ndjson {
+        let usage = options.du.then(|| root_usage(&totals));
+        let mut ndjson = ndjson::Ndjson { options, out, totals };
+        ndjson.root(root_details(root).0, usage);
+        let stats = walk::walk(root, rules, options, &mut ndjson)?;
+        if is_interrupted() {
+            return Err(TreeError::Interrupted);
+        }
+        ndjson.report(stats);
+        return ndjson.out.finish().map_err(TreeError::Write);
+    }
+    if options.json {
+        let tree = snapshot::build(name, root, rules, options)?;
+        if is_interrupted() {
+            return Err(TreeError::Interrupted);
+        }
+        snapshot::print(&mut out, tree);
+        return out.finish().map_err(TreeError::Write);
+    }
+    // what's been printed is still printed when the tree can't be, like the
+    // root with --columns
+    let scale = match options.bars {
+        Some(_) if options.prints_tree() => {
+            bar_scale(root, rules, path::Path::new(""), 1, &totals, options)
+        },
+        _ => Ok(0),
+    };
+    let scale = match scale {
+        Ok(value) => value,
+        Err(error) => {
+            out.flush_rows();
+            return Err(TreeError::Traversal(error));
+        },
+    };
+    let printed = out.row_count();
+    let mut printer = Printer {
+        out,
+        options,
+        totals,
+        summary: Summary::default(),
+        infos: vec::Vec::new(),
+        firsts: vec::Vec::new(),
+        printed: 0..printed,
+        held: vec::Vec::new(),
+        scale,
+        scales: vec::Vec::new(),
+        #[cfg(feature = "git")]
+        git: match root {
+            Source::Path(path) if options.git_status => {
+                git::Statuses::load(path)
+            },
+            _ => None,
+        },
+    };
+    #[cfg(feature = "sqlite")]
+    if let Some(file) = &options.sqlite {
+        printer.summary.export = match start_export(file, root) {
+            Ok(value) => Some(value),
+            Err(error) => export_failed(options, error),
+        };
+    }
+    let stats = walk::walk(root, rules, options, &mut printer);
+    if is_interrupted() || stats.is_err() {
+        printer.out.flush_rows();
+    }
+    if is_interrupted() {
+        return Err(TreeError::Interrupted);
+    }
+    let stats = stats?;
+    let Printer { mut out, summary, .. } = printer;
+    let follows = FOLLOW_LIMIT.get().is_some();
+    out.flush_rows();
+
+    if options.gnu {
+        gnu::add(summary.directories + summary.directory_links,
+                 summary.files + summary.symlinks - summary.directory_links
+                     + summary.other);
+        if stats.unreadable > 0 {
+            gnu::failed();
+        }
+    }
+
+    if options.porcelain {
+        out.write_line(&format!("directories={} files={} symlinks={} \
+                                 other={} size={}", summary.directories,
+                                summary.files, summary.symlinks,
+                                summary.other, summary.bytes));
+    } else if options.count_only {
+        out.write_line(&format!("directories: {}", summary.directories));
+        out.write_line(&format!("files: {}", summary.files));
+        out.write_line(&format!("symlinks: {}", summary.symlinks));
+        out.write_line(&format!("other: {}", summary.other));
+        out.write_line(&format!("size: {}", summary.bytes));
+        if options.filter.filters_time() {
+            out.write_line(&format!("time: {}", time_source().name()));
+        }
+        if follows {
+            out.write_line(&format!("followed: {}", stats.followed));
+        }
+    } else if options.duplicates {
+        print_duplicates(&mut out, root, summary.regular_files);
+    } else if options.sqlite.is_some() {
+        #[cfg(feature = "sqlite")]
+        if let Some(export) = summary.export {
+            if let Err(error) = export.finish() {
+                export_failed(options, error);
+            }
+        }
+    } else if options.type_summary {
+        // the timestamp the entries were filtered by is named, since the
+        // counts depend on it
+        let time = if options.filter.filters_time() {
+            format!(", by {}", time_source().name())
+        } else {
+            String::new()
+        };
+        let followed = if follows {
+            format!(", {} followed", stats.followed)
+        } else {
+            String::new()
+        };
+        out.write_line("");
+        out.write_line(&format!("{} directories, {} files, {} symlinks{}, {} \
+                                 other{}", summary.directories, summary.files,
+                                summary.symlinks, followed, summary.other,
+                                time));
+    }
+    if options.prints_tree() && options.show_empty && !options.no_report {
+        out.write_line("");
+        out.write_line(&format!("{} empty {}", summary.empty_dirs,
+                                if summary.empty_dirs == 1 { "directory" }
+                                else { "directories" }));
+    }
+    if options.prints_tree() && !options.no_report
+        && !summary.mount_points.is_empty() {
+        let count = summary.mount_points.len();
+        out.write_line("");
+        out.write_line(&format!("{} {} not crossed:", count,
+                                if count == 1 { "mount point" }
+                                else { "mount points" }));
+        for path in summary.mount_points.iter() {
+            out.write_line(&format!("    {}", path.to_string_lossy()));
+        }
+    }
+
+    // the directories that timed out are relative to the root as well, and
+    // are only listed for the tree they're in
+    let timed_out = std::mem::take(&mut *lock_timed_out());
+    if options.prints_tree() && !options.no_report && !timed_out.is_empty() {
+        let count = timed_out.len();
+        out.write_line("");
+        out.write_line(&format!("{} {} timed out:", count,
+                                if count == 1 { "directory" }
+                                else { "directories" }));
+        for path in timed_out.iter() {
+            let path = match options.filter.root.as_ref()
+                .and_then(|root| path.strip_prefix(root).ok()) {
+                Some(rel) if rel.as_os_str().is_empty() => path::Path::new("."),
+                Some(rel) => rel,
+                None => path,
+            };
+            out.write_line(&format!("    {}", path.to_string_lossy()));
+        }
+    }
+    out.finish().map_err(TreeError::Write)
+}
+
+// creates or opens the database for an export and writes the root's row
+#[cfg(feature = "sqlite")]
+fn start_export(file: &path::Path, root: &Source)
+    -> rusqlite::Result<sqlite::Export> {
+    let (size, mtime) = root_details(root);
+    let mut export = sqlite::Export::create(file)?;
+    export.insert(".", None, Kind::Directory, size, mtime)?;
+    Ok(export)
+}
+
+// the size and modification time of the root, which unlike those of the
+// entries beneath it aren't read while listing
+fn root_details(root: &Source) -> (Option<u64>, Option<time::SystemTime>) {
+    match root {
+        Source::Path(path) => match fs::metadata(path) {
+            Ok(metadata) => (Some(metadata.len()), entry_time(&metadata)),
+            Err(_) => (None, None),
+        },
+        Source::Node(node) => (node.size, node.mtime.map(|mtime| {
+            time::UNIX_EPOCH + time::Duration::from_secs(mtime)
+        })),
+        Source::Roots(_) => (None, None),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn export_failed(options: &Options, error: rusqlite::Error) -> ! {
+    let file = options.sqlite.as_deref().unwrap_or(path::Path::new(""));
+    eprintln!("ERROR: \"{}\" {}", file.to_string_lossy(), error);
+    std::process::exit(1);
+}
+
+// a name in unicode normalization form C, which is what --normalize compares
+// names in, names that aren't valid unicode or are already composed are left
+// as they are
+fn nfc(name: &ffi::OsStr) -> borrow::Cow<'_, ffi::OsStr> {
+    match name.to_str() {
+        Some(text) if !unicode_normalization::is_nfc(text) => {
+            borrow::Cow::Owned(ffi::OsString::from(text.nfc()
+                .collect::<String>()))
+        },
+        _ => borrow::Cow::Borrowed(name),
+    }
+}
+
+// a relative path with its components joined by /, regardless of the
+// platform, with the root itself being "."
+fn slash_path(path: &path::Path) -> String {
+    let components: vec::Vec<_> = path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    if components.is_empty() {
+        String::from(".")
+    } else {
+        components.join("/")
+    }
+}
+
+// prints the entries of the root with the total size beneath each one in place
+// of the rest of the tree, sorting sees those totals as the entries' sizes
+fn print_sizes(out: &mut Output<impl io::Write>, root: &Source,
+               rules: &ignores::Rules, totals: &Totals, options: &Options)
+    -> Result<(), TraversalError> {
+    let entries = match list(root, rules, &options.filter,
+                             &sort::Sort::default(), options.show_files) {
+        Ok(value) => value,
+        Err(error) if error.ends_walk(options.filter.strict) => {
+            return Err(error);
+        },
+        Err(_) => {
+            print_unreadable(out, &[], options);
+            return Ok(());
+        },
+    };
+
+    let mut sizes: vec::Vec<(Entry, u64, bool)> = vec::Vec::new();
+    for entry in entries {
+        let (total, complete) = match entry.kind {
+            Kind::Directory => {
+                let usage = totals.get(path::Path::new(&entry.name))
+                    .copied()
+                    .unwrap_or_default();
+                (usage.size, usage.complete)
+            },
+            _ => (entry.meta().size.unwrap_or(0), true),
+        };
+        sizes.push((entry, total, complete));
+    }
+    sizes.sort_by(|(a, a_total, _), (b, b_total, _)| {
+        let a_fields = sort::SortFields { size: Some(*a_total),
+                                          ..sort_fields(a, &options.sort) };
+        let b_fields = sort::SortFields { size: Some(*b_total),
+                                          ..sort_fields(b, &options.sort) };
+        options.sort.compare(&a_fields, &b_fields)
+    });
+
+    let entries_count = sizes.len();
+    for (i, (entry, total, complete)) in sizes.iter().enumerate() {
+        let size = format!("{}{}", format::human_size(*total),
+                           if *complete { "" } else { "*" });
+        let mut text = String::new();
+        let columns = if options.columns {
+            vec::Vec::from([size])
+        } else {
+            text.push('[');
+            text.push_str(&size);
+            text.push_str("]  ");
+            vec::Vec::new()
+        };
+        push_name(&mut text, &entry.name.to_string_lossy(), entry.kind,
+                  options);
+        if entry.meta().sparse {
+            text.push_str("  [sparse]");
+        }
+        print_line(out, &[i == entries_count-1], &text, None, columns,
+                   options);
+    }
+    Ok(())
+}
+
+// the totals of the files beneath a directory; only regular files count,
+// like with --count, so symlinks and special files add nothing
+#[derive(Clone, Copy, Default)]
+struct Usage {
+    size: u64,
+    files: u64,
+    // wether every directory beneath it could be read, so that the totals are
+    // exact rather than lower bounds
+    complete: bool,
+}
+
+// the totals of every directory that was walked, by it's path relative to the
+// root, which is the empty path
+type Totals = collections::HashMap<path::PathBuf, Usage>;
+
+// the totals of --du for every directory beneath root, added up in one walk
+// of the whole tree, see walk::walk_all, before any of it is printed; the
+// walk doesn't descend into a directory twice, so a loop or a bind mount is
+// only counted once, and it follows the same symlinks as the printed walk,
+// so the count --max-symlinks limits is put back once it's done
+fn disk_usage(root: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<Totals, TraversalError> {
+    let followed = FOLLOWED.load(atomic::Ordering::SeqCst);
+    let mut tally = Tally {
+        last_dir: path::PathBuf::new(),
+        open: vec::Vec::new(),
+        totals: Totals::new(),
+    };
+    let result = walk::walk_all(root, rules, options, &mut tally);
+    FOLLOWED.store(followed, atomic::Ordering::SeqCst);
+    result?;
+    Ok(tally.totals)
+}
+
+// the totals of the root, which are only missing when it couldn't be read
+fn root_usage(totals: &Totals) -> Usage {
+    totals.get(path::Path::new("")).copied().unwrap_or_default()
+}
+
+// the visitor that adds up the totals of disk_usage, a directory's totals are
+// done once it's left and are then added to those of the directory it's in
+struct Tally {
+    // the path of the directory visited last, which is the one a walk enters
+    // next, or the root before anything was visited
+    last_dir: path::PathBuf,
+    // the directories being walked and their totals so far, innermost last
+    open: vec::Vec<(path::PathBuf, Usage)>,
+    totals: Totals,
+}
+
+impl walk::Visitor for Tally {
+    fn visit(&mut self, node: &walk::Node)
+        -> Result<walk::WalkAction, TraversalError> {
+        if is_interrupted() {
+            return Ok(walk::WalkAction::Stop);
+        }
+        let entry = node.entry;
+        match (entry.kind, self.open.last_mut()) {
+            (Kind::Directory, _) => {
+                self.last_dir = node.rel_path.join(&entry.name);
+            },
+            (Kind::File, Some((_, usage))) => {
+                usage.size += entry.meta().size.unwrap_or(0);
+                usage.files += 1;
+            },
+            _ => {},
+        }
+        Ok(walk::WalkAction::Continue)
+    }
+
+    fn enter(&mut self, _dir: &Source) {
+        let usage = Usage { complete: true, ..Usage::default() };
+        self.open.push((std::mem::take(&mut self.last_dir), usage));
+    }
+
+    fn leave(&mut self) {
+        let (path, usage) = match self.open.pop() {
+            Some(value) => value,
+            None => return,
+        };
+        if let Some((_, parent)) = self.open.last_mut() {
+            parent.size += usage.size;
+            parent.files += usage.files;
+            parent.complete &= usage.complete;
+        }
+        self.totals.insert(path, usage);
+    }
+
+    fn unreadable(&mut self, _prefix: &[bool], rel_path: &path::Path,
+                  _error: &TraversalError) {
+        if let Some((_, parent)) = self.open.last_mut() {
+            parent.complete = false;
+        }
+        self.totals.insert(rel_path.to_path_buf(), Usage::default());
+    }
+}
+
+// the totals shown next to a directory with --du, directories whose contents
+// aren't shown, because of the depth limit or a note like [already visited],
+// don't get any
+fn directory_usage(node: &walk::Node, totals: &Totals, options: &Options)
+    -> Option<Usage> {
+    if !options.du || !shows_contents(node, options) {
+        return None;
+    }
+    totals.get(&node.rel_path.join(&node.entry.name)).copied()
+}
+
+// the totals shown next to a directory with --shallow-size, for the same
+// directories as with --du
+fn shallow_usage(node: &walk::Node, options: &Options)
+    -> Result<Option<Usage>, TraversalError> {
+    let entry = node.entry;
+    if !options.shallow_size || !shows_contents(node, options) {
+        return Ok(None);
+    }
+    let rules = child_rules(&entry.source(), node.rules, options);
+    direct_usage(&entry.source(), &rules, options).map(Some)
+}
+
+// wether an entry is a directory whose contents are shown beneath it
+fn shows_contents(node: &walk::Node, options: &Options) -> bool {
+    node.entry.kind == Kind::Directory && node.note.is_none()
+        && options.max_depth.is_none_or(|max| node.depth() < max)
+}
+
+// the total size and number of the files directly in a directory, the files
+// in it's subdirectories aren't counted
+fn direct_usage(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<Usage, TraversalError> {
+    prescanned(&options.prescans.usage, source, || {
+        scan_usage(source, rules, options)
+    })
+}
+
+fn scan_usage(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<Usage, TraversalError> {
+    let entries = match entries(source, options.filter.strict) {
+        Ok(value) => value,
+        Err(error) if error.ends_walk(options.filter.strict) => {
+            return Err(error);
+        },
+        Err(_) => return Ok(Usage::default()),
+    };
+
+    let mut usage = Usage { complete: true, ..Usage::default() };
+    for entry in entries {
+        if entry.kind == Kind::File
+            && is_listed(&entry, rules, &options.filter, true) {
+            usage.size += entry.meta().size.unwrap_or(0);
+            usage.files += 1;
+        }
+    }
+    Ok(usage)
+}
+
+// the totals of a directory as they're displayed, like [1.2M in 48 files]
+fn usage_note(usage: Usage) -> String {
+    format!("[{}{} in {} {}]", if usage.complete { "" } else { "\u{2265}" },
+            format::human_size(usage.size), usage.files,
+            if usage.files == 1 { "file" } else { "files" })
+}
+
+// the totals of --shallow-size as they're displayed, marked so that they
+// can't be mistaken for those of --du, like [1.2K in 3 files, shallow]
+fn shallow_note(usage: Usage) -> String {
+    format!("[{}{} in {} {}, shallow]",
+            if usage.complete { "" } else { "\u{2265}" },
+            format::human_size(usage.size), usage.files,
+            if usage.files == 1 { "file" } else { "files" })
+}
+
+// what the bars of --bars are scaled to
+#[derive(Clone, Copy, PartialEq)]
+enum BarScale {
+    // the largest entry of the same directory
+    Directory,
+    // the largest entry anywhere in the tree, with --bars-global
+    Global,
+}
+
+// the cells a bar takes up, and the fewest it's shortened to to fit in the
+// terminal before it's left out instead
+const BAR_WIDTH: usize = 10;
+const MIN_BAR_WIDTH: usize = 3;
+
+// a bar to draw after an entry, with the size the whole bar stands for
+#[derive(Clone, Copy)]
+struct Bar {
+    size: u64,
+    scale: u64,
+}
+
+// the size an entry's bar is drawn for, a directory's is the total that's
+// shown next to it so it only has one with --du or --shallow-size; rules are
+// those for the directory the entry is in, rel_path is the path of that
+// directory relative to the root, and depth is the entry's own
+fn bar_size(entry: &Entry, rules: &ignores::Rules, rel_path: &path::Path,
+            depth: usize, totals: &Totals, options: &Options)
+    -> Result<Option<u64>, TraversalError> {
+    if entry.kind != Kind::Directory {
+        return Ok(entry.meta().size);
+    }
+    if options.max_depth.is_some_and(|max| depth >= max) {
+        return Ok(None);
+    }
+    let rules = child_rules(&entry.source(), rules, options);
+    let usage = if options.du {
+        match totals.get(&rel_path.join(&entry.name)) {
+            Some(usage) => *usage,
+            None => return Ok(None),
+        }
+    } else if options.shallow_size {
+        direct_usage(&entry.source(), &rules, options)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(usage.size))
+}
+
+// the size the bars of the entries of a directory are scaled to, the largest
+// of them, or with --bars-global the largest of anything beneath it; rules
+// are those for the contents of the directory, rel_path is it's path
+// relative to the root, and depth is that of it's entries
+fn bar_scale(source: &Source, rules: &ignores::Rules, rel_path: &path::Path,
+             depth: usize, totals: &Totals, options: &Options)
+    -> Result<u64, TraversalError> {
+    let mut largest = 0;
+    for entry in readable_entries(source, options)? {
+        if !is_visible(&entry, rules, options)? {
+            continue;
+        }
+        let size = bar_size(&entry, rules, rel_path, depth, totals, options)?;
+        largest = largest.max(size.unwrap_or(0));
+        // with --du a directory's total already covers everything in it
+        if options.bars == Some(BarScale::Global) && !options.du
+            && entry.kind == Kind::Directory
+            && !is_mount_point(&entry, options)
+            && options.max_depth.is_none_or(|max| depth < max) {
+            let rules = child_rules(&entry.source(), rules, options);
+            largest = largest.max(bar_scale(&entry.source(), &rules,
+                                            &rel_path.join(&entry.name),
+                                            depth+1, totals, options)?);
+        }
+    }
+    Ok(largest)
+}
+
+// draws a bar of the given number of cells, filled in proportion to the size
+// out of the scale, in a block character or # in ASCII; at least one cell is
+// filled, so a bar is never mistaken for that of something empty
+fn push_bar(text: &mut String, bar: Bar, cells: usize, options: &Options) {
+    let filled = match bar.scale {
+        0 => 0,
+        scale => {
+            let scale = u128::from(scale);
+            let filled = (u128::from(bar.size) * cells as u128 + scale / 2)
+                / scale;
+            (filled as usize).clamp(1, cells)
+        },
+    };
+    let (full, empty) = if options.ascii {
+        ("#", ".")
+    } else {
+        ("\u{2587}", "\u{2581}")
+    };
+    push_repeated(text, full, filled, Some("36").filter(|_| options.color));
+    push_repeated(text, empty, cells - filled,
+                  Some("2").filter(|_| options.color));
+}
+
+// puts count copies of text in line, styled like push_styled would
+fn push_repeated(line: &mut String, text: &str, count: usize,
+                 style: Option<&str>) {
+    if count == 0 {
+        return;
+    }
+    push_style(line, style);
+    line.extend(iter::repeat_n(text, count));
+    if style.is_some() {
+        line.push_str(STYLE_RESET);
+    }
+}
+
+// compares the tree under root against the snapshot in file and prints what
+// differs, returning wether anything did
+fn print_differences(file: &str, name: &str, root: &Source,
+                     rules: &ignores::Rules, options: &Options,
+                     stdout: &mut Stdout) -> bool {
+    let saved = match snapshot::load(path::Path::new(file)) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("ERROR: \"{}\" {}", file, error);
+            std::process::exit(1);
+        },
+    };
+    let current = match snapshot::build(name, root, rules, options) {
+        Ok(value) => value,
+        Err(error) => print_failed(stdout, TreeError::Traversal(error)),
+    };
+    if is_interrupted() {
+        exit_interrupted(stdout);
+    }
+    let dir = match root {
+        Source::Path(path) => path.as_path(),
+        Source::Node(_) | Source::Roots(_) => path::Path::new(""),
+    };
+    let differences = diff::compare(&saved, &current, dir);
+    let mut out = Output::new(&mut *stdout);
+    out.emit_line(vec::Vec::new(), options, |line| {
+        push_name(line, name, Kind::Directory, options);
+    });
+    print_difference_tree(&mut out, &differences, &[], options);
+    if let Err(error) = out.finish() {
+        stdout.failed(error);
+    }
+    differences.iter().any(diff::Difference::differs)
+}
+
+fn print_difference_tree(out: &mut Output<impl io::Write>,
+                         differences: &[diff::Difference], prefix: &[bool],
+                         options: &Options) {
+    for (i, difference) in differences.iter().enumerate() {
+        let mut new_prefix = vec::Vec::from(prefix);
+        new_prefix.push(i == differences.len()-1);
+        let mut name = String::new();
+        push_name(&mut name, &difference.name, difference.kind, options);
+        let (text, style) = match difference.change {
+            Some(change) => {
+                let style = match change {
+                    diff::Change::Added => Some("32"),
+                    diff::Change::Removed => Some("31"),
+                    diff::Change::Changed => Some("33"),
+                    diff::Change::NotCompared => None,
+                };
+                let note = if change == diff::Change::NotCompared {
+                    "  [not compared]"
+                } else {
+                    ""
+                };
+                (format!("{} {}{}", change.marker(), name, note),
+                 style.filter(|_| options.color))
+            },
+            None => (name, None),
+        };
+        print_line(out, &new_prefix, &text, style, vec::Vec::new(),
+                   options);
+        print_difference_tree(out, &difference.children, &new_prefix,
+                              options);
+    }
+}
+
+// prints each group of identical files as its hash followed by the paths of
+// its members relative to the root, with a blank line between groups
+fn print_duplicates(out: &mut Output<impl io::Write>, root: &Source,
+                    files: vec::Vec<(path::PathBuf, u64)>) {
+    let root = match root {
+        Source::Path(path) => path.as_path(),
+        Source::Node(_) | Source::Roots(_) => path::Path::new(""),
+    };
+    let parallel = PARALLEL_STATS.load(atomic::Ordering::SeqCst);
+    let report = duplicates::find(files, parallel);
+    let relative = |path: &path::Path| {
+        path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned()
+    };
+    for group in report.groups.iter() {
+        out.write_line(&format!("{}  [{} wasted]", group.hash,
+                                format::human_size(group.wasted())));
+        for path in group.paths.iter() {
+            out.write_line(&format!("    {}", relative(path)));
+        }
+        out.write_line("");
+    }
+    let wasted: u64 = report.groups.iter().map(duplicates::Group::wasted).sum();
+    let count = report.groups.len();
+    out.write_line(&format!("{} {} of duplicates, {} wasted", count,
+                            if count == 1 { "group" } else { "groups" },
+                            format::human_size(wasted)));
+
+    // hardlinks are the same file under more than one name, so they aren't
+    // duplicates, but they're listed since they also look like copies
+    for paths in report.hardlinks.iter() {
+        out.write_line("");
+        out.write_line("hardlinks of the same file");
+        for path in paths.iter() {
+            out.write_line(&format!("    {}", relative(path)));
+        }
+    }
+}
+
+// where the contents of a directory come from, either the filesystem, a
+// previously loaded snapshot, or for the synthetic root of --merge the roots
+// it holds
+#[derive(Clone)]
+enum Source<'a> {
+    Path(path::PathBuf),
+    Node(&'a snapshot::Node),
+    Roots(&'a [Entry<'a>]),
+}
+
+// a single entry of a directory, only it's name and kind are read along
+// with the listing; the rest of the metadata that filtering, sorting, and
+// formatting can depend on is read the first time it's asked for, unless
+// FILE_METADATA says it will be, and the path is joined from the directory
+// it's in when it's needed, so a directory of millions of files doesn't hold
+// millions of paths and metadata that are never looked at
+#[derive(Clone)]
+struct Entry<'a> {
+    name: ffi::OsString,
+    kind: Kind,
+    // for a symlink to a directory with --follow, wether it was followed
+    follow: Option<Follow>,
+    meta: sync::OnceLock<Box<Meta>>,
+    origin: Origin<'a>,
+}
+
+// the metadata of an entry, which is missing altogether if reading it failed
+// after the entry was listed
+#[derive(Clone, Default)]
+struct Meta {
+    size: Option<u64>,
+    mtime: Option<time::SystemTime>,
+    // when the metadata last changed, which sorting by ctime looks at
+    // regardless of --time
+    ctime: Option<time::SystemTime>,
+    mode: Option<u32>,
+    // wether the entry is a file with holes in it, see is_sparse
+    sparse: bool,
+    // the device and inode numbers of the entry, or of the directory it
+    // points to if it's a followed symlink, see file_id
+    id: Option<(u64, u64)>,
+}
+
+// where an entry comes from, the entries read from the same directory share
+// it's path
+#[derive(Clone)]
+enum Origin<'a> {
+    Dir(sync::Arc<path::Path>),
+    Source(Source<'a>),
+}
+
+impl<'a> Entry<'a> {
+    // an entry read from the directory at dir, with the metadata already
+    // read if it's given
+    fn read(name: ffi::OsString, kind: Kind, follow: Option<Follow>,
+            meta: Option<Meta>, dir: &sync::Arc<path::Path>) -> Entry<'a> {
+        Entry {
+            name,
+            kind,
+            follow,
+            meta: match meta {
+                Some(meta) => sync::OnceLock::from(Box::new(meta)),
+                None => sync::OnceLock::new(),
+            },
+            origin: Origin::Dir(sync::Arc::clone(dir)),
+        }
+    }
+
+    fn source(&self) -> Source<'a> {
+        match &self.origin {
+            Origin::Dir(dir) => Source::Path(dir.join(&self.name)),
+            Origin::Source(source) => source.clone(),
+        }
+    }
+
+    // the path of an entry on the filesystem, entries of snapshots don't have
+    // one
+    fn path(&self) -> Option<path::PathBuf> {
+        match self.source() {
+            Source::Path(path) => Some(path),
+            Source::Node(_) | Source::Roots(_) => None,
+        }
+    }
+
+    fn meta(&self) -> &Meta {
+        self.meta.get_or_init(|| {
+            let metadata = self.path().and_then(|path| {
+                match self.follow {
+                    Some(Follow::Followed) => fs::metadata(path),
+                    _ => fs::symlink_metadata(path),
+                }.ok()
+            });
+            Box::new(metadata.map_or_else(Meta::default, |metadata| {
+                meta_of(&metadata, self.kind)
+            }))
+        })
+    }
+}
+
+/// An error reading part of the tree, along with the path it happened at.
+#[derive(Debug)]
+pub struct TraversalError {
+    path: path::PathBuf,
+    error: io::Error,
+}
+
+impl TraversalError {
+    /// The path of the directory or entry that couldn't be read.
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// Wether the process or the system ran out of file descriptors, which
+    /// says nothing about the directory itself; a walk always stops on this
+    /// error, since going on past it would silently leave part of the tree
+    /// out.
+    pub fn is_out_of_handles(&self) -> bool {
+        is_out_of_handles(&self.error)
+    }
+
+    // wether the error stops a walk, rather than the directory it happened
+    // at being skipped
+    fn ends_walk(&self, strict: bool) -> bool {
+        strict || self.is_out_of_handles()
+    }
+}
+
+impl fmt::Display for TraversalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" {}", self.path.to_string_lossy(), self.error)
+    }
+}
+
+impl std::error::Error for TraversalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// decides which files are listed, directories are always listed so that the
+// structure of the tree is kept, use prune to hide the ones that end up
+// without any files
+#[derive(Default)]
+struct Filter {
+    // what files have to be newer than with --newer-than-file and
+    // --changed-since
+    newer_than: Option<Reference>,
+    // the point after which files count as changed with --only-changed
+    changed_within: Option<time::SystemTime>,
+    empty: Option<Empty>,
+    // the types of files --type shows, if it was given
+    types: Option<Types>,
+    // decides which files are shown before the rest of the filter
+    shows: Option<Hook>,
+    exclude: Option<ignores::Patterns>,
+    // the paths --exclude hides, relative to the root
+    exclude_paths: vec::Vec<path::PathBuf>,
+    // the patterns of the root's .dockerignore with --dockerignore
+    context: Option<ignores::Patterns>,
+    strict: bool,
+    // wether names starting with a . are shown, they're only hidden by --gnu
+    dotfiles: bool,
+    // the root of the tree being listed
+    root: Option<path::PathBuf>,
+    // the files --git-tracked limits the tree to
+    #[cfg(feature = "git")]
+    tracked: Option<git::Tracked>,
+}
+
+// the time of a reference file that files have to be newer than, along with
+// why the ones that aren't are hidden, which names the option it was given
+// with
+#[derive(Clone, Copy)]
+struct Reference {
+    time: time::SystemTime,
+    rejection: &'static str,
+}
+
+/// What a hook decides for an entry, a rejection comes with the reason for
+/// it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    /// Shows a file, or descends into a directory.
+    Accept,
+    Reject(&'static str),
+    /// Leaves it to the rest of the filter.
+    Defer,
+}
+
+// a predicate that decides something about an entry, given the entry and
+// it's path relative to the root, which entries of a snapshot don't have;
+// the entries hidden by -I, a .dockerignore, or an ignore file never reach
+// a hook, and for the files that do the shows hook of the filter comes
+// before the other checks, so a file it accepts is shown regardless of
+// them except --empty and --empty-dirs-only; hooked filters are part of the
+// filter, so --prune hides the directories they leave empty
+type Hook = Box<dyn Fn(&Entry, Option<&path::Path>) -> Decision + Send + Sync>;
+
+// what --empty and --empty-dirs-only look for, empty directories are the ones
+// that don't have any files that pass the rest of the filter beneath them
+#[derive(Clone, Copy, PartialEq)]
+enum Empty {
+    FilesAndDirs,
+    DirsOnly,
+}
+
+// the types of files --type shows, directories aren't filtered by it so they
+// don't have one
+#[derive(Clone, Copy)]
+struct Types {
+    files: bool,
+    symlinks: bool,
+    executables: bool,
+}
+
+impl Types {
+    fn accepts(&self, entry: &Entry) -> bool {
+        let executable = entry.meta().mode
+            .is_some_and(|mode| mode & 0o111 != 0);
+        match entry.kind {
+            Kind::File => self.files || (self.executables && executable),
+            Kind::Symlink => self.symlinks,
+            _ => false,
+        }
+    }
+}
+
+impl Filter {
+    fn accepts_file(&self, entry: &Entry) -> bool {
+        let empty = match self.empty {
+            Some(Empty::FilesAndDirs) => {
+                entry.kind == Kind::File && entry.meta().size == Some(0)
+            },
+            Some(Empty::DirsOnly) => false,
+            None => true,
+        };
+        empty && self.accepts_file_contents(entry)
+    }
+
+    // the part of the filter that doesn't depend on looking for empty
+    // entries, this is what decides wether a directory counts as empty
+    fn accepts_file_contents(&self, entry: &Entry) -> bool {
+        match self.hook_decision(entry) {
+            Decision::Accept => return true,
+            Decision::Reject(_) => return false,
+            Decision::Defer => {},
+        }
+        let newer = match self.newer_than {
+            Some(reference) => {
+                entry.meta().mtime.is_some_and(|mtime| mtime > reference.time)
+            },
+            None => true,
+        };
+        let changed = self.changed_within
+            .is_none_or(|since| is_changed(entry, since));
+        let typed = self.types.is_none_or(|types| types.accepts(entry));
+        newer && changed && typed && self.is_tracked(entry)
+    }
+
+    fn hook_decision(&self, entry: &Entry) -> Decision {
+        match &self.shows {
+            Some(shows) => shows(entry, self.rel_path(entry).as_deref()),
+            None => Decision::Defer,
+        }
+    }
+
+    // the path of an entry relative to the root, which patterns with a / in
+    // them are matched against
+    fn rel_path(&self, entry: &Entry) -> Option<path::PathBuf> {
+        let root = self.root.as_ref()?;
+        Some(entry.path()?.strip_prefix(root).ok()?.to_path_buf())
+    }
+
+    // why a file isn't accepted, checked in the same order as accepts_file
+    fn rejection(&self, entry: &Entry) -> &'static str {
+        let empty = entry.kind == Kind::File && entry.meta().size == Some(0);
+        match self.empty {
+            Some(Empty::FilesAndDirs) if !empty => {
+                return "it isn't empty (--empty)";
+            },
+            Some(Empty::DirsOnly) => return "only directories are shown \
+                                             (--empty-dirs-only)",
+            _ => {},
+        }
+        if let Decision::Reject(reason) = self.hook_decision(entry) {
+            return reason;
+        }
+        match self.newer_than {
+            Some(reference) if entry.meta().mtime
+                .is_none_or(|mtime| mtime <= reference.time) => {
+                return reference.rejection;
+            },
+            _ => {},
+        }
+        if self.changed_within
+            .is_some_and(|since| !is_changed(entry, since)) {
+            return "it didn't change within the --changed-within duration";
+        }
+        if self.types.is_some_and(|types| !types.accepts(entry)) {
+            return "it isn't of a type --type shows";
+        }
+        if !self.is_tracked(entry) {
+            return "it isn't tracked by git (--git-tracked)";
+        }
+        "it didn't pass the filter"
+    }
+
+    // wether entries are filtered by any of their timestamps
+    fn filters_time(&self) -> bool {
+        self.newer_than.is_some() || self.changed_within.is_some()
+    }
+
+    #[cfg(feature = "git")]
+    fn is_tracked(&self, entry: &Entry) -> bool {
+        match &self.tracked {
+            Some(tracked) => {
+                entry.path().is_some_and(|path| tracked.contains(&path))
+            },
+            None => true,
+        }
+    }
+
+    #[cfg(not(feature = "git"))]
+    fn is_tracked(&self, _entry: &Entry) -> bool {
+        true
+    }
+}
+
+// wether an entry was modified at or after since, entries without a
+// modification time never count as changed
+fn is_changed(entry: &Entry, since: time::SystemTime) -> bool {
+    entry.meta().mtime.is_some_and(|mtime| mtime >= since)
+}
+
+// lists the contents of a single directory, sorted and with the filter
+// applied; files are only included if files is set
+fn list<'a>(source: &Source<'a>, rules: &ignores::Rules, filter: &Filter,
+            sort: &sort::Sort, files: bool)
+    -> Result<vec::Vec<Entry<'a>>, TraversalError> {
+
+    // the contents of a directory on the filesystem are already collected by
+    // the time they're read, so they're filtered in place rather than copied
+    // into a second vector, which would double the peak memory of listing a
+    // huge directory
+    let mut entries = match source {
+        Source::Path(path) => read_entries(path, filter.strict)?,
+        Source::Node(node) => node_entries(node)?.collect(),
+        Source::Roots(roots) => roots.to_vec(),
+    };
+    // the roots of --merge are always shown, like any other root
+    if !matches!(source, Source::Roots(_)) {
+        entries.retain(|entry| keeps_entry(entry, rules, filter, files));
+    }
+    // reclaim unused memory now that we're done adding to entries
+    entries.shrink_to_fit();
+
+    if sort.is_default() {
+        // sort lexicographically based on name, this is the same order that
+        // snapshots are saved in; only the file name is compared, never the
+        // full path, so the order doesn't depend on where the root is or on
+        // the order the platform returns entries in
+        entries.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    } else if sort.is_unsorted() {
+        // the order they were read in, which is kept by a stable sort that
+        // only groups directories first
+        if sort.is_reversed() {
+            entries.reverse();
+        }
+        entries.sort_by(|a, b| {
+            sort.compare(&sort_fields(a, sort), &sort_fields(b, sort))
+        });
+    } else {
+        entries.sort_by(|a, b| {
+            sort.compare(&sort_fields(a, sort), &sort_fields(b, sort))
+        });
+    }
+    Ok(entries)
+}
+
+// is_listed for the entries of a listing, which --verbose logs
+fn keeps_entry(entry: &Entry, rules: &ignores::Rules, filter: &Filter,
+               files: bool) -> bool {
+    let listed = is_listed(entry, rules, filter, files);
+    if log::log_enabled!(log::Level::Info) {
+        log_listing(entry, listed, rules, filter, files);
+    }
+    listed
+}
+
+// wether an entry passes the filter (which directories always do) and isn't
+// excluded by a pattern or an ignore file
+fn is_listed(entry: &Entry, rules: &ignores::Rules, filter: &Filter,
+             files: bool) -> bool {
+    (entry.kind == Kind::Directory || (files && filter.accepts_file(entry)))
+        && !is_ignored(entry, rules, filter)
+}
+
+// logs wether an entry is listed, and why it isn't if it's not, the reasons
+// are checked in the same order as in is_listed
+fn log_listing(entry: &Entry, listed: bool, rules: &ignores::Rules,
+               filter: &Filter, files: bool) {
+    let path = log_path(entry);
+    if listed {
+        log::debug!("\"{}\" listed", path);
+        return;
+    }
+    let excluded = filter.exclude.as_ref()
+        .filter(|exclude| {
+            exclude.is_match(&entry.name, filter.rel_path(entry).as_deref())
+        })
+        .and_then(|exclude| {
+            exclude.deciding(&entry.name, filter.rel_path(entry).as_deref())
+        });
+    let context = filter.context.as_ref()
+        .filter(|_| is_ignored_by_context(entry, filter))
+        .and_then(|context| {
+            context.deciding(&entry.name, filter.rel_path(entry).as_deref())
+        });
+    let ignored_by = match &entry.source() {
+        Source::Path(path) => {
+            rules.ignored_by(path, entry.kind == Kind::Directory)
+        },
+        Source::Node(_) | Source::Roots(_) => None,
+    };
+    if let Some(pattern) = excluded {
+        log::info!("\"{}\" skipped, it matches the -I pattern \"{}\"", path,
+                   pattern);
+    } else if let Some(pattern) = context {
+        log::info!("\"{}\" skipped, it matches the .dockerignore pattern \
+                    \"{}\"", path, pattern);
+    } else if is_excluded_path(entry, filter) {
+        log::info!("\"{}\" skipped, its path was given to --exclude", path);
+    } else if let Some((rule, file)) = ignored_by {
+        match file {
+            Some(file) => log::info!("\"{}\" skipped, it matches the rule \
+                                      \"{}\" in \"{}\"", path, rule,
+                                     file.to_string_lossy()),
+            None => log::info!("\"{}\" skipped, it matches the ignore rule \
+                                \"{}\"", path, rule),
+        }
+    } else if !files {
+        log::debug!("\"{}\" skipped, files are only shown with -f", path);
+    } else {
+        log::info!("\"{}\" skipped, {}", path, filter.rejection(entry));
+    }
+}
+
+// how an entry is named in the log, entries of snapshots only have their name
+fn log_path<'e>(entry: &'e Entry) -> borrow::Cow<'e, str> {
+    match entry.source() {
+        Source::Path(path) => {
+            borrow::Cow::Owned(path.to_string_lossy().into_owned())
+        },
+        Source::Node(_) | Source::Roots(_) => entry.name.to_string_lossy(),
+    }
+}
+
+// wether an entry is hidden regardless of it's kind, either by the -I patterns,
+// --exclude, an ignore file, or for being a dotfile with --gnu; hidden
+// directories are never descended into
+fn is_ignored(entry: &Entry, rules: &ignores::Rules, filter: &Filter) -> bool {
+    if !filter.dotfiles && entry.name.as_encoded_bytes().starts_with(b".") {
+        return true;
+    }
+    if is_ignored_by_patterns(entry, filter) {
+        return true;
+    }
+    !rules.is_empty() && entry.path().is_some_and(|path| {
+        rules.is_ignored(&path, entry.kind == Kind::Directory)
+    })
+}
+
+fn is_ignored_by_patterns(entry: &Entry, filter: &Filter) -> bool {
+    filter.exclude.as_ref().is_some_and(|exclude| {
+        exclude.is_match(&entry.name, filter.rel_path(entry).as_deref())
+    }) || is_ignored_by_context(entry, filter)
+        || is_excluded_path(entry, filter)
+}
+
+fn is_excluded_path(entry: &Entry, filter: &Filter) -> bool {
+    !filter.exclude_paths.is_empty()
+        && filter.rel_path(entry).is_some_and(|rel_path| {
+            filter.exclude_paths.contains(&rel_path)
+        })
+}
+
+fn is_ignored_by_context(entry: &Entry, filter: &Filter) -> bool {
+    filter.context.as_ref().is_some_and(|context| {
+        context.is_match(&entry.name, filter.rel_path(entry).as_deref())
+    })
+}
+
+// the ignore rules for the contents of a directory, given the rules in effect
+// for the directory it's in
+fn child_rules(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> ignores::Rules {
+    match source {
+        Source::Path(path) => match options.ignore_sources {
+            Some(sources) => ignores::Rules::load(path, rules, sources),
+            None => ignores::Rules::none(),
+        },
+        _ => ignores::Rules::none(),
+    }
+}
+
+// iterates over the unfiltered contents of a directory, this lets checks that
+// only care about wether something exists stop early
+fn entries<'a>(source: &Source<'a>, strict: bool)
+    -> Result<Box<dyn Iterator<Item = Entry<'a>> + 'a>, TraversalError> {
+    match source {
+        Source::Path(path) => {
+            Ok(Box::new(read_entries(path, strict)?.into_iter()))
+        },
+        Source::Node(node) => Ok(Box::new(node_entries(node)?)),
+        Source::Roots(roots) => Ok(Box::new(roots.iter().cloned())),
+    }
+}
+
+// like entries, except that outside of strict mode a directory that can't be
+// read is treated as having no contents rather than as an error
+fn readable_entries<'a>(source: &Source<'a>, options: &Options)
+    -> Result<Box<dyn Iterator<Item = Entry<'a>> + 'a>, TraversalError> {
+    match entries(source, options.filter.strict) {
+        Err(error) if error.ends_walk(options.filter.strict) => Err(error),
+        Err(_) => Ok(Box::new(iter::empty())),
+        result => result,
+    }
+}
+
+// starts reading the contents of a directory, every traversal reads
+// directories through here, so this is what makes the ones that aren't
+// walked with a visitor stop on ctrl-c too
+fn open_dir(path: &path::Path) -> Result<fs::ReadDir, TraversalError> {
+    open_dir_with(path, |path| fs::read_dir(path))
+}
+
+// open_dir with open doing the opening, which is how the openat module opens
+// directories relative to the ones they're in
+fn open_dir_with<T>(path: &path::Path,
+                    open: impl Fn(&path::Path) -> io::Result<T>)
+    -> Result<T, TraversalError> {
+    if is_interrupted() {
+        return Err(TraversalError {
+            path: path.to_path_buf(),
+            error: io::Error::from(io::ErrorKind::Interrupted),
+        });
+    }
+
+    // after running out of file descriptors the handles that are only kept
+    // to be reused are closed and the directory is tried once more; if it
+    // still can't be opened the error ends the walk, see ends_walk
+    let opened = match open(path) {
+        Err(error) if is_out_of_handles(&error) => {
+            log::info!("\"{}\" {}, trying again after closing the kept \
+                        directory handles", path.to_string_lossy(), error);
+            release_handles();
+            open(path)
+        },
+        opened => opened,
+    };
+    opened.map_err(|error| TraversalError { path: path.to_path_buf(), error })
+}
+
+// closes the directory handles that are kept open to be reused: those of the
+// reader thread of --timeout, which ends once it's dropped, and those the
+// openat module keeps on this thread
+fn release_handles() {
+    // the lock is only held while the reader is in use, in which case this
+    // is the reader's own thread
+    if let Ok(mut reader) = READER.try_lock() {
+        *reader = None;
+    }
+    #[cfg(all(target_os = "linux", feature = "openat"))]
+    openat::release_handles();
+}
+
+// the listed contents of a directory on the filesystem in the order they're
+// read, each one as soon as it's read, for --stream; entries that can't be
+// read are handled like in read_entries, but since the directory stays open
+// while it's entries are walked, the number of open handles grows with the
+// depth of the tree
+fn stream_entries<'i, 'a>(path: &path::Path, rules: ignores::Rules,
+                          filter: &'i Filter, files: bool)
+    -> Result<Box<dyn Iterator<Item = Result<Entry<'a>, TraversalError>> + 'i>,
+              TraversalError> {
+    let dir_iter = open_dir(path)?;
+    let dir: sync::Arc<path::Path> = sync::Arc::from(path);
+    Ok(Box::new(dir_iter.filter_map(move |entry| {
+        let entry = entry
+            .map_err(|error| TraversalError { path: dir.to_path_buf(), error })
+            .and_then(|entry| read_entry(&entry, &dir));
+        match entry {
+            Ok(entry) => {
+                keeps_entry(&entry, &rules, filter, files)
+                    .then_some(Ok(entry))
+            },
+            Err(error) if filter.strict => Some(Err(error)),
+            Err(_) => None,
+        }
+    })))
+}
+
+// reads the contents of a directory on the filesystem, entries whose metadata
+// can't be read are discarded, unless strict is set in which case they're an
+// error; the whole directory is read before returning so that its handle is
+// closed before any of its subdirectories are opened, which keeps the number
+// of open handles from growing with the depth of the tree, except for the
+// few the openat module keeps to open subdirectories relative to
+fn read_entries<'a>(path: &path::Path, strict: bool)
+    -> Result<vec::Vec<Entry<'a>>, TraversalError> {
+    match READ_TIMEOUT.get() {
+        Some(timeout) => read_entries_within(path, strict, *timeout),
+        None => read_entries_now(path, strict),
+    }
+}
+
+// set by --timeout, the longest reading a directory can take
+static READ_TIMEOUT: sync::OnceLock<time::Duration> = sync::OnceLock::new();
+
+// the directories that took longer than the timeout to read, in the order
+// they were given up on
+static TIMED_OUT: sync::Mutex<vec::Vec<path::PathBuf>> =
+    sync::Mutex::new(vec::Vec::new());
+
+fn lock_timed_out() -> sync::MutexGuard<'static, vec::Vec<path::PathBuf>> {
+    TIMED_OUT.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+// the thread --timeout reads directories on, it's handed each directory
+// along with wether the read is strict, and sends back what it read
+struct Reader {
+    requests: sync::mpsc::Sender<(path::PathBuf, bool)>,
+    results: sync::mpsc::Receiver<ReadResult>,
+}
+
+type ReadResult = Result<vec::Vec<Entry<'static>>, TraversalError>;
+
+impl Reader {
+    fn start() -> Reader {
+        let (requests, received) =
+            sync::mpsc::channel::<(path::PathBuf, bool)>();
+        let (sender, results) = sync::mpsc::channel();
+        thread::spawn(move || {
+            for (dir, strict) in received {
+                if sender.send(read_entries_now(&dir, strict)).is_err() {
+                    break;
+                }
+            }
+        });
+        Reader { requests, results }
+    }
+}
+
+// the reader of --timeout, which is only started once it's needed
+static READER: sync::Mutex<Option<Reader>> = sync::Mutex::new(None);
+
+// read_entries with --timeout, the directory is read on the reader thread,
+// which is abandoned if it doesn't finish in time since a read that's stuck
+// on a hung network filesystem can't be interrupted, and a new one is started
+// for the next directory; a directory that timed out once isn't tried again
+// when it's read a second time, like by --prune or --du, so that each one
+// only costs the timeout once
+fn read_entries_within<'a>(path: &path::Path, strict: bool,
+                           timeout: time::Duration)
+    -> Result<vec::Vec<Entry<'a>>, TraversalError> {
+    let timed_out = || TraversalError {
+        path: path.to_path_buf(),
+        error: io::Error::new(io::ErrorKind::TimedOut, "Timed out"),
+    };
+    if lock_timed_out().iter().any(|dir| dir == path) {
+        return Err(timed_out());
+    }
+    let mut reader = READER.lock()
+        .unwrap_or_else(sync::PoisonError::into_inner);
+    let running = reader.get_or_insert_with(Reader::start);
+    let _ = running.requests.send((path.to_path_buf(), strict));
+    match running.results.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(sync::mpsc::RecvTimeoutError::Timeout) => {
+            log::info!("\"{}\" took longer than {:?} to read",
+                       path.to_string_lossy(), timeout);
+            *reader = None;
+            lock_timed_out().push(path.to_path_buf());
+            Err(timed_out())
+        },
+        // the thread only ends early if reading panicked, the directory is
+        // read here instead and the next one gets a new thread
+        Err(sync::mpsc::RecvTimeoutError::Disconnected) => {
+            *reader = None;
+            read_entries_now(path, strict)
+        },
+    }
+}
+
+fn read_entries_now<'a>(path: &path::Path, strict: bool)
+    -> Result<vec::Vec<Entry<'a>>, TraversalError> {
+    #[cfg(all(target_os = "linux", feature = "openat"))]
+    if openat::is_supported() {
+        return openat::read_entries(path, strict);
+    }
+    let dir_iter = open_dir(path)?;
+    let dir = sync::Arc::from(path);
+    let parallel = PARALLEL_STATS.load(atomic::Ordering::SeqCst);
+    let mut dir_entries = vec::Vec::new();
+    let mut entries = vec::Vec::new();
+    for entry in dir_iter {
+        // without --threads each entry is read as soon as it's listed, so
+        // the listing of a huge directory isn't held twice
+        let read = match entry {
+            Ok(value) if parallel => {
+                dir_entries.push(value);
+                continue;
+            },
+            Ok(value) => read_entry(&value, &dir),
+            Err(error) => {
+                Err(TraversalError { path: path.to_path_buf(), error })
+            },
+        };
+        match read {
+            Ok(value) => entries.push(value),
+            Err(error) if strict => return Err(error),
+            Err(_) => {},
+        }
+    }
+
+    // with --threads the metadata is read in parallel, the results keep the
+    // order of the entries so the first error is the same either way
+    let read = |entry| read_entry(entry, &dir);
+    let results: vec::Vec<Result<Entry, TraversalError>> =
+        match dir_entries.len() >= PARALLEL_STATS_MIN {
+            true => dir_entries.par_iter().map(read).collect(),
+            false => dir_entries.iter().map(read).collect(),
+        };
+    drop(dir_entries);
+    entries.reserve(results.len());
+    for result in results {
+        match result {
+            Ok(value) => entries.push(value),
+            Err(error) if strict => return Err(error),
+            Err(_) => {},
+        }
+    }
+    Ok(entries)
+}
+
+// set by --threads when there's more than one thread to read metadata with
+static PARALLEL_STATS: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+// the fewest entries a directory needs for reading their metadata in parallel
+// to be worth handing them to the other threads
+const PARALLEL_STATS_MIN: usize = 256;
+
+// the most threads --threads starts for each core
+const THREADS_PER_CORE: usize = 4;
+
+// with --follow, the most symlinks to directories that are read as the
+// directories they point to, and how many have been so far
+static FOLLOW_LIMIT: sync::OnceLock<u64> = sync::OnceLock::new();
+static FOLLOWED: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+// the --max-symlinks used when it's not given, this is far beyond what a sane
+// tree has and still ends a runaway chain before the paths get too long
+const DEFAULT_MAX_SYMLINKS: u64 = 1000;
+
+// what --follow did with a symlink to a directory
+#[derive(Clone, Copy, PartialEq)]
+enum Follow {
+    Followed,
+    // --max-symlinks was reached, so it's left as a symlink
+    Limited,
+}
+
+// counts a symlink as followed, unless that would go over the limit
+fn follow_link(limit: u64) -> bool {
+    FOLLOWED.fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst,
+                          |followed| (followed < limit).then_some(followed+1))
+        .is_ok()
+}
+
+fn read_entry<'a>(entry: &fs::DirEntry, dir: &sync::Arc<path::Path>)
+    -> Result<Entry<'a>, TraversalError> {
+    let failed = |error| TraversalError { path: entry.path(), error };
+    // the kind comes from the listing itself where the filesystem records
+    // it, which is all that's read of the files that nothing looks at
+    let kind = kind_of(entry.file_type().map_err(failed)?);
+    if !reads_metadata_now(kind) {
+        return Ok(Entry::read(entry.file_name(), kind, None, None, dir));
+    }
+    // on linux and the other unixes std reads this with fstatat relative to
+    // the open directory handle, so the cost of an entry doesn't grow with
+    // the depth of the tree; only opening the directory itself goes through
+    // the full path
+    let mut metadata = entry.metadata().map_err(failed)?;
+    let mut follow = None;
+    if let (true, Some(limit)) = (metadata.is_symlink(), FOLLOW_LIMIT.get()) {
+        match fs::metadata(entry.path()) {
+            Ok(target) if target.is_dir() && follow_link(*limit) => {
+                metadata = target;
+                follow = Some(Follow::Followed);
+            },
+            Ok(target) if target.is_dir() => follow = Some(Follow::Limited),
+            _ => {},
+        }
+    }
+    let kind = kind_of(metadata.file_type());
+    Ok(Entry::read(entry.file_name(), kind, follow,
+                   Some(meta_of(&metadata, kind)), dir))
+}
+
+// wether the metadata of an entry is read along with the listing, rather
+// than when it's first asked for; directories always need theirs for
+// noticing loops, and so do symlinks --follow could follow
+fn reads_metadata_now(kind: Kind) -> bool {
+    FILE_METADATA.load(atomic::Ordering::SeqCst) || kind == Kind::Directory
+        || (kind == Kind::Symlink && FOLLOW_LIMIT.get().is_some())
+}
+
+// cleared when none of the options look at the metadata of files, see
+// uses_file_metadata; if one still does that only costs a stat of it's own
+static FILE_METADATA: atomic::AtomicBool = atomic::AtomicBool::new(true);
+
+fn kind_of(file_type: fs::FileType) -> Kind {
+    if file_type.is_dir() {
+        Kind::Directory
+    } else if file_type.is_symlink() {
+        Kind::Symlink
+    } else if file_type.is_file() {
+        Kind::File
+    } else {
+        Kind::Other
+    }
+}
+
+fn meta_of(metadata: &fs::Metadata, kind: Kind) -> Meta {
+    Meta {
+        size: Some(metadata.len()),
+        mtime: entry_time(metadata),
+        ctime: change_time(metadata),
+        mode: mode(metadata),
+        sparse: kind == Kind::File && is_sparse(metadata),
+        id: file_id(metadata),
+    }
+}
+
+// the errors of the process running out of file descriptors, and of the
+// whole system running out of them, which are the same on every unix
+#[cfg(unix)]
+const EMFILE: i32 = 24;
+#[cfg(unix)]
+const ENFILE: i32 = 23;
+
+// wether an error means the process or system ran out of file descriptors,
+// rather than the directory itself being unreadable
+#[cfg(unix)]
+fn is_out_of_handles(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(EMFILE | ENFILE))
+}
+
+#[cfg(not(unix))]
+fn is_out_of_handles(_error: &io::Error) -> bool {
+    false
+}
+
+// lists the children of a snapshot node, snapshots don't record permissions so
+// those are always missing
+fn node_entries(node: &snapshot::Node)
+    -> Result<impl Iterator<Item = Entry<'_>>, TraversalError> {
+    if node.unreadable {
+        return Err(TraversalError {
+            path: path::PathBuf::from(&node.name),
+            error: io::Error::other("contents unreadable when saved"),
+        });
+    }
+    Ok(node.children.iter().map(|child| Entry {
+        name: ffi::OsString::from(&child.name),
+        kind: child.kind,
+        follow: None,
+        meta: sync::OnceLock::from(Box::new(Meta {
+            size: child.size,
+            mtime: child.mtime.map(|mtime| {
+                time::UNIX_EPOCH + time::Duration::from_secs(mtime)
+            }),
+            ..Meta::default()
+        })),
+        origin: Origin::Source(Source::Node(child)),
+    }))
+}
+
+// the sort fields of an entry, the metadata is only read if the keys of
+// sort compare it
+fn sort_fields<'a>(entry: &'a Entry, sort: &sort::Sort)
+    -> sort::SortFields<'a> {
+    let (size, mtime, ctime) = match sort.uses_metadata() {
+        true => {
+            let meta = entry.meta();
+            (meta.size, meta.mtime, meta.ctime)
+        },
+        false => (None, None, None),
+    };
+    sort::SortFields {
+        name: &entry.name,
+        is_dir: entry.kind == Kind::Directory,
+        size,
+        mtime,
+        ctime,
+    }
+}
+
+// the device and inode numbers of a file, which identify it no matter which
+// path it's reached through; these are only available on unix, and snapshots
+// don't record them
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// file_id of a directory that's only known by it's source, like a root, the
+// entries of a directory already have theirs from when they were read
+fn dir_id(source: &Source) -> Option<(u64, u64)> {
+    match source {
+        Source::Path(path) => file_id(&fs::metadata(path).ok()?),
+        Source::Node(_) | Source::Roots(_) => None,
+    }
+}
+
+// wether a directory is on another device than the root with -x, or is a
+// mount point of a type --exclude-fstype leaves out
+fn is_mount_point(entry: &Entry, options: &Options) -> bool {
+    options.root_device.is_some_and(|root| {
+        entry.meta().id.is_some_and(|(device, _)| device != root)
+    }) || is_excluded_mount(&entry.source(), options)
+}
+
+fn is_excluded_mount(source: &Source, options: &Options) -> bool {
+    match source {
+        Source::Path(path) if !options.exclude_fstypes.is_empty() => {
+            mounts::is_excluded(path, &options.exclude_fstypes)
+        },
+        _ => false,
+    }
+}
+
+// the permission bits of an entry, these are only available on unix
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.mode())
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+// wether a file takes up noticeably less space on disk than its size, which
+// means it has holes in it; anything under a block of difference is ignored
+// since small files can be stored inline with no blocks of their own, this is
+// only known on unix
+#[cfg(unix)]
+fn is_sparse(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    has_holes(metadata.blocks(), metadata.len())
+}
+
+// is_sparse for a file of len bytes that takes up blocks of 512 bytes
+#[cfg(unix)]
+fn has_holes(blocks: u64, len: u64) -> bool {
+    blocks*512 + 4096 <= len
+}
+
+#[cfg(not(unix))]
+fn is_sparse(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+// which of an entry's timestamps --time chose, it's what the {mtime} field,
+// sorting by mtime, and the time filters all look at
+#[derive(Clone, Copy)]
+enum TimeSource {
+    Modified,
+    Changed,
+    Accessed,
+    Born,
+}
+
+impl TimeSource {
+    fn name(self) -> &'static str {
+        match self {
+            TimeSource::Modified => "mtime",
+            TimeSource::Changed => "ctime",
+            TimeSource::Accessed => "atime",
+            TimeSource::Born => "btime",
+        }
+    }
+}
+
+// set once from --time before anything is read
+static TIME_SOURCE: sync::OnceLock<TimeSource> = sync::OnceLock::new();
+
+fn time_source() -> TimeSource {
+    TIME_SOURCE.get().copied().unwrap_or(TimeSource::Modified)
+}
+
+// set the first time a birth time can't be read, so that the warning about it
+// is only printed once
+static NO_BIRTH_TIME: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+// the timestamp of an entry chosen by --time, where the filesystem doesn't
+// record birth times the modification time is used instead
+fn entry_time(metadata: &fs::Metadata) -> Option<time::SystemTime> {
+    match time_source() {
+        TimeSource::Modified => metadata.modified().ok(),
+        TimeSource::Changed => change_time(metadata),
+        TimeSource::Accessed => metadata.accessed().ok(),
+        TimeSource::Born => match metadata.created() {
+            Ok(value) => Some(value),
+            Err(error) => {
+                no_birth_time(&error);
+                metadata.modified().ok()
+            },
+        },
+    }
+}
+
+// warns that birth times aren't available the first time one isn't
+fn no_birth_time(error: &dyn fmt::Display) {
+    if !NO_BIRTH_TIME.swap(true, atomic::Ordering::SeqCst) {
+        eprintln!("WARNING: birth times aren't available, using modification \
+                   times instead: {}", error);
+    }
+}
+
+// the time an entry's metadata last changed, this is only available on unix
+#[cfg(unix)]
+fn change_time(metadata: &fs::Metadata) -> Option<time::SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let seconds = u64::try_from(metadata.ctime()).ok()?;
+    let nanoseconds = u32::try_from(metadata.ctime_nsec()).ok()?;
+    time::UNIX_EPOCH.checked_add(time::Duration::new(seconds, nanoseconds))
+}
+
+#[cfg(not(unix))]
+fn change_time(_metadata: &fs::Metadata) -> Option<time::SystemTime> {
+    None
+}
+
+// the results of the scans that look at everything beneath a directory,
+// which are otherwise repeated for it from every directory above it; they're
+// kept by the directory they're for, since it's ignore rules follow from
+// it's path, and forgotten at the start of every walk so that a walk never
+// sees what an earlier one found
+#[derive(Default)]
+struct Prescans {
+    contents: sync::Mutex<collections::HashMap<ScanKey, bool>>,
+    empty: sync::Mutex<collections::HashMap<ScanKey, bool>>,
+    usage: sync::Mutex<collections::HashMap<ScanKey, Usage>>,
+}
+
+// a directory that was scanned: it's path, or where the node of a snapshot is
+// in memory
+#[derive(PartialEq, Eq, Hash)]
+enum ScanKey {
+    Path(path::PathBuf),
+    Node(usize),
+}
+
+impl Prescans {
+    fn clear(&self) {
+        lock(&self.contents).clear();
+        lock(&self.empty).clear();
+        lock(&self.usage).clear();
+    }
+}
+
+fn lock<T>(mutex: &sync::Mutex<T>) -> sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+// the result of scanning the directory at source, which is only scanned the
+// first time it's asked about; errors aren't kept, so that they're reported
+// wherever they come up
+fn prescanned<T: Copy>(scans: &sync::Mutex<collections::HashMap<ScanKey, T>>,
+                       source: &Source,
+                       scan: impl FnOnce() -> Result<T, TraversalError>)
+    -> Result<T, TraversalError> {
+    let key = match source {
+        Source::Path(path) => ScanKey::Path(path.clone()),
+        Source::Node(node) => ScanKey::Node(std::ptr::from_ref(*node) as usize),
+        Source::Roots(_) => return scan(),
+    };
+    if let Some(value) = lock(scans).get(&key) {
+        return Ok(*value);
+    }
+    let value = scan()?;
+    lock(scans).insert(key, value);
+    Ok(value)
+}
+
+// wether any file beneath the directory passes the filter, regardless of
+// wether files are being displayed, this is what prune uses to decide which
+// directories to hide; when looking for empty entries, empty directories
+// count as contents too
+fn has_contents(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<bool, TraversalError> {
+    prescanned(&options.prescans.contents, source, || {
+        scan_contents(source, rules, options)
+    })
+}
+
+fn scan_contents(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<bool, TraversalError> {
+    if options.filter.empty.is_some() && is_empty_dir(source, rules, options)? {
+        return Ok(true);
+    }
+    for entry in readable_entries(source, options)? {
+        if is_ignored(&entry, rules, &options.filter) {
+            continue;
+        }
+        let found = match entry.kind {
+            Kind::Directory => {
+                let rules = child_rules(&entry.source(), rules, options);
+                has_contents(&entry.source(), &rules, options)?
+            },
+            _ => options.filter.accepts_file(&entry),
+        };
+        if found {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// wether a directory has no files that pass the filter (other than the empty
+// check itself) beneath it, directories that can't be read aren't empty
+fn is_empty_dir(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<bool, TraversalError> {
+    prescanned(&options.prescans.empty, source, || {
+        scan_empty(source, rules, options)
+    })
+}
+
+fn scan_empty(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<bool, TraversalError> {
+    let entries = match entries(source, options.filter.strict) {
+        Ok(value) => value,
+        Err(error) if error.ends_walk(options.filter.strict) => {
+            return Err(error);
+        },
+        Err(_) => return Ok(false),
+    };
+    for entry in entries {
+        if is_ignored(&entry, rules, &options.filter) {
+            continue;
+        }
+        let found = match entry.kind {
+            Kind::Directory => {
+                let rules = child_rules(&entry.source(), rules, options);
+                !is_empty_dir(&entry.source(), &rules, options)?
+            },
+            _ => options.filter.accepts_file_contents(&entry),
+        };
+        if found {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// wether a directory would list at least one entry if it were displayed, this
+// is used to mark directories whose contents are hidden by the depth limit
+fn has_visible_child(source: &Source, rules: &ignores::Rules,
+                     options: &Options) -> Result<bool, TraversalError> {
+    for entry in readable_entries(source, options)? {
+        if is_visible(&entry, rules, options)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// wether an entry of a directory would be listed, including that with prune
+// a directory is only listed if there's something in it; rules are those for
+// the contents of the directory the entry is in
+fn is_visible(entry: &Entry, rules: &ignores::Rules, options: &Options)
+    -> Result<bool, TraversalError> {
+    if !is_listed(entry, rules, &options.filter, options.show_files) {
+        return Ok(false);
+    }
+    if !options.prune || entry.kind != Kind::Directory {
+        return Ok(true);
+    }
+    let rules = child_rules(&entry.source(), rules, options);
+    has_contents(&entry.source(), &rules, options)
+}
+
+// the notes --show-empty gives directories that don't list anything
+const EMPTY: &str = "[empty]";
+const CONTENTS_HIDDEN: &str = "[contents hidden]";
+
+// with --show-empty, why a directory doesn't list any entries: there's either
+// nothing in it at all, or everything in it was filtered out or hidden; a
+// directory that can't be read gets neither, since it's shown as unreadable
+// once it's walked
+fn empty_note(source: &Source, rules: &ignores::Rules, options: &Options)
+    -> Result<Option<&'static str>, TraversalError> {
+    let entries = match entries(source, options.filter.strict) {
+        Ok(value) => value,
+        Err(error) if error.is_out_of_handles() => return Err(error),
+        Err(_) => return Ok(None),
+    };
+    let mut any = false;
+    for entry in entries {
+        if is_visible(&entry, rules, options)? {
+            return Ok(None);
+        }
+        any = true;
+    }
+    Ok(Some(if any { CONTENTS_HIDDEN } else { EMPTY }))
+}
+
+// the visitor that prints the tree into out, and gathers the summary for the
+// modes that print one instead
+struct Printer<'o, W: io::Write> {
+    out: Output<W>,
+    options: &'o Options,
+    // the totals of --du, see disk_usage
+    totals: Totals,
+    summary: Summary,
+    // the .info files of the directories being visited, innermost last
+    infos: vec::Vec<Option<info::InfoFile>>,
+    // with --post-order, wether each entry on the path down to the one
+    // printed last is the first of it's siblings, the connectors are drawn
+    // from that instead of from the last entries since the tree is upside
+    // down
+    firsts: vec::Vec<bool>,
+    // with --post-order, the rows of the entry printed last and of the
+    // directories being visited, innermost last, each of which is moved
+    // after it's contents once they've been printed
+    printed: ops::Range<usize>,
+    held: vec::Vec<ops::Range<usize>>,
+    // with --bars, the scale for the contents of the directory about to be
+    // entered and those of the directories being visited, innermost last
+    scale: u64,
+    scales: vec::Vec<u64>,
+    // the status of the repository the tree is in, with --git-status
+    #[cfg(feature = "git")]
+    git: Option<git::Statuses>,
+}
+
+impl<W: io::Write> Printer<'_, W> {
+    // the prefix of an entry at the given depth with --post-order, recording
+    // wether the entry at each level is the first of it's siblings; entries
+    // are visited in order, so an entry is the first when nothing has been
+    // printed at it's depth since it's parent
+    fn first_prefix(&mut self, depth: usize) -> vec::Vec<bool> {
+        let first = self.firsts.len() < depth;
+        self.firsts.truncate(depth - 1);
+        self.firsts.push(first);
+        self.firsts.clone()
+    }
+
+    // an entry's git status as it's displayed, or None if statuses aren't
+    // being shown; unchanged entries get blanks to keep the alignment, and
+    // with color the index and working tree letters are green and red like
+    // in git status, untracked and conflicted entries are red on both sides
+    #[cfg(feature = "git")]
+    fn git_marker(&self, entry: &Entry) -> Option<String> {
+        let statuses = self.git.as_ref()?;
+        let marker = match &entry.source() {
+            Source::Path(path) => statuses.marker(path),
+            Source::Node(_) | Source::Roots(_) => None,
+        };
+        let mut text = String::new();
+        let mut letter = |letter: char, style: &str| {
+            let color = self.options.color && letter != ' ';
+            push_styled(&mut text, letter.encode_utf8(&mut [0; 4]),
+                        Some(style).filter(|_| color));
+        };
+        match marker {
+            Some(marker) => {
+                let index_style = match marker.index {
+                    '?' | 'U' => "31",
+                    _ => "32",
+                };
+                letter(marker.index, index_style);
+                letter(marker.worktree, "31");
+            },
+            None => text.push_str("  "),
+        }
+        Some(text)
+    }
+
+    #[cfg(not(feature = "git"))]
+    fn git_marker(&self, _entry: &Entry) -> Option<String> {
+        None
+    }
+}
+
+// with --gnu a directory that's about to be descended into but can't be
+// opened is marked on it's own line, like gnu tree does, instead of getting a
+// placeholder for it's contents
+fn gnu_open_error(node: &walk::Node, options: &Options)
+    -> Option<&'static str> {
+    let descends = node.entry.kind == Kind::Directory && node.note.is_none()
+        && options.max_depth.is_none_or(|max| node.depth() < max);
+    match (options.gnu && descends).then(|| node.entry.path()).flatten() {
+        Some(path) if fs::read_dir(&path).is_err() => Some(gnu::OPEN_ERROR),
+        _ => None,
+    }
+}
+
+impl<W: io::Write> walk::Visitor for Printer<'_, W> {
+    fn visit(&mut self, node: &walk::Node)
+        -> Result<walk::WalkAction, TraversalError> {
+        if is_interrupted() {
+            return Ok(walk::WalkAction::Stop);
+        }
+        let options = self.options;
+        let entry = node.entry;
+        self.summary.add(entry, node.rel_path, options);
+        self.summary.directories += node.joined.saturating_sub(1);
+        if !options.prints_tree() {
+            return Ok(walk::WalkAction::Continue);
+        }
+
+        let firsts;
+        let prefix = if options.post_order {
+            firsts = self.first_prefix(node.prefix.len());
+            &firsts
+        } else {
+            node.prefix
+        };
+
+        // comments for the entries of a directory come from its own .info
+        // file if it has a valid one, falling back to the global info file
+        let local_info = self.infos.last().and_then(Option::as_ref);
+        let git_marker = self.git_marker(entry);
+        let du = directory_usage(node, &self.totals, options);
+        let shallow = shallow_usage(node, options)?;
+        let usage = du.map(usage_note).or_else(|| shallow.map(shallow_note));
+        let size = match entry.kind {
+            _ if options.bars.is_none() => None,
+            Kind::Directory => du.or(shallow).map(|usage| usage.size),
+            _ => entry.meta().size,
+        };
+        // empty entries don't have anything to draw a bar for
+        let bar = size.filter(|size| *size > 0)
+            .map(|size| Bar {
+                size,
+                scale: self.scales.last().copied().unwrap_or(0),
+            });
+        if options.bars == Some(BarScale::Directory)
+            && shows_contents(node, options) {
+            let rules = child_rules(&entry.source(), node.rules, options);
+            self.scale = bar_scale(&entry.source(), &rules,
+                                   &node.rel_path.join(&entry.name),
+                                   node.depth()+1, &self.totals, options)?;
+        }
+        if node.note == Some(walk::MOUNT_POINT) {
+            self.summary.mount_points.push(node.rel_path.join(&entry.name));
+        }
+        let empty = match entry.kind {
+            Kind::Directory if options.show_empty && node.note.is_none() => {
+                let rules = child_rules(&entry.source(), node.rules, options);
+                empty_note(&entry.source(), &rules, options)?
+            },
+            _ => None,
+        };
+        self.summary.empty_dirs += (empty == Some(EMPTY)) as u64;
+        let note = node.note.or(usage.as_deref())
+            .or_else(|| gnu_open_error(node, options));
+        let note = match (note, empty) {
+            (Some(note), Some(empty)) => Some(format!("{}  {}", note, empty)),
+            (note, empty) => note.or(empty).map(String::from),
+        };
+        // a directory at the depth limit isn't descended into, a placeholder
+        // is shown in place of its contents if there was anything to display,
+        // which comes before the directory with --post-order
+        let truncated = entry.kind == Kind::Directory && node.note.is_none()
+            && !options.gnu
+            && options.max_depth.is_some_and(|max| node.depth() >= max)
+            && has_visible_child(&entry.source(),
+                                 &child_rules(&entry.source(), node.rules,
+                                              options),
+                                 options)?;
+        // nothing more is printed once ctrl-c is pressed or a write fails,
+        // since the contents of the entry might not have been read in full
+        if is_interrupted() || self.out.error.is_some() {
+            return Ok(walk::WalkAction::Stop);
+        }
+        if truncated && options.post_order {
+            print_truncated(&mut self.out, prefix, options);
+        }
+        let start = self.out.row_count();
+        print_entry(&mut self.out, entry, node.rel_path, prefix, local_info,
+                    Annotations {
+                        note: note.as_deref(),
+                        git_marker,
+                        bar,
+                    }, options);
+        self.printed = start..self.out.row_count();
+        if truncated && !options.post_order {
+            print_truncated(&mut self.out, prefix, options);
+        }
+        Ok(walk::WalkAction::Continue)
+    }
+
+    // snapshots don't include .info files so only the global one applies to
+    // them
+    fn enter(&mut self, dir: &Source) {
+        let local_info = match dir {
+            Source::Path(path) if self.options.info
+                && self.options.prints_tree() => {
+                info::InfoFile::load(&path.join(info::INFO_FILE_NAME))
+            },
+            _ => None,
+        };
+        self.infos.push(local_info);
+        self.scales.push(self.scale);
+        if self.options.post_order {
+            self.held.push(self.printed.clone());
+        }
+    }
+
+    fn leave(&mut self) {
+        self.infos.pop();
+        self.scales.pop();
+        if let Some(rows) = self.held.pop() {
+            self.out.move_rows_to_end(rows);
+        }
+    }
+
+    fn unreadable(&mut self, prefix: &[bool], _rel_path: &path::Path,
+                  error: &TraversalError) {
+        if !self.options.prints_tree() || self.options.gnu
+            || is_interrupted() {
+            return;
+        }
+        let firsts = self.firsts.clone();
+        let prefix = if self.options.post_order { &firsts } else { prefix };
+        if error.error.kind() == io::ErrorKind::TimedOut {
+            print_placeholder(&mut self.out, prefix, "[timed out]",
+                              self.options);
+        } else {
+            print_unreadable(&mut self.out, prefix, self.options);
+        }
+        if self.options.post_order {
+            self.out.move_rows_to_end(self.printed.clone());
+        }
+    }
+}
+
+// indent one level and print an error message indicating the contents of the
+// directory could not be read
+fn print_unreadable(out: &mut Output<impl io::Write>, prefix: &[bool],
+                    options: &Options) {
+    print_placeholder(out, prefix, "<CONTENTS UNREADABLE>", options);
+}
+
+// indent one level and print an ellipsis indicating the directory has contents
+// that are hidden by the depth limit
+fn print_truncated(out: &mut Output<impl io::Write>, prefix: &[bool],
+                   options: &Options) {
+    print_placeholder(out, prefix, options.ellipsis, options);
+}
+
+fn print_placeholder(out: &mut Output<impl io::Write>, prefix: &[bool],
+                     text: &str, options: &Options) {
+    // without connectors a placeholder couldn't be told apart from an entry
+    if options.flat {
+        return;
+    }
+    out.emit_line(vec::Vec::new(), options, |line| {
+        let branch = iter::once(options.format_str[0].as_str());
+        push_connectors(line, connectors(prefix, false, options).chain(branch),
+                        options);
+        line.push_str(text);
+    });
+}
+
+// what's shown with an entry besides it's name and attributes
+struct Annotations<'a> {
+    note: Option<&'a str>,
+    git_marker: Option<String>,
+    bar: Option<Bar>,
+}
+
+// prints a single entry line, prefix holds one bool per level of the tree
+// recording wether the entry at that level is the last of its siblings
+fn print_entry(out: &mut Output<impl io::Write>, entry: &Entry,
+               rel_path: &path::Path, prefix: &[bool],
+               local_info: Option<&info::InfoFile>, annotations: Annotations,
+               options: &Options) {
+    let name = entry.name.to_string_lossy();
+
+    // look up the info comment for this entry, if info mode is enabled
+    let comment = local_info
+        .and_then(|info| info.comment(&name))
+        .or_else(|| options.global_info.as_ref()
+            .and_then(|info| info.comment(&name)));
+
+    // permissions and the git status go in brackets before the text, or in
+    // columns ahead of the tree with --columns, entries without permissions,
+    // like those from snapshots, get dashes of the same width to keep the
+    // alignment
+    let mut attributes = vec::Vec::new();
+    if options.perms {
+        attributes.push(match entry.meta().mode {
+            Some(mode) => format::permissions(entry.kind, mode),
+            None => String::from("----------"),
+        });
+    }
+    if options.perm_octal {
+        attributes.push(match entry.meta().mode {
+            Some(mode) => format::octal_permissions(mode),
+            None => String::from("----"),
+        });
+    }
+    attributes.extend(annotations.git_marker);
+    let columns = if options.columns {
+        std::mem::take(&mut attributes)
+    } else {
+        vec::Vec::new()
+    };
+
+    let details = match options.xattrs.then(|| entry.path()).flatten() {
+        Some(path) => xattrs(&path, options.xattr_values),
+        None => vec::Vec::new(),
+    };
+
+    let age_style = options.age_color.and_then(|(now, scale)| {
+        let age = now.duration_since(entry.meta().mtime?).unwrap_or_default();
+        Some(format::age_color(age, scale))
+    });
+    let style = options.changed.as_ref()
+        .filter(|(since, _)| is_changed(entry, *since))
+        .map(|(_, style)| style.as_str())
+        .or(age_style.as_deref());
+
+    // what's left of the terminal's width after the gutter and the columns,
+    // the columns of other rows might be wider but those of this entry are
+    // the best guess for how much room they'll take up
+    let columns_width: usize = columns.iter()
+        .map(|column| visible_width(column) + 1)
+        .sum();
+    let columns_width = columns_width + (!columns.is_empty()) as usize;
+    let width = options.width
+        .saturating_sub(out.gutter_width(options) + columns_width);
+
+    // the text of the entry is put together right in the line after it's
+    // connectors, and is either the filename or the format template rendered
+    // for this entry; the comment follows it if it fits on the same line
+    let wraps = out.emit_line(columns, options, |line| {
+        let start = line.len();
+        push_connectors(line, connectors(prefix, true, options), options);
+        let styled = line.len();
+        push_style(line, style);
+        let text = line.len();
+        if !attributes.is_empty() {
+            line.push('[');
+            for (i, attribute) in attributes.iter().enumerate() {
+                if i > 0 {
+                    line.push(' ');
+                }
+                line.push_str(attribute);
+            }
+            line.push_str("]  ");
+        }
+        match &options.format {
+            Some(template) => template.push_rendered(line, &format::Fields {
+                name: &name,
+                path: &rel_path.join(&entry.name),
+                depth: prefix.len(),
+                kind: entry.kind,
+                size: entry.meta().size,
+                mtime: entry.meta().mtime,
+                mode: entry.meta().mode,
+                quoting: options.quoting,
+            }),
+            None => push_name(line, &name, entry.kind, options),
+        }
+        // gnu tree shows where a symlink points right after it's name,
+        // followed or not, with the target as it's stored in the link
+        let links = entry.kind == Kind::Symlink || entry.follow.is_some();
+        if let Some(path) = (options.gnu && links).then(|| entry.path())
+            .flatten() {
+            if let Ok(target) = fs::read_link(path) {
+                line.push_str(" -> ");
+                options.quoting.push(line, &target.to_string_lossy());
+            }
+        }
+        if let Some(note) = annotations.note {
+            line.push_str("  ");
+            line.push_str(note);
+        }
+        // a bar is shortened to what's left of the terminal's width after
+        // the rest of the line
+        if let Some(bar) = annotations.bar {
+            let line_width = visible_width(&line[start..]) + 2;
+            let cells = BAR_WIDTH.min(width.saturating_sub(line_width));
+            if cells >= MIN_BAR_WIDTH {
+                line.push_str("  ");
+                push_bar(line, bar, cells, options);
+            }
+        }
+        if let Some(path) = options.mime.then(|| entry.path()).flatten() {
+            line.push_str("  [");
+            line.push_str(&mime::detect(&path, entry.kind));
+            line.push(']');
+        }
+        // sparse files are only marked when sizes are shown, since that's
+        // when it matters that the size isn't what the file takes up on disk
+        if options.format.as_ref()
+            .is_some_and(|template| template.shows_size())
+            && entry.meta().sparse {
+            line.push_str("  [sparse]");
+        }
+        let resolves = options.resolve_links && entry.kind == Kind::Symlink;
+        if let Some(path) = resolves.then(|| entry.path()).flatten() {
+            let (chain, marker) = link_chain(&path);
+            for target in chain {
+                line.push_str(" -> ");
+                line.push_str(&target);
+            }
+            if let Some(marker) = marker {
+                line.push_str("  ");
+                line.push_str(marker);
+            }
+        }
+        // like push_styled, nothing is drawn around text that's empty
+        if line.len() == text {
+            line.truncate(styled);
+        } else if style.is_some() {
+            line.push_str(STYLE_RESET);
+        }
+
+        let comment = comment?;
+        let line_width = visible_width(&line[start..]);
+        if line_width + 2 + comment.chars().count() > width {
+            return Some(comment);
+        }
+        line.push_str("  ");
+        line.push_str(comment);
+        None
+    });
+
+    // a comment that doesn't fit on the same line as the name is wrapped
+    // onto lines indented under the entry
+    if let Some(comment) = wraps {
+        let indent = prefix_width(connectors(prefix, false, options));
+        for wrapped in info::wrap(comment, width.saturating_sub(indent)) {
+            out.emit_line(vec::Vec::new(), options, |line| {
+                push_connectors(line, connectors(prefix, false, options),
+                                options);
+                line.push_str(&wrapped);
+            });
+        }
+    }
+    for detail in &details {
+        out.emit_line(vec::Vec::new(), options, |line| {
+            push_connectors(line, connectors(prefix, false, options), options);
+            line.push_str(detail);
+        });
+    }
+}
+
+// puts a name in line as it's displayed, quoted if -Q or --shell-escape was
+// given and followed by a slash if it's a directory and --slash was given;
+// the slash goes outside of the quotes, and isn't doubled for a root like /
+fn push_name(line: &mut String, name: &str, kind: Kind, options: &Options) {
+    options.quoting.push(line, name);
+    if options.slash && kind == Kind::Directory && !name.ends_with('/') {
+        line.push('/');
+    }
+}
+
+// the most links a chain is followed through before giving up, the same limit
+// linux puts on resolving a path
+const MAX_LINK_HOPS: usize = 40;
+
+// the targets of a chain of symlinks starting at path, each as it's stored in
+// the link rather than canonicalized, along with [broken] if the last target
+// doesn't exist or [loop] if it comes back to a link already in the chain; a
+// relative target is resolved against the directory of the link it was read
+// from
+fn link_chain(path: &path::Path)
+    -> (vec::Vec<String>, Option<&'static str>) {
+    let mut chain = vec::Vec::new();
+    let mut seen = vec::Vec::from([path.to_path_buf()]);
+    let mut current = path.to_path_buf();
+    while chain.len() < MAX_LINK_HOPS {
+        let target = match fs::read_link(&current) {
+            Ok(value) => value,
+            Err(_) => break,
+        };
+        chain.push(target.to_string_lossy().into_owned());
+
+        let next = current.parent()
+            .unwrap_or(path::Path::new(""))
+            .join(&target);
+        if seen.contains(&next) {
+            return (chain, Some("[loop]"));
+        }
+        match fs::symlink_metadata(&next) {
+            Ok(metadata) if metadata.is_symlink() => {},
+            Ok(_) => return (chain, None),
+            Err(_) => return (chain, Some("[broken]")),
+        }
+        seen.push(next.clone());
+        current = next;
+    }
+    let marker = if chain.len() == MAX_LINK_HOPS {
+        Some("[too many links]")
+    } else {
+        None
+    };
+    (chain, marker)
+}
+
+// the extended attributes of a file, one line per attribute, entries whose
+// attributes can't be read are treated as having none
+#[cfg(unix)]
+fn xattrs(path: &path::Path, values: bool) -> vec::Vec<String> {
+    let names = match xattr::list(path) {
+        Ok(value) => value,
+        Err(_) => return vec::Vec::new(),
+    };
+    names.map(|name| {
+        let value = if values {
+            xattr::get(path, &name).ok().flatten()
+        } else {
+            None
+        };
+        match value {
+            Some(value) => format!("{}={}", name.to_string_lossy(),
+                                   value.escape_ascii()),
+            None => name.to_string_lossy().into_owned(),
+        }
+    }).collect()
+}
+
+#[cfg(not(unix))]
+fn xattrs(_path: &path::Path, _values: bool) -> vec::Vec<String> {
+    vec::Vec::new()
+}
+
+// prints text after the connectors for it's prefix, drawn in the given style
+// if there is one, with the columns before it
+fn print_line(out: &mut Output<impl io::Write>, prefix: &[bool], text: &str,
+              style: Option<&str>, columns: vec::Vec<String>,
+              options: &Options) {
+    out.emit_line(columns, options, |line| {
+        push_connectors(line, connectors(prefix, true, options), options);
+        push_styled(line, text, style);
+    });
+}
+
+// the sets of lines --style draws the connectors with, each replaces the
+// plain ones: the corner before the last entry of a directory, the tee before
+// the others, the line down past a level, the line across to a name, and the
+// corner turned upside down for --post-order
+const STYLES: [(&str, [char; 5]); 4] = [
+    ("plain", ['└', '├', '│', '─', '┌']),
+    ("rounded", ['╰', '├', '│', '─', '╭']),
+    ("double", ['╚', '╠', '║', '═', '╔']),
+    ("heavy", ['┗', '┣', '┃', '━', '┏']),
+];
+
+// a connector drawn with plain lines redrawn with those of style, ASCII ones
+// are left as they are
+fn restyle(connector: &str, style: &[char; 5]) -> String {
+    let plain = &STYLES[0].1;
+    connector.chars()
+        .map(|c| match plain.iter().position(|line| *line == c) {
+            Some(i) => style[i],
+            None => c,
+        })
+        .collect()
+}
+
+// prints a small tree in each of the styles, for --list-styles
+fn print_styles(stdout: &mut Stdout) {
+    let sample = ["├───src", "│   └───main.rs", "└───README"];
+    for (i, (name, style)) in STYLES.iter().enumerate() {
+        if i > 0 {
+            stdout.line("");
+        }
+        stdout.line(name);
+        for line in sample {
+            stdout.line(&restyle(line, style));
+        }
+    }
+}
+
+// the connectors that draw the structure of the tree before an entry, one per
+// level of prefix; with branch the last level branches off to the entry
+// itself, without it every level only continues down past the entry, which
+// is what lines belonging to the entry (but not to its children) are indented
+// with
+fn connectors<'o>(prefix: &'o [bool], branch: bool, options: &'o Options)
+    -> impl Iterator<Item = &'o str> + 'o {
+    let format_str = &options.format_str;
+    let levels = if options.flat { 0 } else { prefix.len() };
+    prefix.iter().take(levels).enumerate().map(move |(i, last_entry)| {
+        match (branch && i+1 == prefix.len(), *last_entry) {
+            (true, true) => format_str[0].as_str(),
+            (true, false) => format_str[1].as_str(),
+            (false, true) => format_str[2].as_str(),
+            (false, false) => format_str[3].as_str(),
+        }
+    })
+}
+
+// how many characters the given connectors take up
+fn prefix_width<'o>(connectors: impl Iterator<Item = &'o str>) -> usize {
+    connectors.map(|connector| connector.chars().count()).sum()
+}
+
+// the number of characters of text that take up space on the terminal, which
+// leaves out the escape codes of anything already styled in it
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        width += rest[..start].width();
+        // styles end with m, see push_style
+        rest = match rest[start..].find('m') {
+            Some(end) => &rest[start+end+1..],
+            None => "",
+        };
+    }
+    width + rest.width()
+}
+
+// text with the escape codes of any styling removed
+fn unstyled(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // styles end with m, see push_style
+            chars.by_ref().find(|c| *c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+// puts the tree connectors in line, in the branch style if there is one
+fn push_connectors<'o>(line: &mut String,
+                       connectors: impl Iterator<Item = &'o str>,
+                       options: &Options) {
+    let style = options.branch_style.as_deref();
+    let start = line.len();
+    push_style(line, style);
+    let text_start = line.len();
+    for connector in connectors {
+        line.push_str(connector);
+    }
+    // like push_styled, nothing is drawn around connectors that are empty
+    if line.len() == text_start {
+        line.truncate(start);
+    } else if style.is_some() {
+        line.push_str(STYLE_RESET);
+    }
+}
+
+// the escape code that ends a style
+const STYLE_RESET: &str = "\x1b[0m";
+
+// puts text in line wrapped in the escape codes for the given ansi
+// attributes, if there are any and there's any text
+fn push_styled(line: &mut String, text: &str, style: Option<&str>) {
+    if text.is_empty() {
+        return;
+    }
+    push_style(line, style);
+    line.push_str(text);
+    if style.is_some() {
+        line.push_str(STYLE_RESET);
+    }
+}
+
+// puts the escape code that starts style in line, if there is one
+fn push_style(line: &mut String, style: Option<&str>) {
+    if let Some(style) = style {
+        line.push_str("\x1b[");
+        line.push_str(style);
+        line.push('m');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the options of a plain tree with files and default connectors, which
+    // tests change what they're about in
+    pub(crate) fn options() -> Options {
+        Options { show_files: true, no_report: true, ..Options::new() }
+    }
+
+    // a fresh directory named after the test holding paths, the ones ending
+    // in a / are directories and the rest empty files
+    pub(crate) fn fixture(name: &str, paths: &[&str]) -> path::PathBuf {
+        let root = env::temp_dir()
+            .join(format!("tree-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for path in paths {
+            match path.strip_suffix('/') {
+                Some(dir) => fs::create_dir_all(root.join(dir)).unwrap(),
+                None => fs::write(root.join(path), "").unwrap(),
+            }
+        }
+        root
+    }
+
+    // the tree rooted at root as it's printed with options
+    pub(crate) fn render(root: &path::Path, options: &Options) -> String {
+        let mut printed = vec::Vec::new();
+        let result = try_print_tree("root", &Source::Path(root.to_path_buf()),
+                                    &ignores::Rules::none(), options,
+                                    Output::new(&mut printed));
+        assert!(result.is_ok());
+        String::from_utf8(printed).unwrap()
+    }
+
+    #[test]
+    fn prints_into_any_writer() {
+        let root = fixture("writer", &["b/", "b/c", "a", "d"]);
+        assert_eq!(render(&root, &options()), "\
+root
+├───a
+├───b
+│   └───c
+└───d
+");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn adds_up_each_directory_once() {
+        let root = fixture("du", &["a/b/", "c/"]);
+        fs::write(root.join("a/b/f"), "xx").unwrap();
+        fs::write(root.join("c/f"), "xxx").unwrap();
+        std::os::unix::fs::symlink("../..", root.join("a/b/up")).unwrap();
+        std::os::unix::fs::symlink("../a", root.join("c/a")).unwrap();
+        let _ = FOLLOW_LIMIT.set(DEFAULT_MAX_SYMLINKS);
+        assert_eq!(render(&root, &Options { du: true, ..options() }), "\
+root  [5B in 2 files]
+├───a  [2B in 1 file]
+│   └───b  [2B in 1 file]
+│       ├───f
+│       └───up  [recursive, not followed]
+└───c  [3B in 1 file]
+    ├───a  [already visited]
+    └───f
+");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // a sink that's full after limit bytes, like /dev/full
+    struct Full {
+        limit: usize,
+    }
+
+    impl io::Write for Full {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            if self.limit < bytes.len() {
+                return Err(io::Error::from(io::ErrorKind::StorageFull));
+            }
+            self.limit -= bytes.len();
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_on_the_first_failed_write() {
+        let root = fixture("full", &["a", "b", "c"]);
+        let result = try_print_tree("root", &Source::Path(root.clone()),
+                                    &ignores::Rules::none(), &options(),
+                                    Output::new(Full { limit: 10 }));
+        assert!(matches!(result, Err(TreeError::Write(error))
+                         if error.kind() == io::ErrorKind::StorageFull));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn counts_entries_changed_right_at_the_boundary_as_changed() {
+        let root = fixture("changed", &["before", "at", "after"]);
+        let since = time::UNIX_EPOCH + time::Duration::from_secs(1_000_000);
+        let second = time::Duration::from_secs(1);
+        for (file, mtime) in [("before", since - second), ("at", since),
+                              ("after", since + second)] {
+            fs::File::options().write(true).open(root.join(file)).unwrap()
+                .set_modified(mtime).unwrap();
+        }
+        let mut changed = options();
+        changed.filter.changed_within = Some(since);
+        let listed = match list(&Source::Path(root.clone()),
+                                &ignores::Rules::none(), &changed.filter,
+                                &changed.sort, true) {
+            Ok(entries) => entries,
+            Err(error) => panic!("{}", error),
+        };
+        let names: vec::Vec<_> = listed.iter()
+            .map(|entry| entry.name.to_string_lossy())
+            .collect();
+        assert_eq!(names, ["after", "at"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reads_the_metadata_of_a_file_when_its_asked_for() {
+        let root = fixture("lazy", &["a"]);
+        fs::write(root.join("a"), "contents").unwrap();
+        let dir = sync::Arc::from(root.as_path());
+        let listed = fs::read_dir(&root).unwrap().next().unwrap().unwrap();
+        let read = read_entry(&listed, &dir).unwrap();
+        let lazy = Entry::read(ffi::OsString::from("a"), Kind::File, None,
+                               None, &dir);
+        assert!(lazy.meta.get().is_none());
+        assert_eq!(lazy.path(), Some(root.join("a")));
+        let fields = |meta: &Meta| {
+            (meta.size, meta.mtime, meta.ctime, meta.mode, meta.id)
+        };
+        assert_eq!(fields(lazy.meta()), fields(read.meta()));
+        assert_eq!(lazy.meta().size, Some(8));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn forgets_what_it_pruned_between_walks() {
+        let root = fixture("prescans", &["a/b/c/", "d/e/", "d/e/f"]);
+        let options = Options { prune: true, ..options() };
+        assert_eq!(render(&root, &options), "\
+root
+└───d
+    └───e
+        └───f
+");
+        fs::write(root.join("a/b/c/g"), "").unwrap();
+        assert_eq!(render(&root, &options), "\
+root
+├───a
+│   └───b
+│       └───c
+│           └───g
+└───d
+    └───e
+        └───f
+");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tries_again_once_after_running_out_of_handles() {
+        let dir = path::Path::new("dir");
+        let tries = std::cell::Cell::new(0);
+        let opened = open_dir_with(dir, |_| {
+            tries.set(tries.get() + 1);
+            match tries.get() {
+                1 => Err(io::Error::from_raw_os_error(EMFILE)),
+                _ => Ok(()),
+            }
+        });
+        assert!(opened.is_ok());
+        assert_eq!(tries.get(), 2);
+
+        tries.set(0);
+        let failed = open_dir_with(dir, |_| {
+            tries.set(tries.get() + 1);
+            Err::<(), _>(io::Error::from_raw_os_error(ENFILE))
+        }).unwrap_err();
+        assert_eq!(tries.get(), 2);
+        assert!(failed.is_out_of_handles() && failed.ends_walk(false));
+    }
+
+    #[test]
+    fn parses_durations_with_several_units() {
+        let cases = [
+            ("90s", Some(90)),
+            ("1h30m", Some(5400)),
+            ("2d", Some(172800)),
+            ("1w1s", Some(604801)),
+            ("0m", Some(0)),
+            ("", None),
+            ("10", None),
+            ("h", None),
+            ("1y", None),
+            ("-1d", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_duration(text).ok(),
+                       expected.map(time::Duration::from_secs), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path;
+use std::vec;
+
+use crate::Kind;
+
+// how much of the start of a file is read to recognize it, which is enough
+// for the signatures of every format that's detected
+const HEADER_SIZE: u64 = 8192;
+
+// the mime type of an entry, files are recognized by their contents first,
+// then by their extension, and otherwise by wether they look like text;
+// entries that aren't files get the inode types file --mime-type uses
+pub fn detect(path: &path::Path, kind: Kind) -> String {
+    let special = match kind {
+        Kind::Directory => "inode/directory",
+        Kind::Symlink => "inode/symlink",
+        Kind::Other => "inode/x-special",
+        Kind::File => return file_type(path),
+    };
+    String::from(special)
+}
+
+fn file_type(path: &path::Path) -> String {
+    let guess = mime_guess::from_path(path).first();
+    let header = match header(path) {
+        Ok(value) => value,
+        // without the contents the extension is all there is to go on
+        Err(_) => return guess.map_or(String::from("application/octet-stream"),
+                                      |guess| guess.essence_str().to_string()),
+    };
+    if header.is_empty() {
+        return String::from("inode/x-empty");
+    }
+    if let Some(kind) = infer::get(&header) {
+        return String::from(kind.mime_type());
+    }
+    if let Some(guess) = guess {
+        return guess.essence_str().to_string();
+    }
+    // the header might end partway through a character
+    let text = match std::str::from_utf8(&header) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none(),
+    };
+    if text && !header.contains(&0) {
+        String::from("text/plain")
+    } else {
+        String::from("application/octet-stream")
+    }
+}
+
+fn header(path: &path::Path) -> io::Result<vec::Vec<u8>> {
+    let mut header = vec::Vec::new();
+    fs::File::open(path)?.take(HEADER_SIZE).read_to_end(&mut header)?;
+    Ok(header)
+}
@@ -0,0 +1,154 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::collections;
+use std::path;
+use std::sync;
+use std::vec;
+
+// the filesystem types --local-only leaves out, network filesystems, ones
+// that are served by a process through fuse, and the pseudo filesystems the
+// kernel uses to expose it's own state
+pub const NON_LOCAL: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "ncpfs", "afs", "9p", "ceph",
+    "glusterfs", "lustre", "gpfs", "davfs", "fuse", "fuseblk", "autofs",
+    "proc", "sysfs", "devpts", "cgroup", "cgroup2", "debugfs", "tracefs",
+    "securityfs", "pstore", "bpf", "configfs", "fusectl", "mqueue",
+    "hugetlbfs", "binfmt_misc", "efivarfs", "rpc_pipefs", "nsfs",
+];
+
+// the mount points of the system and the type of the filesystem mounted on
+// each, this is only read on linux, the first time it's needed
+static TABLE: sync::OnceLock<collections::HashMap<path::PathBuf, String>> =
+    sync::OnceLock::new();
+
+// parses the contents of a mountinfo file, each line of which describes one
+// mount with the mount point as the fifth field and the filesystem type as
+// the first field after the - separator; when something is mounted over
+// another mount, the later line is the one that's visible
+pub fn parse(mountinfo: &str) -> collections::HashMap<path::PathBuf, String> {
+    let mut table = collections::HashMap::new();
+    for line in mountinfo.lines() {
+        let fields: vec::Vec<&str> = line.split(' ').collect();
+        let separator = match fields.iter().position(|field| *field == "-") {
+            Some(value) => value,
+            None => continue,
+        };
+        if let (Some(mount_point), Some(fstype)) =
+            (fields.get(4), fields.get(separator+1)) {
+            table.insert(path::PathBuf::from(unescape(mount_point)),
+                         String::from(*fstype));
+        }
+    }
+    table
+}
+
+// mountinfo writes spaces, tabs, newlines, and backslashes in paths as
+// backslashes followed by three octal digits
+fn unescape(field: &str) -> String {
+    let mut bytes = vec::Vec::new();
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let code = tail.get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match (byte, code) {
+            (b'\\', Some(code)) => {
+                bytes.push(code);
+                rest = &tail[3..];
+            },
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            },
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(target_os = "linux")]
+fn table() -> &'static collections::HashMap<path::PathBuf, String> {
+    TABLE.get_or_init(|| {
+        match std::fs::read_to_string("/proc/self/mountinfo") {
+            Ok(mountinfo) => parse(&mountinfo),
+            Err(error) => {
+                log::info!("\"/proc/self/mountinfo\" {}, no filesystem types \
+                            are known", error);
+                collections::HashMap::new()
+            },
+        }
+    })
+}
+
+// other platforms don't have a mount table to read, so no directory is known
+// to be a mount point of any type
+#[cfg(not(target_os = "linux"))]
+fn table() -> &'static collections::HashMap<path::PathBuf, String> {
+    TABLE.get_or_init(collections::HashMap::new)
+}
+
+// wether a directory is a mount point of one of the given filesystem types, a
+// type also matches the subtypes written after it with a dot, like fuse does
+// for fuse.sshfs; path needs to be canonical to be found in the table
+pub fn is_excluded(path: &path::Path, fstypes: &[String]) -> bool {
+    table().get(path).is_some_and(|fstype| {
+        let base = fstype.split('.').next().unwrap_or(fstype);
+        fstypes.iter().any(|excluded| excluded == fstype || excluded == base)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mount_points_and_types() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 0:21 / /proc rw,nosuid - proc proc rw
+24 22 0:45 / /mnt/my\\040share rw shared:5 master:2 - cifs //nas/share rw
+25 22 0:46 / /home/me/remote rw - fuse.sshfs me@host: rw
+26 22 0:47 / /tmp rw - tmpfs tmpfs rw
+27 26 0:48 / /tmp rw - ramfs none rw
+not a mount line
+28 22 0:49 / /back\\134slash rw - xfs /dev/sdb1 rw
+";
+        let table = parse(mountinfo);
+        let cases = [
+            ("/", "ext4"),
+            ("/proc", "proc"),
+            ("/mnt/my share", "cifs"),
+            ("/home/me/remote", "fuse.sshfs"),
+            // the mount that was made last is the one that's visible
+            ("/tmp", "ramfs"),
+            ("/back\\slash", "xfs"),
+        ];
+        for (mount_point, fstype) in cases {
+            assert_eq!(table.get(path::Path::new(mount_point))
+                           .map(String::as_str),
+                       Some(fstype), "{}", mount_point);
+        }
+        assert_eq!(table.len(), cases.len());
+    }
+}
@@ -0,0 +1,142 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+
+use serde::Serialize;
+
+use std::io;
+use std::path;
+
+use crate::walk;
+use crate::{Kind, Options, Totals, TraversalError, Usage};
+
+// one line of output, nesting isn't represented by containment but by the
+// depth and path of each entry, so a line can be written as soon as its entry
+// is visited; path is relative to the root and joined with /, and the root
+// itself is the first line, with a path of . and a depth of 0
+#[derive(Serialize)]
+struct Line<'a> {
+    path: &'a str,
+    depth: usize,
+    #[serde(rename = "type")]
+    kind: Kind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    // the totals of the files beneath a directory with --du, complete is
+    // false when they're lower bounds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    complete: Option<bool>,
+}
+
+// written in place of the contents of a directory that couldn't be read, with
+// the path of the directory and the depth its contents would have had, so
+// that an unreadable directory can be told apart from an empty one
+#[derive(Serialize)]
+struct ErrorLine<'a> {
+    path: &'a str,
+    depth: usize,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    error: String,
+}
+
+// the last line, after every entry
+#[derive(Serialize)]
+struct Report {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    errors: u64,
+}
+
+// the visitor that streams the tree into out as newline delimited json, one
+// object per entry
+pub struct Ndjson<'o, W: io::Write> {
+    pub options: &'o Options,
+    pub out: crate::Output<W>,
+    // the totals of --du, see crate::disk_usage
+    pub totals: Totals,
+}
+
+impl<W: io::Write> Ndjson<'_, W> {
+    pub fn root(&mut self, size: Option<u64>, usage: Option<Usage>) {
+        self.print_line(&Line {
+            path: ".",
+            depth: 0,
+            kind: Kind::Directory,
+            size,
+            total_size: usage.map(|usage| usage.size),
+            total_files: usage.map(|usage| usage.files),
+            complete: usage.map(|usage| usage.complete),
+        });
+    }
+
+    pub fn report(&mut self, stats: walk::Stats) {
+        self.print_line(&Report { kind: "report", errors: stats.unreadable });
+    }
+
+    fn print_line(&mut self, line: &impl Serialize) {
+        // serializing a struct of plain fields can't fail
+        if let Ok(json) = serde_json::to_string(line) {
+            self.out.write_line(&json);
+        }
+    }
+}
+
+impl<W: io::Write> walk::Visitor for Ndjson<'_, W> {
+    fn visit(&mut self, node: &walk::Node)
+        -> Result<walk::WalkAction, TraversalError> {
+        if crate::is_interrupted() || self.out.error.is_some() {
+            return Ok(walk::WalkAction::Stop);
+        }
+        let entry = node.entry;
+        let usage = crate::directory_usage(node, &self.totals, self.options);
+        self.print_line(&Line {
+            path: &crate::slash_path(&node.rel_path.join(&entry.name)),
+            depth: node.depth(),
+            kind: entry.kind,
+            size: entry.meta().size,
+            total_size: usage.map(|usage| usage.size),
+            total_files: usage.map(|usage| usage.files),
+            complete: usage.map(|usage| usage.complete),
+        });
+        Ok(walk::WalkAction::Continue)
+    }
+
+    fn unreadable(&mut self, prefix: &[bool], rel_path: &path::Path,
+                  error: &TraversalError) {
+        if crate::is_interrupted() {
+            return;
+        }
+        self.print_line(&ErrorLine {
+            path: &crate::slash_path(rel_path),
+            depth: prefix.len()+1,
+            kind: "error",
+            error: error.error.to_string(),
+        });
+    }
+}
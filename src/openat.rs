@@ -0,0 +1,317 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+// reading directories on linux with openat and statx relative to the handle
+// of the directory they're in, so that what a directory or entry costs
+// doesn't grow with how deep it is in the tree; read_entries_now uses this in
+// place of std where statx is available, and the entries it reads are the
+// same either way
+
+use rayon::prelude::*;
+use rustix::fs::{AtFlags, FileType, Mode, OFlags, StatxFlags};
+use rustix::io::Errno;
+
+use std::cell;
+use std::ffi;
+use std::mem;
+use std::os::fd;
+use std::os::fd::AsFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path;
+use std::sync;
+use std::sync::atomic;
+use std::time;
+use std::vec;
+
+use crate::{Entry, Follow, Kind, Meta, TimeSource, TraversalError};
+
+// how much of a directory is read at once, which is what glibc reads too
+const DIR_BUFFER: usize = 32*1024;
+
+// the most directory handles that are kept on each thread, the tree is
+// usually shallower than this, and past it only the deepest are kept since
+// those are the ones the next directories are opened relative to
+const MAX_HANDLES: usize = 64;
+
+thread_local! {
+    // the directories read last from the root down to the one read last,
+    // along with their handles, once a directory is read that isn't beneath
+    // one it's handle is closed
+    static HANDLES: cell::RefCell<vec::Vec<(path::PathBuf, fd::OwnedFd)>>
+        = const { cell::RefCell::new(vec::Vec::new()) };
+}
+
+// wether statx can be used, it's missing before linux 4.11 and can be
+// blocked by a seccomp filter, in which case std reads directories instead
+pub fn is_supported() -> bool {
+    static SUPPORTED: sync::OnceLock<bool> = sync::OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        let probe = rustix::fs::statx(rustix::fs::CWD, c"/", AtFlags::empty(),
+                                      StatxFlags::BASIC_STATS);
+        !matches!(probe, Err(Errno::NOSYS | Errno::PERM))
+    })
+}
+
+// read_entries_now for a directory on disk, with each entry's metadata read
+// relative to the directory's handle
+pub fn read_entries<'a>(path: &path::Path, strict: bool)
+    -> Result<vec::Vec<Entry<'a>>, TraversalError> {
+    let fd = crate::open_dir_with(path, open)?;
+    let mut buffer = vec![mem::MaybeUninit::uninit(); DIR_BUFFER];
+    let mut dir = rustix::fs::RawDir::new(&fd, &mut buffer);
+    let dir_path = sync::Arc::from(path);
+    let parallel = crate::PARALLEL_STATS.load(atomic::Ordering::SeqCst);
+    let mut names = vec::Vec::new();
+    let mut entries = vec::Vec::new();
+    while let Some(entry) = dir.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) if strict => {
+                return Err(TraversalError {
+                    path: path.to_path_buf(),
+                    error: error.into(),
+                });
+            },
+            // like std the rest of a directory isn't read after an error
+            Err(_) => break,
+        };
+        let name = entry.file_name().to_bytes();
+        if name == b"." || name == b".." {
+            continue;
+        }
+        let name = ffi::OsStr::from_bytes(name).to_os_string();
+        let kind = match entry.file_type() {
+            FileType::Unknown => None,
+            file_type => Some(kind_of(file_type)),
+        };
+        // without --threads each entry is read as soon as it's listed, like
+        // crate::read_entries_now does
+        if parallel {
+            names.push((name, kind));
+            continue;
+        }
+        match read_entry(fd.as_fd(), &dir_path, name, kind) {
+            Ok(value) => entries.push(value),
+            Err(error) if strict => return Err(error),
+            Err(_) => {},
+        }
+    }
+
+    let results: vec::Vec<Result<Entry, TraversalError>> = {
+        let read = |(name, kind)| read_entry(fd.as_fd(), &dir_path, name, kind);
+        match names.len() >= crate::PARALLEL_STATS_MIN {
+            true => names.into_par_iter().map(read).collect(),
+            false => names.into_iter().map(read).collect(),
+        }
+    };
+    keep(path, fd);
+    entries.reserve(results.len());
+    for result in results {
+        match result {
+            Ok(value) => entries.push(value),
+            Err(error) if strict => return Err(error),
+            Err(_) => {},
+        }
+    }
+    Ok(entries)
+}
+
+// closes the handles kept on this thread, the next directory is opened by
+// it's whole path
+pub fn release_handles() {
+    HANDLES.with_borrow_mut(vec::Vec::clear);
+}
+
+// opens a directory relative to the deepest directory above it that still
+// has a handle, or by it's whole path if none do
+fn open(path: &path::Path) -> std::io::Result<fd::OwnedFd> {
+    let flags = OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC;
+    let fd = HANDLES.with_borrow_mut(|handles| {
+        while handles.last()
+            .is_some_and(|(dir, _)| beneath(path, dir).is_none()) {
+            handles.pop();
+        }
+        match handles.last() {
+            Some((dir, handle)) => {
+                let rest = beneath(path, dir).unwrap_or(path);
+                rustix::fs::openat(handle, rest, flags, Mode::empty())
+            },
+            None => {
+                rustix::fs::openat(rustix::fs::CWD, path, flags, Mode::empty())
+            },
+        }
+    })?;
+    Ok(fd)
+}
+
+// the rest of path after dir if it's somewhere beneath dir, the paths are
+// compared as bytes since deep in a tree they have too many components to
+// compare them one by one for every directory that's opened
+fn beneath<'p>(path: &'p path::Path, dir: &path::Path)
+    -> Option<&'p path::Path> {
+    let dir = dir.as_os_str().as_bytes();
+    let rest = path.as_os_str().as_bytes().strip_prefix(dir)?;
+    let rest = match dir.ends_with(b"/") {
+        true => rest,
+        false => rest.strip_prefix(b"/")?,
+    };
+    (!rest.is_empty()).then(|| path::Path::new(ffi::OsStr::from_bytes(rest)))
+}
+
+// keeps the handle of a directory that was read for opening the ones beneath
+// it, the directories these are opened relative to don't need to be
+// readable, only searchable, which is already what reading them needed
+fn keep(path: &path::Path, fd: fd::OwnedFd) {
+    HANDLES.with_borrow_mut(|handles| {
+        if handles.len() == MAX_HANDLES {
+            handles.remove(0);
+        }
+        handles.push((path.to_path_buf(), fd));
+    });
+}
+
+// the entry of a directory named name, like crate::read_entry does it; kind
+// is what the listing said the entry is, if it said
+fn read_entry<'a>(fd: fd::BorrowedFd, dir: &sync::Arc<path::Path>,
+                  name: ffi::OsString, kind: Option<Kind>)
+    -> Result<Entry<'a>, TraversalError> {
+    if let Some(kind) = kind.filter(|kind| !crate::reads_metadata_now(*kind)) {
+        return Ok(Entry::read(name, kind, None, None, dir));
+    }
+    let failed = |error: Errno| TraversalError {
+        path: dir.join(&name),
+        error: error.into(),
+    };
+    let mask = match crate::time_source() {
+        TimeSource::Born => StatxFlags::BASIC_STATS | StatxFlags::BTIME,
+        _ => StatxFlags::BASIC_STATS,
+    };
+    let mut stat = rustix::fs::statx(fd, &name, AtFlags::SYMLINK_NOFOLLOW,
+                                     mask).map_err(failed)?;
+    let mut follow = None;
+    if let (FileType::Symlink, Some(limit)) =
+        (file_type(&stat), crate::FOLLOW_LIMIT.get()) {
+        match rustix::fs::statx(fd, &name, AtFlags::empty(), mask) {
+            Ok(target) if file_type(&target) == FileType::Directory
+                && crate::follow_link(*limit) => {
+                stat = target;
+                follow = Some(Follow::Followed);
+            },
+            Ok(target) if file_type(&target) == FileType::Directory => {
+                follow = Some(Follow::Limited);
+            },
+            _ => {},
+        }
+    }
+    let kind = kind_of(file_type(&stat));
+    let mtime = match crate::time_source() {
+        TimeSource::Modified => timestamp(&stat.stx_mtime),
+        TimeSource::Changed => timestamp(&stat.stx_ctime),
+        TimeSource::Accessed => timestamp(&stat.stx_atime),
+        TimeSource::Born if stat.stx_mask & StatxFlags::BTIME.bits() != 0 => {
+            timestamp(&stat.stx_btime)
+        },
+        TimeSource::Born => {
+            crate::no_birth_time(&"creation time is not available for the \
+                                    filesystem");
+            timestamp(&stat.stx_mtime)
+        },
+    };
+    let device = rustix::fs::makedev(stat.stx_dev_major, stat.stx_dev_minor);
+    let meta = Meta {
+        size: Some(stat.stx_size),
+        mtime,
+        ctime: timestamp(&stat.stx_ctime),
+        mode: Some(u32::from(stat.stx_mode)),
+        sparse: kind == Kind::File
+            && crate::has_holes(stat.stx_blocks, stat.stx_size),
+        id: Some((device, stat.stx_ino)),
+    };
+    Ok(Entry::read(name, kind, follow, Some(meta), dir))
+}
+
+fn kind_of(file_type: FileType) -> Kind {
+    match file_type {
+        FileType::Directory => Kind::Directory,
+        FileType::Symlink => Kind::Symlink,
+        FileType::RegularFile => Kind::File,
+        _ => Kind::Other,
+    }
+}
+
+fn file_type(stat: &rustix::fs::Statx) -> FileType {
+    FileType::from_raw_mode(u32::from(stat.stx_mode))
+}
+
+// a timestamp of statx as the time it stands for, which can be before the
+// epoch
+fn timestamp(timestamp: &rustix::fs::StatxTimestamp)
+    -> Option<time::SystemTime> {
+    let nanoseconds = time::Duration::from_nanos(u64::from(timestamp.tv_nsec));
+    let seconds = time::Duration::from_secs(timestamp.tv_sec.unsigned_abs());
+    match timestamp.tv_sec >= 0 {
+        true => time::UNIX_EPOCH.checked_add(seconds)?,
+        false => time::UNIX_EPOCH.checked_sub(seconds)?,
+    }.checked_add(nanoseconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::os::unix;
+
+    use crate::tests::fixture;
+
+    #[test]
+    fn reads_what_std_reads() {
+        let root = fixture("openat", &["a/", "a/b", "c", "d/"]);
+        fs::write(root.join("c"), "contents").unwrap();
+        unix::fs::symlink("a", root.join("e")).unwrap();
+        let fields = |entry: &Entry| {
+            let meta = entry.meta();
+            (entry.name.clone(), entry.kind.name(), meta.size, meta.mtime,
+             meta.ctime, meta.mode, meta.sparse, meta.id, entry.path())
+        };
+        let read_std = |dir: &path::Path| {
+            let shared = sync::Arc::from(dir);
+            fs::read_dir(dir).unwrap()
+                .map(|entry| fields(&crate::read_entry(&entry.unwrap(),
+                                                       &shared).unwrap()))
+                .collect::<vec::Vec<_>>()
+        };
+        let mut read: vec::Vec<_> = read_entries(&root, true).unwrap().iter()
+            .map(fields).collect();
+        let mut expected = read_std(&root);
+        read.sort();
+        expected.sort();
+        assert_eq!(read, expected);
+        // a is opened relative to the handle of the root, which was read last
+        let read = read_entries(&root.join("a"), true).unwrap();
+        assert_eq!(read.iter().map(fields).collect::<vec::Vec<_>>(),
+                   read_std(&root.join("a")));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
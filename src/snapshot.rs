@@ -0,0 +1,341 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path;
+use std::time;
+use std::vec;
+
+use crate::format;
+use crate::ignores;
+use crate::sort;
+use crate::walk;
+use crate::{Filter, Kind, Options, Source, TraversalError};
+
+// the version of the snapshot format written by this build, snapshots with a
+// newer version are refused rather than misread
+pub const FORMAT_VERSION: u32 = 1;
+
+// a saved tree along with the version of the format it was written in
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub root: Node,
+}
+
+// a single entry of a saved tree, mtime is stored in seconds since the unix
+// epoch and unreadable marks a directory whose contents couldn't be read when
+// the snapshot was taken; modified, permissions, and is_symlink are only
+// written by --json-meta and are ignored when loading, modified is mtime as
+// an RFC 3339 timestamp in the local time zone and permissions are the four
+// octal digits of the mode, like 0755
+#[derive(Serialize, Deserialize)]
+pub struct Node {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_symlink: Option<bool>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unreadable: bool,
+    #[serde(default, skip_serializing_if = "vec::Vec::is_empty")]
+    pub children: vec::Vec<Node>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Node {
+    fn new(name: String, kind: Kind, size: Option<u64>,
+           mtime: Option<time::SystemTime>) -> Node {
+        let mtime = mtime
+            .and_then(|mtime| mtime.duration_since(time::UNIX_EPOCH).ok())
+            .map(|mtime| mtime.as_secs());
+        Node {
+            name,
+            kind,
+            size,
+            mtime,
+            modified: None,
+            permissions: None,
+            is_symlink: None,
+            unreadable: false,
+            children: vec::Vec::new(),
+        }
+    }
+
+    // fills in the fields of --json-meta
+    fn with_meta(self, mtime: Option<time::SystemTime>, mode: Option<u32>)
+        -> Node {
+        let modified = mtime.map(|mtime| {
+            chrono::DateTime::<chrono::Local>::from(mtime)
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+        });
+        Node {
+            modified,
+            permissions: mode.map(format::octal_permissions),
+            is_symlink: Some(self.kind == Kind::Symlink),
+            ..self
+        }
+    }
+}
+
+// traverse the whole tree under path, nothing is filtered out so that the
+// snapshot can later be rendered with any options; directories that can't be
+// read are recorded as unreadable, only running out of file descriptors
+// fails the scan
+pub fn scan(path: &path::Path, name: &str, metadata: &fs::Metadata)
+    -> Result<Node, TraversalError> {
+    let mut root = Node::new(String::from(name), Kind::Directory,
+                             Some(metadata.len()), metadata.modified().ok());
+    scan_children(&Source::Path(path.to_path_buf()), &mut root)?;
+    Ok(root)
+}
+
+fn scan_children(source: &Source, node: &mut Node)
+    -> Result<(), TraversalError> {
+    let entries = match crate::list(source, &ignores::Rules::none(),
+                                    &Filter::default(),
+                                    &sort::Sort::default(), true) {
+        Ok(value) => value,
+        Err(error) if error.is_out_of_handles() => return Err(error),
+        Err(_) => {
+            node.unreadable = true;
+            return Ok(());
+        },
+    };
+
+    for entry in entries {
+        let meta = entry.meta();
+        let mut child = Node::new(entry.name.to_string_lossy().into_owned(),
+                                  entry.kind, meta.size, meta.mtime);
+        if entry.kind == Kind::Directory {
+            scan_children(&entry.source(), &mut child)?;
+        }
+        node.children.push(child);
+    }
+    Ok(())
+}
+
+// builds the tree under root as it would be printed, with the filter, sort,
+// depth limit, and the rest of the options applied; unlike printing, the
+// whole tree is held in memory until it's complete, which for a tree of
+// millions of entries takes hundreds of megabytes, so this is only used when
+// the tree is wanted as a single document
+pub fn build(name: &str, root: &Source, rules: &ignores::Rules,
+             options: &Options) -> Result<Node, TraversalError> {
+    let (size, mtime) = crate::root_details(root);
+    let mut root_node = Node::new(String::from(name), Kind::Directory, size,
+                                  mtime);
+    if options.json_meta {
+        let mode = match root {
+            Source::Path(path) => {
+                fs::metadata(path).ok().and_then(|metadata| {
+                    crate::mode(&metadata)
+                })
+            },
+            Source::Node(_) | Source::Roots(_) => None,
+        };
+        root_node = root_node.with_meta(mtime, mode);
+    }
+    let mut builder = Builder {
+        root: root_node,
+        levels: vec::Vec::new(),
+        meta: options.json_meta,
+    };
+    walk::walk(root, rules, options, &mut builder)?;
+    Ok(builder.root)
+}
+
+// the visitor that collects the entries of a walk into nodes, levels holds
+// the contents of every directory from the root down to the one being walked
+struct Builder {
+    root: Node,
+    levels: vec::Vec<vec::Vec<Node>>,
+    // wether the fields of --json-meta are filled in
+    meta: bool,
+}
+
+impl Builder {
+    // the directory that was visited last, which is the one a walk enters
+    // next, or the root before anything was visited
+    fn last_dir(&mut self) -> &mut Node {
+        match self.levels.last_mut().and_then(|level| level.last_mut()) {
+            Some(node) => node,
+            None => &mut self.root,
+        }
+    }
+}
+
+impl walk::Visitor for Builder {
+    fn visit(&mut self, node: &walk::Node)
+        -> Result<walk::WalkAction, TraversalError> {
+        if crate::is_interrupted() {
+            return Ok(walk::WalkAction::Stop);
+        }
+        let entry = node.entry;
+        let meta = entry.meta();
+        let mut built = Node::new(entry.name.to_string_lossy().into_owned(),
+                                  entry.kind, meta.size, meta.mtime);
+        if self.meta {
+            built = built.with_meta(meta.mtime, meta.mode);
+        }
+        if let Some(level) = self.levels.last_mut() {
+            level.push(built);
+        }
+        Ok(walk::WalkAction::Continue)
+    }
+
+    fn enter(&mut self, _dir: &Source) {
+        self.levels.push(vec::Vec::new());
+    }
+
+    fn leave(&mut self) {
+        if let Some(children) = self.levels.pop() {
+            self.last_dir().children = children;
+        }
+    }
+
+    fn unreadable(&mut self, _prefix: &[bool], _rel_path: &path::Path,
+                  _error: &TraversalError) {
+        self.last_dir().unreadable = true;
+    }
+}
+
+// write the tree rooted at root to file as compact json
+pub fn save(file: &path::Path, root: Node) -> io::Result<Node> {
+    let snapshot = Snapshot {
+        version: FORMAT_VERSION,
+        root,
+    };
+    let mut writer = io::BufWriter::new(fs::File::create(file)?);
+    serde_json::to_writer(&mut writer, &snapshot)?;
+    writer.flush()?;
+    Ok(snapshot.root)
+}
+
+// write the tree rooted at root into out as compact json, in the same format
+// as a saved snapshot so that it can be loaded again
+pub fn print(out: &mut crate::Output<impl io::Write>, root: Node) {
+    let snapshot = Snapshot {
+        version: FORMAT_VERSION,
+        root,
+    };
+    // serializing plain fields can't fail
+    if let Ok(json) = serde_json::to_string(&snapshot) {
+        out.write_line(&json);
+    }
+}
+
+// read a snapshot written by save or --json, or the output of GNU tree's -J,
+// from file or from stdin when file is -; the version is checked before the
+// rest of the document is interpreted so that newer formats produce a clear
+// error, and fields that aren't known are ignored
+pub fn load(file: &path::Path) -> io::Result<Node> {
+    let mut text = String::new();
+    if file == path::Path::new("-") {
+        io::stdin().read_to_string(&mut text)?;
+    } else {
+        fs::File::open(file)?.read_to_string(&mut text)?;
+    }
+    // the document is parsed a second time into it's actual shape, since
+    // only errors from parsing the text itself have a line and column
+    let document: serde_json::Value = serde_json::from_str(&text)?;
+    if document.is_array() {
+        let entries: vec::Vec<GnuEntry> = serde_json::from_str(&text)?;
+        return entries.into_iter()
+            .find(|entry| entry.kind != "report")
+            .map(GnuEntry::into_node)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+                                          "no tree in the document"));
+    }
+
+    let version = document.get("version").and_then(|version| version.as_u64());
+    match version {
+        Some(version) if version <= FORMAT_VERSION as u64 => {},
+        Some(version) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("snapshot format version {} is newer than the \
+                         supported version {}", version, FORMAT_VERSION)));
+        },
+        None => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                "missing snapshot format version"));
+        },
+    }
+
+    let snapshot: Snapshot = serde_json::from_str(&text)?;
+    Ok(snapshot.root)
+}
+
+// an entry of the output of GNU tree's -J, which is an array holding the
+// root followed by a report; a directory that couldn't be read has an
+// object with an error in it's contents, and times aren't read since they're
+// formatted however the user chose
+#[derive(Deserialize)]
+struct GnuEntry {
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    size: Option<u64>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    contents: vec::Vec<GnuEntry>,
+}
+
+impl GnuEntry {
+    fn into_node(self) -> Node {
+        let kind = match self.kind.as_str() {
+            "directory" => Kind::Directory,
+            "file" => Kind::File,
+            "link" => Kind::Symlink,
+            _ => Kind::Other,
+        };
+        let mut node = Node::new(self.name, kind, self.size, None);
+        for entry in self.contents {
+            if entry.error.is_some() {
+                node.unreadable = true;
+            } else {
+                node.children.push(entry.into_node());
+            }
+        }
+        node
+    }
+}
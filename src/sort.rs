@@ -0,0 +1,424 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use icu_collator::options::CollatorOptions;
+use icu_collator::{Collator, CollatorBorrowed};
+use icu_locale_core::Locale;
+
+use std::cmp;
+use std::env;
+use std::ffi;
+use std::iter;
+use std::path;
+use std::time;
+use std::vec;
+
+// the keys accepted by --sort, listed in error messages
+const KEYS: &str = "name, name-mixed, mtime, ctime, size, version, extension \
+                    (or ext), none";
+
+#[derive(Clone, Copy)]
+enum Key {
+    Name,
+    Mtime,
+    Ctime,
+    Size,
+    Version,
+    Extension,
+}
+
+#[derive(Clone, Copy)]
+struct SortKey {
+    key: Key,
+    descending: bool,
+}
+
+// name is always the last key compared, so entries only ever compare equal if
+// they have the same name and the order is deterministic
+const TIE_BREAKER: SortKey = SortKey {
+    key: Key::Name,
+    descending: false,
+};
+
+// how the entries of a directory are ordered, keys are compared in order with
+// each one only consulted when all the previous ones are equal; when grouping
+// directories first the groups are formed before any key is compared, so the
+// keys only decide the order within each group
+#[derive(Default)]
+pub struct Sort {
+    keys: vec::Vec<SortKey>,
+    reverse: bool,
+    dirs_first: bool,
+    // wether entries are left in the order they're read in, with none
+    unsorted: bool,
+    collator: Option<CollatorBorrowed<'static>>,
+    // wether names are put in normalization form C before being compared
+    normalize: bool,
+}
+
+// the parts of an entry that sorting can depend on
+#[derive(Clone, Copy)]
+pub struct SortFields<'a> {
+    pub name: &'a ffi::OsStr,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub mtime: Option<time::SystemTime>,
+    pub ctime: Option<time::SystemTime>,
+}
+
+impl Sort {
+    // parse a comma separated list of keys, each of which can be suffixed with
+    // :desc (or :asc, which is the default) to choose its direction; none and
+    // name-mixed are whole orders rather than keys, so they can only be given
+    // on their own, and name-mixed is the default order spelled out, which
+    // keeps directories among the files and so can't be grouped
+    pub fn parse(spec: &str, reverse: bool, dirs_first: bool)
+        -> Result<Sort, String> {
+        match spec {
+            "none" => return Ok(Sort {
+                unsorted: true,
+                ..Sort::new(reverse, dirs_first)
+            }),
+            "name-mixed" if dirs_first => {
+                return Err(String::from("--sort name-mixed can't be combined \
+                                         with --dirsfirst"));
+            },
+            "name-mixed" => return Ok(Sort::new(reverse, false)),
+            _ => {},
+        }
+
+        let mut keys = vec::Vec::new();
+        for item in spec.split(',') {
+            let (name, direction) = match item.split_once(':') {
+                Some((name, direction)) => (name, Some(direction)),
+                None => (item, None),
+            };
+            let key = match name {
+                "name" => Key::Name,
+                "mtime" => Key::Mtime,
+                "ctime" => Key::Ctime,
+                "size" => Key::Size,
+                "version" => Key::Version,
+                "extension" | "ext" => Key::Extension,
+                "none" | "name-mixed" => return Err(format!(
+                    "sort order \"{}\" can't be combined with other keys",
+                    name)),
+                _ => return Err(format!("unknown sort key \"{}\", valid keys \
+                                         are: {}", name, KEYS)),
+            };
+            let descending = match direction {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(direction) => return Err(format!(
+                    "unknown sort direction \"{}\" for key \"{}\", expected \
+                     asc or desc", direction, name)),
+            };
+            keys.push(SortKey { key, descending });
+        }
+        Ok(Sort {
+            keys,
+            reverse,
+            dirs_first,
+            unsorted: false,
+            collator: None,
+            normalize: false,
+        })
+    }
+
+    // the plain alphabetical order, optionally reversed or grouped
+    pub fn new(reverse: bool, dirs_first: bool) -> Sort {
+        Sort {
+            keys: vec::Vec::new(),
+            reverse,
+            dirs_first,
+            unsorted: false,
+            collator: None,
+            normalize: false,
+        }
+    }
+
+    // compare names using the collation rules of the LC_COLLATE locale rather
+    // than byte by byte, if the locale can't be determined (or is the C
+    // locale) names keep being compared as bytes
+    pub fn use_locale(&mut self) {
+        self.collator = collation_locale().and_then(|locale| {
+            Collator::try_new((&locale).into(), CollatorOptions::default())
+                .ok()
+        });
+    }
+
+    // compare names in normalization form C, so that the same name sorts the
+    // same way wether it's stored composed or decomposed
+    pub fn normalize_names(&mut self) {
+        self.normalize = true;
+    }
+
+    // wether this is the plain alphabetical order
+    pub fn is_default(&self) -> bool {
+        self.keys.is_empty() && !self.reverse && !self.dirs_first
+            && !self.unsorted && self.collator.is_none() && !self.normalize
+    }
+
+    // wether entries are kept in the order they're read in, which -r reverses
+    // and --dirsfirst only groups, without comparing anything else
+    pub fn is_unsorted(&self) -> bool {
+        self.unsorted
+    }
+
+    pub fn is_reversed(&self) -> bool {
+        self.reverse
+    }
+
+    // wether any of the keys compare more than the names of entries
+    pub fn uses_metadata(&self) -> bool {
+        self.keys.iter().any(|key| {
+            matches!(key.key, Key::Mtime | Key::Ctime | Key::Size)
+        })
+    }
+
+    pub fn compare(&self, a: &SortFields, b: &SortFields) -> cmp::Ordering {
+        let (a_name, b_name);
+        let (a, b) = if self.normalize {
+            a_name = crate::nfc(a.name);
+            b_name = crate::nfc(b.name);
+            (&SortFields { name: &a_name, ..*a },
+             &SortFields { name: &b_name, ..*b })
+        } else {
+            (a, b)
+        };
+
+        if self.dirs_first {
+            match b.is_dir.cmp(&a.is_dir) {
+                cmp::Ordering::Equal => {},
+                ordering => return ordering,
+            }
+        }
+
+        if self.unsorted {
+            return cmp::Ordering::Equal;
+        }
+
+        for key in self.keys.iter().chain(iter::once(&TIE_BREAKER)) {
+            let mut ordering = match (key.key, &self.collator) {
+                (Key::Name, Some(collator)) => {
+                    collator.compare(&a.name.to_string_lossy(),
+                                     &b.name.to_string_lossy())
+                        .then(a.name.cmp(b.name))
+                },
+                _ => compare_key(key.key, a, b),
+            };
+            if key.descending != self.reverse {
+                ordering = ordering.reverse();
+            }
+            if ordering != cmp::Ordering::Equal {
+                return ordering;
+            }
+        }
+        cmp::Ordering::Equal
+    }
+}
+
+fn compare_key(key: Key, a: &SortFields, b: &SortFields) -> cmp::Ordering {
+    match key {
+        Key::Name => a.name.cmp(b.name),
+        Key::Mtime => a.mtime.cmp(&b.mtime),
+        Key::Ctime => a.ctime.cmp(&b.ctime),
+        Key::Size => a.size.cmp(&b.size),
+        Key::Version => version_cmp(a.name.as_encoded_bytes(),
+                                    b.name.as_encoded_bytes()),
+        // entries without an extension sort before those that have one
+        Key::Extension => extension(a.name).cmp(&extension(b.name)),
+    }
+}
+
+// the case folded extension of a name, which is what follows the last dot,
+// so archive.tar.gz has the extension gz; a name that only starts with a dot,
+// like .gitignore, has none
+fn extension(name: &ffi::OsStr) -> Option<String> {
+    path::Path::new(name).extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+}
+
+// the locale used for collation, from the same variables as the C library
+// consults, with the encoding and modifier parts of the name dropped
+fn collation_locale() -> Option<Locale> {
+    let name = ["LC_ALL", "LC_COLLATE", "LANG"].iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    let name = name.split(['.', '@']).next()?;
+    if name == "C" || name == "POSIX" {
+        return None;
+    }
+    Locale::try_from_str(&name.replace('_', "-")).ok()
+}
+
+// compares names so that runs of digits are ordered by their numeric value,
+// e.g. file2 sorts before file10
+fn version_cmp(a: &[u8], b: &[u8]) -> cmp::Ordering {
+    let mut a = a;
+    let mut b = b;
+    while !a.is_empty() && !b.is_empty() {
+        let a_digits = a[0].is_ascii_digit();
+        let b_digits = b[0].is_ascii_digit();
+        let a_len = a.iter().position(|c| c.is_ascii_digit() != a_digits)
+            .unwrap_or(a.len());
+        let b_len = b.iter().position(|c| c.is_ascii_digit() != b_digits)
+            .unwrap_or(b.len());
+        let (a_run, a_rest) = a.split_at(a_len);
+        let (b_run, b_rest) = b.split_at(b_len);
+
+        let ordering = if a_digits && b_digits {
+            // leading zeros don't change the value, and once they're removed
+            // the longer number is the larger one
+            let a_run = &a_run[a_run.iter().position(|c| *c != b'0')
+                .unwrap_or(a_run.len())..];
+            let b_run = &b_run[b_run.iter().position(|c| *c != b'0')
+                .unwrap_or(b_run.len())..];
+            a_run.len().cmp(&b_run.len()).then(a_run.cmp(b_run))
+        } else {
+            a_run.cmp(b_run)
+        };
+        if ordering != cmp::Ordering::Equal {
+            return ordering;
+        }
+        a = a_rest;
+        b = b_rest;
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the names of entries, given as their name, wether they're a directory,
+    // and their size, in the order sort puts them in
+    fn sorted(sort: &Sort, entries: &[(&str, bool, u64)]) -> vec::Vec<String> {
+        let mut fields: vec::Vec<SortFields> = entries.iter()
+            .map(|(name, is_dir, size)| SortFields {
+                name: ffi::OsStr::new(name),
+                is_dir: *is_dir,
+                size: Some(*size),
+                mtime: None,
+                ctime: None,
+            })
+            .collect();
+        fields.sort_by(|a, b| sort.compare(a, b));
+        fields.iter()
+            .map(|fields| fields.name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn parse(spec: &str, reverse: bool, dirs_first: bool) -> Sort {
+        Sort::parse(spec, reverse, dirs_first).unwrap()
+    }
+
+    #[test]
+    fn orders_by_each_key() {
+        let entries = [("b.txt", false, 3), ("a.rs", false, 1),
+                       ("c", false, 2), ("file10", false, 0),
+                       ("file2", false, 4)];
+        let cases = [
+            ("name", false, ["a.rs", "b.txt", "c", "file10", "file2"]),
+            ("name", true, ["file2", "file10", "c", "b.txt", "a.rs"]),
+            ("size", false, ["file10", "a.rs", "c", "b.txt", "file2"]),
+            ("size:desc", false, ["file2", "b.txt", "c", "a.rs", "file10"]),
+            ("size:desc", true, ["file10", "a.rs", "c", "b.txt", "file2"]),
+            ("version", false, ["a.rs", "b.txt", "c", "file2", "file10"]),
+            ("ext", false, ["c", "file10", "file2", "a.rs", "b.txt"]),
+        ];
+        for (spec, reverse, expected) in cases {
+            assert_eq!(sorted(&parse(spec, reverse, false), &entries),
+                       expected, "{} reverse={}", spec, reverse);
+        }
+    }
+
+    #[test]
+    fn breaks_ties_by_the_next_key_and_then_by_name() {
+        let entries = [("d.rs", false, 1), ("c.txt", false, 2),
+                       ("b.rs", false, 2), ("a.txt", false, 1)];
+        let cases = [
+            ("size", false, ["a.txt", "d.rs", "b.rs", "c.txt"]),
+            // the name that breaks ties stays ascending when the key is
+            // descending, and is only reversed along with everything else
+            ("size:desc", false, ["b.rs", "c.txt", "a.txt", "d.rs"]),
+            ("size", true, ["c.txt", "b.rs", "d.rs", "a.txt"]),
+            ("ext,size", false, ["d.rs", "b.rs", "a.txt", "c.txt"]),
+            ("ext,size:desc", false, ["b.rs", "d.rs", "c.txt", "a.txt"]),
+            ("size,ext:desc", false, ["a.txt", "d.rs", "c.txt", "b.rs"]),
+        ];
+        for (spec, reverse, expected) in cases {
+            assert_eq!(sorted(&parse(spec, reverse, false), &entries),
+                       expected, "{} reverse={}", spec, reverse);
+        }
+    }
+
+    #[test]
+    fn groups_directories_before_comparing_keys() {
+        let entries = [("b", true, 9), ("a", false, 1), ("d", true, 0),
+                       ("c", false, 5)];
+        assert_eq!(sorted(&parse("size:desc", false, true), &entries),
+                   ["b", "d", "c", "a"]);
+        assert_eq!(sorted(&Sort::new(true, true), &entries),
+                   ["d", "b", "c", "a"]);
+        // without keys the order they're in is kept within each group
+        assert_eq!(sorted(&parse("none", false, true), &entries),
+                   ["b", "d", "a", "c"]);
+    }
+
+    #[test]
+    fn compares_numbers_in_names_by_value() {
+        let cases = [
+            ("file2", "file10", cmp::Ordering::Less),
+            ("file010", "file9", cmp::Ordering::Greater),
+            ("file01", "file1", cmp::Ordering::Equal),
+            ("v1.2.10", "v1.2.9", cmp::Ordering::Greater),
+            ("a", "a1", cmp::Ordering::Less),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(version_cmp(a.as_bytes(), b.as_bytes()), expected,
+                       "{} {}", a, b);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_and_combined_orders() {
+        assert!(Sort::parse("colour", false, false).is_err());
+        assert!(Sort::parse("size:up", false, false).is_err());
+        assert!(Sort::parse("size,none", false, false).is_err());
+        assert!(Sort::parse("name-mixed", false, true).is_err());
+        assert!(parse("name-mixed", false, false).is_default());
+    }
+
+    #[test]
+    fn compares_case_folded_extensions() {
+        let entries = [("b.ÉTÉ", false, 0), ("archive.tar.gz", false, 0),
+                       (".gitignore", false, 0), ("a.été", false, 0),
+                       ("c.GZ", false, 0), ("Makefile", false, 0)];
+        // hidden files have no extension, and archive.tar.gz only has gz
+        assert_eq!(sorted(&parse("ext", false, false), &entries),
+                   [".gitignore", "Makefile", "archive.tar.gz", "c.GZ",
+                    "a.été", "b.ÉTÉ"]);
+    }
+}
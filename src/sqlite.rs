@@ -0,0 +1,82 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use rusqlite::{params, Connection};
+
+use std::path;
+use std::time;
+
+use crate::Kind;
+
+// the schema of an exported tree, there's one row per entry with the root
+// itself stored as "." without a parent; paths are relative to the root and
+// use / as the separator, parent is the path of the directory an entry is in,
+// type is one of directory, file, symlink, or other, and mtime is in seconds
+// since the unix epoch; exporting into an existing database replaces every
+// row the table already has, so entries that have since been removed don't
+// linger, and leaves any other tables alone
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        path TEXT PRIMARY KEY,
+        parent TEXT,
+        type TEXT NOT NULL,
+        size INTEGER,
+        mtime INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS entries_parent ON entries (parent);
+";
+
+// an open export, the old rows are cleared and every row is written inside
+// one transaction which is only committed by finish, so the table keeps it's
+// old rows until the new ones are all written
+pub struct Export {
+    connection: Connection,
+}
+
+impl Export {
+    pub fn create(file: &path::Path) -> rusqlite::Result<Export> {
+        let connection = Connection::open(file)?;
+        connection.execute_batch(SCHEMA)?;
+        connection.execute_batch("BEGIN; DELETE FROM entries;")?;
+        Ok(Export { connection })
+    }
+
+    pub fn insert(&mut self, path: &str, parent: Option<&str>, kind: Kind,
+                  size: Option<u64>, mtime: Option<time::SystemTime>)
+        -> rusqlite::Result<()> {
+        let mtime = mtime
+            .and_then(|mtime| mtime.duration_since(time::UNIX_EPOCH).ok())
+            .map(|mtime| mtime.as_secs() as i64);
+        let mut statement = self.connection.prepare_cached(
+            "INSERT OR REPLACE INTO entries (path, parent, type, size, mtime)
+             VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        statement.execute(params![path, parent, kind.name(),
+                                  size.map(|size| size as i64), mtime])?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> rusqlite::Result<()> {
+        self.connection.execute_batch("COMMIT")
+    }
+}
@@ -0,0 +1,863 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::collections;
+use std::iter;
+use std::path;
+use std::vec;
+
+use crate::ignores;
+use crate::{Decision, Entry, EntryInfo, Follow, Kind, Options, Origin};
+use crate::{Source, TraversalError, TreeOptions};
+
+// the note of a directory that -x doesn't descend into
+pub const MOUNT_POINT: &str = "[mount point, not crossed]";
+
+// an entry as it's handed to a visitor, along with where it is in the tree
+pub struct Node<'n, 'a> {
+    pub entry: &'n Entry<'a>,
+    // the path of the directory the entry is in, relative to the root
+    pub rel_path: &'n path::Path,
+    // one bool per level of the tree recording wether the entry at that level
+    // is the last of its siblings, the last one is for the entry itself
+    pub prefix: &'n [bool],
+    // the ignore rules in effect for the directory the entry is in
+    pub rules: &'n ignores::Rules,
+    // why a directory that was listed won't be descended into, regardless of
+    // what the visitor returns, this is shown after its name
+    pub note: Option<&'static str>,
+    // how many directories the entry stands for, which is more than one for
+    // a chain joined by --collapse
+    pub joined: u64,
+}
+
+impl Node<'_, '_> {
+    // how deep the entry is, the entries of the root are at depth 1
+    pub fn depth(&self) -> usize {
+        self.prefix.len()
+    }
+}
+
+/// What a walk does after a visitor has seen an entry.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WalkAction {
+    /// Carry on, descending into the entry if it's a directory.
+    Continue,
+    /// Carry on without descending into the entry, for anything other than
+    /// a directory this is the same as continuing.
+    SkipChildren,
+    /// End the walk right away, without visiting anything else; this isn't
+    /// an error, and the directories still being walked aren't left.
+    Stop,
+}
+
+/// What a walk went through, every directory of a chain shown as one with
+/// --collapse is counted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub directories: u64,
+    /// Everything visited that isn't a directory.
+    pub files: u64,
+    /// The directories whose contents couldn't be read.
+    pub unreadable: u64,
+    /// The symlinks to directories that were visited as directories.
+    pub followed: u64,
+}
+
+// receives the entries of a tree one at a time while it's walked, each
+// directory's contents are visited right after it, so nothing but the path
+// down to the current directory is ever held in memory
+pub trait Visitor {
+    // called for every listed entry, the returned action decides wether the
+    // walk descends into it, moves on, or ends, and an error ends it too
+    fn visit(&mut self, node: &Node) -> Result<WalkAction, TraversalError>;
+
+    // called before the contents of a directory, the root included, are
+    // visited and after the last of them, these are only called for
+    // directories that could be read
+    fn enter(&mut self, _dir: &Source) {}
+    fn leave(&mut self) {}
+
+    // called in place of the contents of a directory that couldn't be read,
+    // outside of strict mode where that stops the walk with an error instead;
+    // rel_path is the path of the directory relative to the root
+    fn unreadable(&mut self, _prefix: &[bool], _rel_path: &path::Path,
+                  _error: &TraversalError) {}
+}
+
+// the directories seen so far by a walk, by their device and inode numbers
+struct Seen {
+    // every directory from the root down to the one being walked
+    ancestors: vec::Vec<(u64, u64)>,
+    // every directory that has been descended into
+    visited: collections::HashSet<(u64, u64)>,
+}
+
+// walks the tree beneath root, handing each entry that's listed to the
+// visitor; the filter, sort, pruning, depth limit, and --no-recurse patterns
+// of the options all apply, and a directory that was already descended into,
+// which bind mounts can cause even without following symlinks, is never
+// descended into again, this is what keeps a directory that's one of its
+// own ancestors from being walked forever
+pub fn walk(root: &Source, rules: &ignores::Rules, options: &Options,
+            visitor: &mut dyn Visitor) -> Result<Stats, TraversalError> {
+    walk_scoped(root, rules, options, false, visitor)
+}
+
+// like walk, but through everything beneath root that the filter and ignore
+// rules list, what's only left out of the display isn't: files without -f,
+// anything past the depth limit, directories emptied by --prune and the ones
+// --no-recurse stops at; this is what the totals of --du add up, and it's
+// never walked with --stream or --collapse
+pub fn walk_all(root: &Source, rules: &ignores::Rules, options: &Options,
+                visitor: &mut dyn Visitor) -> Result<Stats, TraversalError> {
+    walk_scoped(root, rules, options, true, visitor)
+}
+
+// hands each step of a TreeIter to the visitor, counting what it goes
+// through along the way
+fn walk_scoped(root: &Source, rules: &ignores::Rules, options: &Options,
+               all: bool, visitor: &mut dyn Visitor)
+    -> Result<Stats, TraversalError> {
+    let mut steps = TreeIter::scoped(root.clone(), rules.clone(), options,
+                                     all);
+    let mut stats = Stats::default();
+    while let Some(step) = steps.step() {
+        match step? {
+            Step::Enter => visitor.enter(steps.dir()),
+            Step::Leave => visitor.leave(),
+            Step::Unreadable(prefix, rel_path, error) => {
+                stats.unreadable += 1;
+                visitor.unreadable(&prefix, &rel_path, &error);
+            },
+            Step::Entry(item) => {
+                match item.entry.kind {
+                    Kind::Directory => stats.directories += item.joined,
+                    _ => stats.files += 1,
+                }
+                if item.entry.follow == Some(Follow::Followed) {
+                    stats.followed += 1;
+                }
+                match visitor.visit(&item.node())? {
+                    WalkAction::Continue => {},
+                    WalkAction::SkipChildren => steps.skip_children(),
+                    WalkAction::Stop => break,
+                }
+            },
+        }
+    }
+    Ok(stats)
+}
+
+/// An entry as a TreeIter yields it, along with where it is in the tree.
+pub struct Item<'a> {
+    pub(crate) entry: Entry<'a>,
+    pub(crate) rel_path: path::PathBuf,
+    pub(crate) prefix: vec::Vec<bool>,
+    pub(crate) rules: ignores::Rules,
+    pub(crate) note: Option<&'static str>,
+    pub(crate) joined: u64,
+}
+
+impl<'a> Item<'a> {
+    pub(crate) fn node(&self) -> Node<'_, 'a> {
+        Node {
+            entry: &self.entry,
+            rel_path: &self.rel_path,
+            prefix: &self.prefix,
+            rules: &self.rules,
+            note: self.note,
+            joined: self.joined,
+        }
+    }
+
+    pub fn info(&self) -> EntryInfo<'_> {
+        EntryInfo { entry: &self.entry }
+    }
+
+    /// The path of the entry relative to the root.
+    pub fn path(&self) -> path::PathBuf {
+        self.rel_path.join(&self.entry.name)
+    }
+
+    /// How deep the entry is, the entries of the root are at depth 1.
+    pub fn depth(&self) -> usize {
+        self.prefix.len()
+    }
+
+    /// One bool per level of the tree recording wether the entry at that
+    /// level is the last of it's siblings, the last one is for the entry
+    /// itself.
+    pub fn prefix(&self) -> &[bool] {
+        &self.prefix
+    }
+
+    /// Why a directory won't be descended into, if it isn't, which is shown
+    /// after it's name.
+    pub fn note(&self) -> Option<&'static str> {
+        self.note
+    }
+}
+
+// what a walk goes through, in order
+enum Step<'a> {
+    // the contents of a directory were read, and are what comes next until
+    // it's left; the directory is the one TreeIter::dir returns
+    Enter,
+    Entry(Item<'a>),
+    Leave,
+    // a directory that couldn't be read, with it's prefix and it's path
+    // relative to the root, outside of strict mode where that ends the walk
+    // with the error instead
+    Unreadable(vec::Vec<bool>, path::PathBuf, TraversalError),
+}
+
+// the listed contents of a directory
+type ListResult<'a> = Result<vec::Vec<Entry<'a>>, TraversalError>;
+
+// the entries of a directory that are still to come
+type Entries<'a> =
+    Box<dyn Iterator<Item = Result<Entry<'a>, TraversalError>> + 'a>;
+
+// a directory that's read, or about to be
+struct Dir<'a> {
+    source: Source<'a>,
+    // the rules in effect for it's contents
+    rules: ignores::Rules,
+    rel_path: path::PathBuf,
+    prefix: vec::Vec<bool>,
+    // how many ancestors there were before it was descended into, which are
+    // the ones left once it's done
+    ancestors: usize,
+}
+
+// a directory being walked, the entry after the one that's next is peeked at
+// to know wether the next one is the last
+struct Frame<'a> {
+    dir: Dir<'a>,
+    entries: iter::Peekable<Entries<'a>>,
+}
+
+/// The walk of walk as an iterator over the entries it lists, in the order a
+/// tree is rendered in. Each directory's contents come right after it, and
+/// only the directories from the root down to the current one are held,
+/// along with the entries of each that are still to come, so what it holds
+/// depends on how deep the tree is rather than how big. A directory that
+/// can't be read is an Err in place of it's contents, and the walk carries
+/// on with what's after it, except with strict where it's the last item.
+/// The connectors of the classic rendering come from the prefix of each
+/// item:
+///
+/// ```
+/// # use std::fs;
+/// # let root = std::env::temp_dir()
+/// #     .join(format!("tree-doc-{}", std::process::id()));
+/// # fs::create_dir_all(root.join("src"))?;
+/// # fs::write(root.join("src/lib.rs"), "")?;
+/// # fs::write(root.join("Cargo.toml"), "")?;
+/// let options = tree::TreeOptions::new().files(true);
+/// let mut rendered = String::new();
+/// for item in tree::TreeIter::new(&root, &options) {
+///     let item = item?;
+///     let (last, above) = item.prefix().split_last().unwrap();
+///     for last in above {
+///         rendered.push_str(if *last { "    " } else { "│   " });
+///     }
+///     rendered.push_str(if *last { "└───" } else { "├───" });
+///     rendered.push_str(&item.info().name().to_string_lossy());
+///     rendered.push('\n');
+/// }
+/// assert_eq!(rendered, "├───Cargo.toml\n└───src\n    └───lib.rs\n");
+///
+/// let scanned = tree::scan(&root, &options)?;
+/// assert_eq!(tree::render(&scanned, &options),
+///            format!("{}\n{}", scanned.name, rendered));
+/// # fs::remove_dir_all(root)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct TreeIter<'a> {
+    options: &'a Options,
+    // wether this is for a walk_all
+    all: bool,
+    seen: Seen,
+    // the directories being walked, innermost last
+    stack: vec::Vec<Frame<'a>>,
+    // the directory to read before anything else, which is the root at first
+    // and after that the directory yielded last if it's descended into, with
+    // it's contents if they've already been read to find the end of a chain
+    // with --collapse
+    next_dir: Option<(Dir<'a>, Option<ListResult<'a>>)>,
+    // wether the walk has ended, after which nothing is yielded
+    done: bool,
+}
+
+impl<'a> TreeIter<'a> {
+    /// A walk of the tree beneath root, with the options' filter and depth
+    /// limit applied.
+    pub fn new(root: &path::Path, options: &'a TreeOptions) -> TreeIter<'a> {
+        TreeIter::over(Source::Path(root.to_path_buf()),
+                       ignores::Rules::none(), &options.options)
+    }
+
+    // a walk of the tree beneath root, with the rules in effect there
+    pub(crate) fn over(root: Source<'a>, rules: ignores::Rules,
+                       options: &'a Options) -> TreeIter<'a> {
+        TreeIter::scoped(root, rules, options, false)
+    }
+
+    fn scoped(root: Source<'a>, rules: ignores::Rules, options: &'a Options,
+              all: bool) -> TreeIter<'a> {
+        options.prescans.clear();
+        let root_id = crate::dir_id(&root);
+        let seen = Seen {
+            ancestors: root_id.into_iter().collect(),
+            visited: root_id.into_iter().collect(),
+        };
+        let root = Dir {
+            source: root,
+            rules,
+            rel_path: path::PathBuf::new(),
+            prefix: vec::Vec::new(),
+            ancestors: seen.ancestors.len(),
+        };
+        TreeIter {
+            options,
+            all,
+            seen,
+            stack: vec::Vec::new(),
+            next_dir: Some((root, None)),
+            done: false,
+        }
+    }
+
+    /// Keeps the directory yielded last from being descended into, for a
+    /// directory that would have been this is as if it never was, so it can
+    /// still be descended into where it's reached again.
+    pub fn skip_children(&mut self) {
+        if let Some((dir, _)) = self.next_dir.take() {
+            for id in self.seen.ancestors.drain(dir.ancestors..) {
+                self.seen.visited.remove(&id);
+            }
+        }
+    }
+
+    // the directory entered last that hasn't been left yet
+    fn dir(&self) -> &Source<'a> {
+        &self.stack[self.stack.len()-1].dir.source
+    }
+
+    fn step(&mut self) -> Option<Result<Step<'a>, TraversalError>> {
+        if self.done {
+            return None;
+        }
+        let step = self.advance();
+        if matches!(step, None | Some(Err(_))) {
+            self.done = true;
+        }
+        step
+    }
+
+    fn advance(&mut self) -> Option<Result<Step<'a>, TraversalError>> {
+        if let Some((dir, entries)) = self.next_dir.take() {
+            return Some(self.open(dir, entries));
+        }
+        let frame = self.stack.last_mut()?;
+        let entry = match frame.entries.next() {
+            Some(Ok(entry)) => entry,
+            Some(Err(error)) => return Some(Err(error)),
+            None => {
+                let ancestors = frame.dir.ancestors;
+                self.stack.pop();
+                self.seen.ancestors.truncate(ancestors);
+                return Some(Ok(Step::Leave));
+            },
+        };
+        let last = frame.entries.peek().is_none();
+        Some(Ok(self.entry(entry, last)))
+    }
+
+    // reads the contents of a directory, unless they already were
+    fn open(&mut self, dir: Dir<'a>, entries: Option<ListResult<'a>>)
+        -> Result<Step<'a>, TraversalError> {
+        let options = self.options;
+        let listed = |entries: ListResult<'a>| {
+            entries.map(|entries| Box::new(entries.into_iter().map(Ok))
+                as Entries<'a>)
+        };
+        let entries = match (entries, &dir.source) {
+            (Some(entries), _) => listed(entries),
+            (None, Source::Path(path)) if options.stream && !self.all => {
+                crate::stream_entries(path, dir.rules.clone(), &options.filter,
+                                      options.show_files)
+            },
+            (None, source) => {
+                listed(contents(source, &dir.rules, options, self.all))
+            },
+        };
+        match entries {
+            Ok(entries) => {
+                self.stack.push(Frame { dir, entries: entries.peekable() });
+                Ok(Step::Enter)
+            },
+            Err(error) => {
+                self.seen.ancestors.truncate(dir.ancestors);
+                if error.ends_walk(options.filter.strict) {
+                    return Err(error);
+                }
+                log::info!("{}, it's contents are skipped", error);
+                Ok(Step::Unreadable(dir.prefix, dir.rel_path, error))
+            },
+        }
+    }
+
+    // the step for an entry of the innermost directory, last is wether it's
+    // the last of them; a directory that's descended into is read next
+    fn entry(&mut self, entry: Entry<'a>, last: bool) -> Step<'a> {
+        let options = self.options;
+        let dir = &self.stack[self.stack.len()-1].dir;
+        let rel_path = dir.rel_path.clone();
+        let rules = dir.rules.clone();
+        let mut prefix = dir.prefix.clone();
+        prefix.push(last);
+        let note = note(&entry, &rel_path, options, &self.seen, self.all);
+        let at_max_depth = !self.all && options.max_depth
+            .is_some_and(|max| prefix.len() >= max);
+        let descends = entry.kind == Kind::Directory && note.is_none()
+            && !at_max_depth;
+
+        if entry.kind == Kind::Directory && at_max_depth {
+            log::info!("\"{}\" not descended into, it's at the depth limit",
+                       crate::log_path(&entry));
+        }
+        if descends {
+            let ancestors = self.seen.ancestors.len();
+            // with --collapse any directory that's descended into might be
+            // the start of a chain
+            if options.collapse && !self.all {
+                return self.chain(entry, rel_path, prefix, rules, ancestors);
+            }
+            self.seen.ancestors.extend(entry.meta().id);
+            self.seen.visited.extend(entry.meta().id);
+            let source = entry.source();
+            let dir = Dir {
+                rules: crate::child_rules(&source, &rules, options),
+                source,
+                rel_path: rel_path.join(&entry.name),
+                prefix: prefix.clone(),
+                ancestors,
+            };
+            self.next_dir = Some((dir, None));
+        }
+        Step::Entry(Item { entry, rel_path, prefix, rules, note, joined: 1 })
+    }
+
+    // follows a chain of directories that each only have another directory
+    // in them as a single entry, starting at entry, and joins their names by
+    // / into the one entry that's yielded, with the contents of the last of
+    // them read next; rel_path is the path of the directory the chain starts
+    // in, and rules are the rules in effect there
+    fn chain(&mut self, entry: Entry<'a>, rel_path: path::PathBuf,
+             prefix: vec::Vec<bool>, rules: ignores::Rules, ancestors: usize)
+        -> Step<'a> {
+        let options = self.options;
+        let mut name = entry.name.clone();
+        let mut end = entry;
+        // the rules in effect for the directory the end of the chain is in,
+        // and for it's contents
+        let mut rules = rules;
+        let mut dir_rules = crate::child_rules(&end.source(), &rules, options);
+        loop {
+            let dir_path = rel_path.join(&name);
+            self.seen.ancestors.extend(end.meta().id);
+            self.seen.visited.extend(end.meta().id);
+
+            let entries = contents(&end.source(), &dir_rules, options, false);
+            let next = match entries.as_deref() {
+                Ok([child]) if child.kind == Kind::Directory
+                    && note(child, &dir_path, options, &self.seen, false)
+                        .is_none() => {
+                    Some(child.clone())
+                },
+                _ => None,
+            };
+            if let Some(child) = next {
+                name.push("/");
+                name.push(&child.name);
+                let child_rules = crate::child_rules(&child.source(),
+                                                     &dir_rules, options);
+                rules = std::mem::replace(&mut dir_rules, child_rules);
+                end = child;
+                continue;
+            }
+
+            // names can't contain a /, so each component is one of the
+            // directories of the chain
+            let count = path::Path::new(&name).components().count();
+            let dir = Dir {
+                source: end.source(),
+                rules: dir_rules,
+                rel_path: dir_path,
+                prefix: prefix.clone(),
+                ancestors,
+            };
+            self.next_dir = Some((dir, Some(entries)));
+            return Step::Entry(Item {
+                // the joined name isn't in the directory the last one is
+                // in, so the path is kept whole
+                entry: Entry {
+                    name,
+                    origin: Origin::Source(end.source()),
+                    ..end
+                },
+                rel_path,
+                prefix,
+                rules,
+                note: None,
+                joined: count as u64,
+            });
+        }
+    }
+}
+
+impl<'a> Iterator for TreeIter<'a> {
+    type Item = Result<Item<'a>, TraversalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.step()? {
+                Ok(Step::Entry(item)) => return Some(Ok(item)),
+                Ok(Step::Unreadable(_, _, error)) | Err(error) => {
+                    return Some(Err(error));
+                },
+                Ok(Step::Enter | Step::Leave) => {},
+            }
+        }
+    }
+}
+
+impl iter::FusedIterator for TreeIter<'_> {}
+
+// the listed contents of a directory, pruned directories are dropped before
+// anything is visited so that the last entry is still known to be last; all
+// is wether this is for a walk_all
+fn contents<'a>(source: &Source<'a>, rules: &ignores::Rules,
+                options: &Options, all: bool)
+    -> Result<vec::Vec<Entry<'a>>, TraversalError> {
+    let entries = crate::list(source, rules, &options.filter, &options.sort,
+                              options.show_files || all)?;
+    if !options.prune || all {
+        return Ok(entries);
+    }
+    let mut kept = vec::Vec::new();
+    for entry in entries {
+        if entry.kind != Kind::Directory
+            || crate::has_contents(&entry.source(),
+                                   &crate::child_rules(&entry.source(), rules,
+                                                       options),
+                                   options)? {
+            kept.push(entry);
+        }
+    }
+    Ok(kept)
+}
+
+// why a directory that's listed won't be descended into, if there's a reason
+// other than the depth limit; rel_path is the path of the directory it's in,
+// and all is wether this is for a walk_all, which --no-recurse doesn't stop
+fn note(entry: &Entry, rel_path: &path::Path, options: &Options,
+        seen: &Seen, all: bool) -> Option<&'static str> {
+    if entry.follow == Some(Follow::Limited) {
+        return Some("[symlink limit reached]");
+    }
+    let id = match entry.kind {
+        Kind::Directory => entry.meta().id,
+        _ => None,
+    };
+    // directories that were already descended into, mount points with -x or
+    // of a type --exclude-fstype leaves out, and ones the descends hook
+    // rejects, are shown but their contents aren't
+    if id.is_some_and(|id| seen.ancestors.contains(&id)) {
+        return Some("[recursive, not followed]");
+    } else if id.is_some_and(|id| seen.visited.contains(&id)) {
+        return Some("[already visited]");
+    } else if id.is_some_and(|(device, _)| {
+        options.root_device.is_some_and(|root| device != root)
+    }) || (entry.kind == Kind::Directory
+           && crate::is_excluded_mount(&entry.source(), options)) {
+        return Some(MOUNT_POINT);
+    }
+    match &options.descends {
+        Some(descends) if entry.kind == Kind::Directory && !all => {
+            match descends(entry, Some(&rel_path.join(&entry.name))) {
+                Decision::Reject(reason) => Some(reason),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{fixture, options};
+
+    use std::fs;
+    use std::time;
+
+    // records what a walk hands it, and answers the visit of the entry at
+    // the given path, if there's one, with the given action
+    struct Recorder<'r> {
+        root: &'r path::Path,
+        act_at: Option<(&'r str, WalkAction)>,
+        events: vec::Vec<String>,
+    }
+
+    impl Visitor for Recorder<'_> {
+        fn visit(&mut self, node: &Node)
+            -> Result<WalkAction, TraversalError> {
+            let path = node.rel_path.join(&node.entry.name);
+            let path = path.to_string_lossy().into_owned();
+            let action = match self.act_at {
+                Some((at, action)) if at == path => action,
+                _ => WalkAction::Continue,
+            };
+            self.events.push(path);
+            Ok(action)
+        }
+
+        fn enter(&mut self, dir: &Source) {
+            if let Source::Path(path) = dir {
+                let path = path.strip_prefix(self.root).unwrap();
+                self.events.push(format!("enter {}", path.display()));
+            }
+        }
+
+        fn leave(&mut self) {
+            self.events.push(String::from("leave"));
+        }
+    }
+
+    // what a recorder records while walking root
+    fn recorded(root: &path::Path, act_at: Option<(&str, WalkAction)>)
+        -> vec::Vec<String> {
+        let mut recorder = Recorder {
+            root,
+            act_at,
+            events: vec::Vec::new(),
+        };
+        let result = walk(&Source::Path(root.to_path_buf()),
+                          &ignores::Rules::none(), &options(), &mut recorder);
+        assert!(result.is_ok());
+        recorder.events
+    }
+
+    #[test]
+    fn stops_right_after_the_visit_that_asks_to() {
+        let root = fixture("walk", &["a/", "a/x", "b/", "b/y", "c", "d/",
+                                     "d/z"]);
+        let cases: [(Option<&str>, &[&str]); 4] = [
+            (None,
+             &["enter ", "a", "enter a", "a/x", "leave", "b", "enter b",
+               "b/y", "leave", "c", "d", "enter d", "d/z", "leave",
+               "leave"]),
+            // the directories still being walked aren't left
+            (Some("b"),
+             &["enter ", "a", "enter a", "a/x", "leave", "b"]),
+            (Some("b/y"),
+             &["enter ", "a", "enter a", "a/x", "leave", "b", "enter b",
+               "b/y"]),
+            (Some("d/z"),
+             &["enter ", "a", "enter a", "a/x", "leave", "b", "enter b",
+               "b/y", "leave", "c", "d", "enter d", "d/z"]),
+        ];
+        for (stop_at, expected) in cases {
+            let act_at = stop_at.map(|path| (path, WalkAction::Stop));
+            assert_eq!(recorded(&root, act_at), expected);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn skips_the_children_of_the_visit_that_asks_to() {
+        let root = fixture("skip", &["a/", "a/x", "b/", "b/y", "b/z/",
+                                     "b/z/w", "c"]);
+        let cases: [(&str, &[&str]); 3] = [
+            ("b",
+             &["enter ", "a", "enter a", "a/x", "leave", "b", "c", "leave"]),
+            ("b/z",
+             &["enter ", "a", "enter a", "a/x", "leave", "b", "enter b",
+               "b/y", "b/z", "leave", "c", "leave"]),
+            // skipping the children of a file is the same as continuing
+            ("a/x",
+             &["enter ", "a", "enter a", "a/x", "leave", "b", "enter b",
+               "b/y", "b/z", "enter b/z", "b/z/w", "leave", "leave", "c",
+               "leave"]),
+        ];
+        for (skip_at, expected) in cases {
+            let act_at = Some((skip_at, WalkAction::SkipChildren));
+            assert_eq!(recorded(&root, act_at), expected);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn counts_what_it_walked() {
+        let root = fixture("stats", &["a/", "a/x", "a/y/", "b", "c/", "c/z"]);
+        let source = Source::Path(root.clone());
+        // a walk that's stopped only counts what was visited before it
+        let cases = [(None, 3, 3), (Some("a/y"), 2, 1)];
+        for (stop_at, directories, files) in cases {
+            let mut recorder = Recorder {
+                root: &root,
+                act_at: stop_at.map(|path| (path, WalkAction::Stop)),
+                events: vec::Vec::new(),
+            };
+            let stats = match walk(&source, &ignores::Rules::none(),
+                                   &options(), &mut recorder) {
+                Ok(value) => value,
+                Err(error) => panic!("{}", error),
+            };
+            assert_eq!((stats.directories, stats.files, stats.unreadable),
+                       (directories, files, 0), "{:?}", stop_at);
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // the entries a walk of root with options lists, each with the note
+    // after it if there's one
+    fn listed(root: &path::Path, options: &Options) -> vec::Vec<String> {
+        let items = TreeIter::over(Source::Path(root.to_path_buf()),
+                                  ignores::Rules::none(), options);
+        items.map(|item| {
+            let item = item.ok().unwrap();
+            let path = item.path().to_string_lossy().into_owned();
+            match item.note {
+                Some(note) => format!("{} {}", path, note),
+                None => path,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn hooks_hide_files_and_stop_descending() {
+        let root = fixture("hooks", &["src/", "src/a.rs", "src/b.txt",
+                                      "src/gen/", "src/gen/c.rs", "docs/",
+                                      "docs/d.txt", "f.rs", "keep.txt"]);
+        let shows = |entry: &Entry, _: Option<&path::Path>| {
+            let name = path::Path::new(&entry.name);
+            match name.extension() {
+                _ if name == path::Path::new("keep.txt") => Decision::Accept,
+                Some(extension) if extension == "rs" => Decision::Defer,
+                _ => Decision::Reject("it isn't rust"),
+            }
+        };
+        let descends = |_: &Entry, rel_path: Option<&path::Path>| {
+            if rel_path == Some(path::Path::new("src/gen")) {
+                Decision::Reject("[generated]")
+            } else {
+                Decision::Defer
+            }
+        };
+        let options = || {
+            let mut options = options();
+            options.filter.shows = Some(Box::new(shows));
+            options.descends = Some(Box::new(descends));
+            options
+        };
+        assert_eq!(listed(&root, &options()),
+                   ["docs", "f.rs", "keep.txt", "src", "src/a.rs",
+                    "src/gen [generated]"]);
+        // a file the hook accepts skips the rest of the filter where one it
+        // defers on still has to pass it, and the directories left empty
+        // are pruned
+        let mut changed = options();
+        changed.filter.changed_within = Some(time::SystemTime::now()
+                                             + time::Duration::from_secs(3600));
+        changed.prune = true;
+        assert_eq!(listed(&root, &changed), ["keep.txt"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn yields_entries_in_the_order_they_are_printed() {
+        let root = fixture("iter", &["b/", "b/c/", "b/c/d", "b/e", "a", "f/",
+                                     "g"]);
+        let options = options();
+        // the classic rendering, from nothing but what's yielded
+        let mut printed = String::from("root\n");
+        let items = TreeIter::over(Source::Path(root.clone()),
+                                  ignores::Rules::none(), &options);
+        for item in items {
+            let item = item.ok().unwrap();
+            let (last, above) = item.prefix.split_last().unwrap();
+            for last in above {
+                printed.push_str(if *last { "    " } else { "│   " });
+            }
+            printed.push_str(if *last { "└───" } else { "├───" });
+            printed.push_str(&item.entry.name.to_string_lossy());
+            printed.push('\n');
+        }
+        assert_eq!(printed, crate::tests::render(&root, &options));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // what a TreeIter over root yields, with b removed after a is yielded,
+    // by the paths of the entries and of the directories it couldn't read
+    fn yielded(root: &path::Path, options: &Options) -> vec::Vec<String> {
+        fs::create_dir_all(root.join("b")).unwrap();
+        let mut items = TreeIter::over(Source::Path(root.to_path_buf()),
+                                      ignores::Rules::none(), options);
+        let mut yielded = vec::Vec::new();
+        for item in items.by_ref() {
+            match item {
+                Ok(item) => {
+                    // b is listed by now, but it's gone once it's read
+                    if item.path() == path::Path::new("a") {
+                        fs::remove_dir(root.join("b")).unwrap();
+                    }
+                    yielded.push(item.path().to_string_lossy().into_owned());
+                },
+                Err(error) => {
+                    let path = error.path.strip_prefix(root).unwrap();
+                    yielded.push(format!("error {}", path.display()));
+                },
+            }
+        }
+        assert!(items.next().is_none());
+        yielded
+    }
+
+    #[test]
+    fn yields_unreadable_directories_as_errors() {
+        let root = fixture("iter-errors", &["a/", "a/x", "c"]);
+        assert_eq!(yielded(&root, &options()),
+                   ["a", "a/x", "b", "error b", "c"]);
+        // in strict mode the error is the last item
+        let mut strict = options();
+        strict.filter.strict = true;
+        assert_eq!(yielded(&root, &strict), ["a", "a/x", "b", "error b"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
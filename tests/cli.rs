@@ -0,0 +1,1344 @@
+/*
+MIT License
+
+Copyright (c) 2022 Jenna Fligor
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+// tests of the command line as a whole, each runs the built binary on a tree
+// made for it and looks at what it prints
+
+use std::env;
+use std::fs;
+use std::path;
+use std::process;
+use std::time;
+
+// a fresh directory named name, which is what tree prints as it's root,
+// holding paths, the ones ending in a / are directories and the rest empty
+// a fresh directory named name, which is what tree prints as it's root,
+// holding paths, the ones ending in a / are directories and the rest empty
+// files
+fn fixture(name: &str, paths: &[&str]) -> path::PathBuf {
+    let root = env::temp_dir()
+        .join(format!("tree-cli-{}", process::id()))
+        .join(name);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    for path in paths {
+        match path.strip_suffix('/') {
+            Some(dir) => fs::create_dir_all(root.join(dir)).unwrap(),
+            None => fs::write(root.join(path), "").unwrap(),
+        }
+    }
+    root
+}
+
+// sets the modification time of the file at path to seconds after the epoch
+fn set_mtime(path: &path::Path, seconds: u64) {
+    let time = time::UNIX_EPOCH + time::Duration::from_secs(seconds);
+    fs::File::options().write(true).open(path).unwrap()
+        .set_modified(time).unwrap();
+}
+
+// runs tree with args in dir, without color and at the standard width
+fn tree(dir: &path::Path, args: &[&str]) -> process::Output {
+    process::Command::new(env!("CARGO_BIN_EXE_tree"))
+        .args(args)
+        .current_dir(dir)
+        .env("COLUMNS", "80")
+        .output()
+        .unwrap()
+}
+
+// what tree prints to stdout with args in dir, which it has to succeed at
+fn printed(dir: &path::Path, args: &[&str]) -> String {
+    let output = tree(dir, args);
+    assert!(output.status.success(), "{}",
+            String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// what tree prints to stderr with args in dir, which it has to fail at
+fn rejected(dir: &path::Path, args: &[&str]) -> String {
+    let output = tree(dir, args);
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn formats_entries_with_a_template() {
+    let root = fixture("format", &["a", "d/", "d/b"]);
+    assert_eq!(printed(&root, &["-f", "--format", "{name} {type} {depth}"]),
+               "\
+format
+├───a file 1
+└───d directory 1
+    └───b file 2
+");
+    let single = fixture("format-single", &["a"]);
+    fs::write(single.join("a"), "12345").unwrap();
+    set_mtime(&single.join("a"), 365 * 86400 + 43200);
+    assert_eq!(printed(&single, &["-f", "--format",
+                                  "{path}\\t{size}\\t{size:h}"]),
+               "format-single\n└───a\t5\t5B\n");
+    assert_eq!(printed(&single, &["-f", "--format", "{mtime:%Y-%m}\\n{name}"]),
+               "format-single\n└───1971-01\na\n");
+    assert_eq!(rejected(&root, &["--format", "{name} {owner}"]),
+               "ERROR: unknown placeholder \"{owner}\" in format, valid \
+                placeholders are: {name}, {path}, {size}, {size:h}, {mtime}, \
+                {mtime:<strftime format>}, {perms}, {type}, {depth}\n");
+    fs::remove_dir_all(root).unwrap();
+    fs::remove_dir_all(single).unwrap();
+}
+
+#[test]
+fn expands_patterns_in_paths() {
+    let root = fixture("patterns", &["src/a/tests/", "src/a/tests/x",
+                                     "src/b/tests/", "src/c/", "src/c/tests"]);
+    // only directories match, so src/c/tests is left out
+    let expected = "tests\n└───x\n\ntests\n";
+    assert_eq!(printed(&root, &["-f", "src/*/tests"]), expected);
+    #[cfg(windows)]
+    assert_eq!(printed(&root, &["-f", "src\\*\\tests"]), expected);
+    assert_eq!(rejected(&root, &["src/*/docs"]),
+               "ERROR: \"src/*/docs\" No directories match this pattern\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+// bind mounts dir at mount_point, returning wether it could, which it can't
+// without root
+#[cfg(target_os = "linux")]
+fn bind(dir: &path::Path, mount_point: &path::Path) -> bool {
+    let status = process::Command::new("mount")
+        .arg("--bind").arg(dir).arg(mount_point)
+        .stderr(process::Stdio::null())
+        .status();
+    matches!(status, Ok(status) if status.success())
+}
+
+#[cfg(target_os = "linux")]
+fn unbind(mount_point: &path::Path) {
+    let status = process::Command::new("umount").arg(mount_point).status();
+    assert!(status.unwrap().success());
+}
+
+// mounts an empty tmpfs at mount_point, returning wether it could, which it
+// can't without root
+#[cfg(target_os = "linux")]
+fn mount_tmpfs(mount_point: &path::Path) -> bool {
+    let status = process::Command::new("mount")
+        .args(["-t", "tmpfs", "tmpfs"]).arg(mount_point)
+        .stderr(process::Stdio::null())
+        .status();
+    matches!(status, Ok(status) if status.success())
+}
+
+// without following links only a bind mount of an ancestor makes a loop, so
+// there's nothing to test without root
+#[cfg(target_os = "linux")]
+#[test]
+fn marks_bind_mounts_of_an_ancestor() {
+    let root = fixture("bind", &["a/b/up/", "c"]);
+    let up = root.join("a/b/up");
+    if bind(&root, &up) {
+        let output = tree(&root, &["-f"]);
+        unbind(&up);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+bind
+├───a
+│   └───b
+│       └───up  [recursive, not followed]
+└───c
+");
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+// root reads directories whatever their mode, so as root there's no error to
+// stop at and only the listing is checked
+#[cfg(unix)]
+#[test]
+fn stops_at_an_unreadable_directory_with_strict() {
+    use std::os::unix::fs::PermissionsExt;
+    let root = fixture("strict", &["a/b/", "a/b/c", "d"]);
+    let locked = root.join("a/b");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    let unreadable = fs::read_dir(&locked).is_err();
+    let strict = tree(&root, &["-f", "--strict"]);
+    let lenient = tree(&root, &["-f"]);
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    if unreadable {
+        assert!(!strict.status.success());
+        let error = String::from_utf8(strict.stderr).unwrap();
+        assert!(error.starts_with(&format!("ERROR: \"{}\" ",
+                                           locked.display())), "{}", error);
+        assert_eq!(String::from_utf8(lenient.stdout).unwrap(), "\
+strict
+├───a
+│   └───b
+│       └───<CONTENTS UNREADABLE>
+└───d
+");
+    } else {
+        assert!(strict.status.success());
+    }
+    assert!(lenient.status.success());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn marks_directories_it_does_not_recurse_into() {
+    let root = fixture("no-recurse", &["node_modules/x/", "node_modules/x/y",
+                                       "src/target/", "src/target/o",
+                                       "src/a.rs"]);
+    assert_eq!(printed(&root, &["-f", "--no-recurse",
+                                "node_modules|target"]), "\
+no-recurse
+├───node_modules  [...]
+└───src
+    ├───a.rs
+    └───target  [...]
+");
+    // -I hides what it matches where --no-recurse only leaves it closed
+    assert_eq!(printed(&root, &["-f", "-I", "node_modules",
+                                "--no-recurse", "target"]), "\
+no-recurse
+└───src
+    ├───a.rs
+    └───target  [...]
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+// names can't hold quotes on windows
+#[cfg(unix)]
+#[test]
+fn quotes_names() {
+    let root = fixture("quote", &["a dir/", "a dir/say \"hi\"", "back\\slash"]);
+    std::os::unix::fs::symlink("back\\slash", root.join("li nk")).unwrap();
+    // the target of a link stays outside of the quotes
+    assert_eq!(printed(&root, &["-f", "-Q", "--resolve-links"]), "\
+\"quote\"
+├───\"a dir\"
+│   └───\"say \\\"hi\\\"\"
+├───\"back\\\\slash\"
+└───\"li nk\" -> back\\slash
+");
+    assert_eq!(printed(&root, &["-f", "-Q", "--flat"]), "\
+\"quote\"
+\"a dir\"
+\"say \\\"hi\\\"\"
+\"back\\\\slash\"
+\"li nk\"
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+// a directory bound in a second place is only walked the first time
+#[cfg(target_os = "linux")]
+#[test]
+fn walks_a_directory_bound_twice_once() {
+    let root = fixture("bound", &["a/", "a/f", "c/a/"]);
+    let second = root.join("c/a");
+    if bind(&root.join("a"), &second) {
+        let output = tree(&root, &["-f"]);
+        unbind(&second);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+bound
+├───a
+│   └───f
+└───c
+    └───a  [already visited]
+");
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn marks_directories_with_a_slash() {
+    let root = fixture("slash", &["src/", "src/a"]);
+    assert_eq!(printed(&root, &["-f", "--slash"]), "\
+slash/
+└───src/
+    └───a
+");
+    // the root isn't given a second slash when typed with one
+    assert_eq!(printed(&root, &["-f", "--slash", "src/"]), "src/\n└───a\n");
+    assert_eq!(printed(&root, &["-f", "--slash", "-Q"]), "\
+\"slash\"/
+└───\"src\"/
+    └───\"a\"
+");
+    // names in json are left without it
+    assert!(!printed(&root, &["--slash", "--json"]).contains('/'));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn resolves_chains_of_links() {
+    let root = fixture("links", &["d/", "d/f"]);
+    let absolute = root.join("d");
+    for (link, target) in [("abs", absolute.to_str().unwrap()), ("rel", "d/f"),
+                           ("chain", "rel"), ("mid", "missing"),
+                           ("dangling", "mid"), ("x", "y"), ("y", "x")] {
+        std::os::unix::fs::symlink(target, root.join(link)).unwrap();
+    }
+    assert_eq!(printed(&root, &["-f", "--resolve-links"]), format!("\
+links
+├───abs -> {}
+├───chain -> rel -> d/f
+├───d
+│   └───f
+├───dangling -> mid -> missing  [broken]
+├───mid -> missing  [broken]
+├───rel -> d/f
+├───x -> y -> x  [loop]
+└───y -> x -> y  [loop]
+", absolute.display()));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn names_the_root_the_same_with_a_trailing_separator() {
+    let root = fixture("trailing", &["src/", "src/a"]);
+    let expected = "src\n└───a\n";
+    assert_eq!(printed(&root, &["-f", "src"]), expected);
+    assert_eq!(printed(&root, &["-f", "src/"]), expected);
+    assert_eq!(printed(&root, &["-f", "src//"]), expected);
+    #[cfg(windows)]
+    assert_eq!(printed(&root, &["-f", "src\\"]), expected);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn marks_sparse_files() {
+    let root = fixture("sparse", &[]);
+    fs::File::create(root.join("holes")).unwrap()
+        .set_len(1 << 20).unwrap();
+    fs::write(root.join("dense"), vec![1; 1 << 20]).unwrap();
+    assert_eq!(printed(&root, &["-f", "--summarize"]), "\
+sparse
+├───[1.0M]  dense
+└───[1.0M]  holes  [sparse]
+");
+    // it's only marked next to the size
+    assert_eq!(printed(&root, &["-f"]), "sparse\n├───dense\n└───holes\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn orders_entries_the_same_wherever_the_root_is() {
+    let paths = ["b", "a.b/", "a.b/z", "a-b", "A", "a/", "a/c", "a/B"];
+    let shallow = fixture("shallow", &paths);
+    let parent = fixture("deep", &["zz/a b/..x/"]);
+    let deep = parent.join("zz/a b/..x");
+    for path in paths {
+        match path.strip_suffix('/') {
+            Some(dir) => fs::create_dir(deep.join(dir)).unwrap(),
+            None => fs::write(deep.join(path), "").unwrap(),
+        }
+    }
+    // everything but the name of the root
+    let entries = |root: &path::Path| {
+        let printed = printed(root, &["-f"]);
+        printed.split_once('\n').unwrap().1.to_owned()
+    };
+    assert_eq!(entries(&shallow), entries(&deep));
+    fs::remove_dir_all(shallow).unwrap();
+    fs::remove_dir_all(parent).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn marks_entries_with_their_git_status() {
+    let root = fixture("status", &["src/", "src/a.rs", "src/b.rs", "staged"]);
+    let repository = git2::Repository::init(&root).unwrap();
+    let mut index = repository.index().unwrap();
+    for path in ["src/a.rs", "src/b.rs", "staged"] {
+        index.add_path(path::Path::new(path)).unwrap();
+    }
+    let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("a", "a@example.com").unwrap();
+    repository.commit(Some("HEAD"), &signature, &signature, "initial", &tree,
+                      &[]).unwrap();
+    fs::write(root.join("src/a.rs"), "changed").unwrap();
+    fs::write(root.join("staged"), "changed").unwrap();
+    index.add_path(path::Path::new("staged")).unwrap();
+    index.write().unwrap();
+    fs::write(root.join("new"), "").unwrap();
+    assert_eq!(printed(&root, &["-f", "--git-status", "-I", ".git"]), "\
+status
+├───[??]  new
+├───[  ]  src
+│   ├───[ M]  a.rs
+│   └───[  ]  b.rs
+└───[M ]  staged
+");
+    // outside of a repository nothing is annotated
+    let outside = fixture("no-status", &["a"]);
+    assert_eq!(printed(&outside, &["-f", "--git-status"]),
+               "no-status\n└───a\n");
+    fs::remove_dir_all(root).unwrap();
+    fs::remove_dir_all(outside).unwrap();
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn lists_only_tracked_files() {
+    let root = fixture("tracked", &["src/", "src/a.rs", "src/b.rs", "build/",
+                                    "build/out", "README"]);
+    let repository = git2::Repository::init(&root).unwrap();
+    let mut index = repository.index().unwrap();
+    for path in ["README", "src/a.rs"] {
+        index.add_path(path::Path::new(path)).unwrap();
+    }
+    index.write().unwrap();
+    assert_eq!(printed(&root, &["-f", "--git-tracked", "--prune"]), "\
+tracked
+├───README
+└───src
+    └───a.rs
+");
+    let outside = fixture("untracked", &[]);
+    assert_eq!(rejected(&outside, &["--git-tracked"]),
+               format!("ERROR: \"{}\" Is not inside a git repository\n",
+                       outside.canonicalize().unwrap().display()));
+    fs::remove_dir_all(root).unwrap();
+    fs::remove_dir_all(outside).unwrap();
+}
+
+#[test]
+fn matches_names_written_either_way_with_normalize() {
+    let composed = "caf\u{e9}";
+    let decomposed = "cafe\u{301}";
+    for (fixture_name, name, pattern) in
+        [("composed", composed, decomposed),
+         ("decomposed", decomposed, composed)] {
+        let root = fixture(fixture_name, &[name, "other"]);
+        assert_eq!(printed(&root, &["-f", "-P", pattern]),
+                   format!("{}\n", fixture_name));
+        // names are still shown the way they're written
+        assert_eq!(printed(&root, &["-f", "-P", pattern, "--normalize"]),
+                   format!("{}\n└───{}\n", fixture_name, name));
+        assert_eq!(printed(&root, &["-f", "-I", pattern, "--normalize"]),
+                   format!("{}\n└───other\n", fixture_name));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
+
+#[test]
+fn uses_the_timestamp_chosen_with_time() {
+    let root = fixture("time", &["a", "b"]);
+    let year = time::Duration::from_secs(366 * 86400);
+    for (file, accessed, modified) in [("a", 1, 4), ("b", 3, 2)] {
+        let times = fs::FileTimes::new()
+            .set_accessed(time::UNIX_EPOCH + year * accessed)
+            .set_modified(time::UNIX_EPOCH + year * modified);
+        fs::File::options().write(true).open(root.join(file)).unwrap()
+            .set_times(times).unwrap();
+    }
+    let format = ["-f", "--sort", "mtime", "--format", "{name} {mtime:%Y}"];
+    assert_eq!(printed(&root, &format),
+               "time\n├───b 1972\n└───a 1974\n");
+    assert_eq!(printed(&root, &[&format[..], &["--time", "atime"]].concat()),
+               "time\n├───a 1971\n└───b 1973\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn explains_why_entries_are_skipped() {
+    let root = fixture("verbose", &["a.log", "b", "d/", "d/e/", "d/e/f"]);
+    fs::write(root.join(".ignore"), "b\n").unwrap();
+    let output = tree(&root, &["-f", "-v", "-I", "*.log", "-L", "2",
+                               "--respect-ignore-files"]);
+    let logged = String::from_utf8(output.stderr).unwrap();
+    let root = root.canonicalize().unwrap();
+    for reason in [
+        format!("\"{}\" skipped, it matches the -I pattern \"*.log\"",
+                root.join("a.log").display()),
+        format!("\"{}\" skipped, it matches the rule \"b\" in \"{}\"",
+                root.join("b").display(), root.join(".ignore").display()),
+        format!("\"{}\" not descended into, it's at the depth limit",
+                root.join("d").join("e").display()),
+    ] {
+        assert!(logged.contains(&reason), "{}", logged);
+    }
+    // nothing is logged without -v
+    assert!(tree(&root, &["-f", "-I", "*.log"]).stderr.is_empty());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn draws_collapsed_chains_as_one_entry() {
+    let root = fixture("collapse", &["a/b/c/", "a/b/c/x", "e/f/", "e/f/y",
+                                     "g/h/i/", "g/h/i/z", "g/j/", "k"]);
+    assert_eq!(printed(&root, &["-f", "--collapse"]), "\
+collapse
+├───a/b/c
+│   └───x
+├───e/f
+│   └───y
+├───g
+│   ├───h/i
+│   │   └───z
+│   └───j
+└───k
+");
+    // without -f the files don't keep a directory from being in a chain
+    assert_eq!(printed(&root, &["--collapse"]), "\
+collapse
+├───a/b/c
+├───e/f
+└───g
+    ├───h/i
+    └───j
+");
+    assert_eq!(printed(&root, &["-f", "--collapse", "--post-order"]),
+               "    ┌───x
+┌───a/b/c
+│   ┌───y
+├───e/f
+│       ┌───z
+│   ┌───h/i
+│   ├───j
+├───g
+├───k
+collapse
+");
+    assert_eq!(printed(&root, &["-f", "--collapse", "-P", "y", "--prune"]),
+               "collapse\n└───e/f\n    └───y\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn writes_the_tree_that_would_be_printed_as_json() {
+    let root = fixture("json", &["a/", "a/x.rs", "a/y.txt", "b/", "b/c/",
+                                 "d"]);
+    let document = root.with_extension("json");
+    let load = ["-f", "--load", document.to_str().unwrap()];
+    // loading with -f alone shows what the options left in the tree
+    for args in [&["-f"][..], &[], &["-f", "--prune"]] {
+        fs::write(&document, printed(&root, &[args, &["--json"]].concat()))
+            .unwrap();
+        assert_eq!(printed(&root, &load), printed(&root, args));
+    }
+    // the tree ends at the depth limit, so there's nothing beneath it to
+    // show placeholders for
+    fs::write(&document, printed(&root, &["-f", "-L", "1", "--json"]))
+        .unwrap();
+    assert_eq!(printed(&root, &load), "json\n├───a\n├───b\n└───d\n");
+    fs::remove_dir_all(root).unwrap();
+    fs::remove_file(document).unwrap();
+}
+
+#[test]
+fn sizes_each_directory_by_its_own_files() {
+    let root = fixture("shallow", &["d/", "d/e/"]);
+    for (file, contents) in [("a", "12"), ("d/b", "1234"), ("d/c", "123"),
+                             ("d/e/f", "1")] {
+        fs::write(root.join(file), contents).unwrap();
+    }
+    // the files are counted even when they aren't listed
+    assert_eq!(printed(&root, &["--shallow-size"]), "\
+shallow  [2B in 1 file, shallow]
+└───d  [7B in 2 files, shallow]
+    └───e  [1B in 1 file, shallow]
+");
+    assert!(!rejected(&root, &["--shallow-size", "--du"]).is_empty());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn adds_timestamps_permissions_and_links_to_json() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = fixture("meta", &["a"]);
+    set_mtime(&root.join("a"), 1_000_000_000);
+    fs::set_permissions(root.join("a"), fs::Permissions::from_mode(0o4640))
+        .unwrap();
+    std::os::unix::fs::symlink("a", root.join("l")).unwrap();
+    let output = process::Command::new(env!("CARGO_BIN_EXE_tree"))
+        .args(["-f", "--json-meta"])
+        .current_dir(&root)
+        .env("TZ", "UTC")
+        .output()
+        .unwrap();
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .unwrap();
+    let children = &document["root"]["children"];
+    assert_eq!(children[0]["mtime"], 1_000_000_000);
+    assert_eq!(children[0]["modified"], "2001-09-09T01:46:40+00:00");
+    assert_eq!(children[0]["permissions"], "4640");
+    assert_eq!(children[0]["is_symlink"], false);
+    assert_eq!(children[1]["type"], "symlink");
+    assert_eq!(children[1]["is_symlink"], true);
+    // plain --json leaves the fields out
+    let plain = printed(&root, &["-f", "--json"]);
+    assert!(!plain.contains("modified") && !plain.contains("permissions"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn replaces_single_connectors() {
+    let root = fixture("connectors", &["a", "d/", "d/b", "d/e/", "d/e/f"]);
+    assert_eq!(printed(&root, &["-f", "--conn-last", "`-- ",
+                                "--conn-tee", "|-- ", "--conn-space", "    ",
+                                "--conn-vert", "|   "]), "\
+connectors
+|-- a
+`-- d
+    |-- b
+    `-- e
+        `-- f
+");
+    // the others are left as the style draws them
+    assert_eq!(printed(&root, &["-f", "-a", "--conn-last", "\\-"]), "\
+connectors
++---a
+\\-d
+    +---b
+    \\-e
+        \\-f
+");
+    assert!(!rejected(&root, &["--conn-tee", ""]).is_empty());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn stays_on_the_filesystem_of_the_root() {
+    let root = fixture("one-filesystem", &["a", "b/", "b/c", "m/"]);
+    let mount_point = root.join("m");
+    if mount_tmpfs(&mount_point) {
+        fs::write(mount_point.join("d"), "").unwrap();
+        let crossed = printed(&root, &["-f"]);
+        let output = tree(&root, &["-f", "-x"]);
+        let unreported = tree(&root, &["-f", "-x", "--no-report"]);
+        unbind(&mount_point);
+        assert!(crossed.contains("d\n"));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+one-filesystem
+├───a
+├───b
+│   └───c
+└───m  [mount point, not crossed]
+
+1 mount point not crossed:
+    m
+");
+        assert!(!String::from_utf8(unreported.stdout).unwrap()
+                .contains("not crossed:"));
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn streams_the_same_entries_unsorted() {
+    let root = fixture("stream", &["a/", "a/b/", "a/b/c", "d/", "d/e", "d/f",
+                                   "g"]);
+    // the paths printed with args, without the connectors before them
+    let paths = |args: &[&str]| -> Vec<String> {
+        printed(&root, args).lines()
+            .map(|line| line.rsplit('─').next().unwrap().to_string())
+            .collect()
+    };
+    let sorted = |args: &[&str]| {
+        let mut paths = paths(args);
+        paths.sort();
+        paths
+    };
+    let format = ["-f", "--format", "{path}"];
+    let streamed = [&format[..], &["--stream"]].concat();
+    assert_eq!(sorted(&streamed), sorted(&format));
+    // but each directory still comes right before what's in it
+    let paths = paths(&streamed);
+    for (i, path) in paths.iter().enumerate().skip(1) {
+        if let Some((parent, _)) = path.rsplit_once('/') {
+            let start = paths.iter().position(|path| path == parent).unwrap();
+            assert!(paths[start..i].iter()
+                .all(|between| between.starts_with(parent)), "{:?}", paths);
+        }
+    }
+    // ordering the entries needs whole directories
+    for conflicting in ["--prune", "-t", "--summarize"] {
+        assert!(!rejected(&root, &["--stream", conflicting]).is_empty(),
+                "{}", conflicting);
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+// what tree prints with args in dir when document is piped to it
+fn piped(dir: &path::Path, args: &[&str], document: &str) -> process::Output {
+    use std::io::Write;
+
+    let mut child = process::Command::new(env!("CARGO_BIN_EXE_tree"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(document.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn loads_gnu_documents_from_stdin_and_points_at_errors() {
+    let root = fixture("documents", &[]);
+    let document = r#"[
+        {"type":"directory","name":".","contents":[
+            {"type":"file","name":"a","size":3},
+            {"type":"link","name":"l","target":"a"},
+            {"type":"directory","name":"d","contents":[
+                {"error":"opening dir"}
+            ]}
+        ]},
+        {"type":"report","directories":1,"files":2}
+    ]"#;
+    let output = piped(&root, &["-f", "--load", "-"], document);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+.
+├───a
+├───d
+│   └───<CONTENTS UNREADABLE>
+└───l
+");
+    // --from-json is the same option, and a document of the wrong shape is
+    // reported where it goes wrong
+    let malformed = "{\"version\": 1,\n \"root\": {\"name\": 3}}";
+    let output = piped(&root, &["--from-json", "-"], malformed);
+    assert!(!output.status.success());
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert!(error.trim_end().ends_with("at line 2 column 19"), "{}", error);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn prints_flat_lines_without_placeholders() {
+    let root = fixture("flat", &["a", "d/", "d/b", "d/e/", "d/e/f"]);
+    assert_eq!(printed(&root, &["-f", "--flat"]), "flat\na\nd\nb\ne\nf\n");
+    assert_eq!(printed(&root, &["-L", "1", "--flat", "--format", "{path}"]),
+               "flat\nd\n");
+    assert_eq!(printed(&root, &["-L", "1"]), "flat\n└───d\n    └───…\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn shows_what_differs_from_a_snapshot() {
+    let root = fixture("drift", &["d/", "d/x", "gone", "grow", "keep", "kind",
+                                  "skip.log"]);
+    let snapshot = root.with_extension("json");
+    fs::write(&snapshot, printed(&root, &["-f", "--json"])).unwrap();
+    let compare = ["-f", "--diff-snapshot", snapshot.to_str().unwrap(),
+                   "-I", "*.log"];
+    // entries that weren't compared aren't a difference
+    assert_eq!(printed(&root, &compare),
+               "drift\n└───? skip.log  [not compared]\n");
+
+    fs::remove_file(root.join("gone")).unwrap();
+    fs::write(root.join("grow"), "more").unwrap();
+    fs::remove_file(root.join("kind")).unwrap();
+    fs::create_dir(root.join("kind")).unwrap();
+    for added in ["d/y", "new"] {
+        fs::write(root.join(added), "").unwrap();
+    }
+    let output = tree(&root, &compare);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+drift
+├───d
+│   └───+ y
+├───- gone
+├───~ grow
+├───~ kind
+├───+ new
+└───? skip.log  [not compared]
+");
+    fs::remove_dir_all(root).unwrap();
+    fs::remove_file(snapshot).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn follows_links_up_to_the_limit() {
+    let root = fixture("follow", &["t1/", "t1/a", "t2/", "t2/b"]);
+    for (link, target) in [("l1", "t1"), ("l2", "t2")] {
+        std::os::unix::fs::symlink(target, root.join(link)).unwrap();
+    }
+    assert_eq!(printed(&root, &["-f", "-l", "--max-symlinks", "1"]), "\
+follow
+├───l1
+│   └───a
+├───l2  [symlink limit reached]
+├───t1  [already visited]
+└───t2
+    └───b
+");
+    assert!(printed(&root, &["-f", "-l", "--count"]).contains("followed: 2\n"));
+    assert_eq!(printed(&root, &["-f"]), "\
+follow
+├───l1
+├───l2
+├───t1
+│   └───a
+└───t2
+    └───b
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn marks_links_back_to_an_ancestor() {
+    let root = fixture("ancestor", &["a/b/", "c/"]);
+    std::os::unix::fs::symlink("../..", root.join("a/b/up")).unwrap();
+    std::os::unix::fs::symlink("..", root.join("c/back")).unwrap();
+    assert_eq!(printed(&root, &["-l"]), "\
+ancestor
+├───a
+│   └───b
+│       └───up  [recursive, not followed]
+└───c
+    └───back  [recursive, not followed]
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+// a link followed with -l reaches a directory the way a second bind mount of
+// it would
+#[cfg(unix)]
+#[test]
+fn walks_a_directory_reached_twice_once() {
+    let root = fixture("visited", &["a/", "a/f", "c/"]);
+    std::os::unix::fs::symlink("../a", root.join("c/a")).unwrap();
+    assert_eq!(printed(&root, &["-f", "-l"]), "\
+visited
+├───a
+│   └───f
+└───c
+    └───a  [already visited]
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+// each capture of gnu tree in the fixture file, with the arguments it was
+// printed with
+fn captures(file: &str) -> Vec<(Vec<String>, String)> {
+    let mut captures: Vec<(Vec<String>, String)> = Vec::new();
+    for line in file.lines().filter(|line| !line.starts_with('#')) {
+        match line.strip_prefix("$ tree") {
+            Some(args) => {
+                let args = args.split_whitespace().map(String::from);
+                captures.push((args.collect(), String::new()));
+            },
+            None => {
+                let (_, output) = captures.last_mut().unwrap();
+                output.push_str(line);
+                output.push('\n');
+            },
+        }
+    }
+    captures
+}
+
+#[cfg(unix)]
+#[test]
+fn prints_what_gnu_tree_prints() {
+    let root = fixture("gnu", &[".hidden", "a", "d/", "d/b", "d/e/"]);
+    std::os::unix::fs::symlink("a", root.join("l")).unwrap();
+    let file = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"),
+                                          "/tests/fixtures/gnu-tree.txt"))
+        .unwrap();
+    let captures = captures(&file);
+    assert_eq!(captures.len(), 6);
+    for (args, expected) in captures {
+        let args: Vec<&str> = ["--gnu"].into_iter()
+            .chain(args.iter().map(String::as_str))
+            .collect();
+        assert_eq!(printed(&root, &args), expected, "{:?}", args);
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn rejects_a_timeout_too_long_for_a_duration() {
+    let root = fixture("timeout", &["a"]);
+    for timeout in ["1e30", "0", "-1", "inf", "NaN", "soon"] {
+        assert_eq!(rejected(&root, &[&format!("--timeout={}", timeout)]),
+                   format!("ERROR: \"{}\" Is not a valid number of seconds, \
+                            expected a positive number\n", timeout));
+    }
+    assert_eq!(printed(&root, &["-f", "--timeout", "5"]), "timeout\n└───a\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn stops_at_the_first_match() {
+    let root = fixture("first", &["a/", "a/b/", "a/b/x.rs", "c/", "c/y.rs",
+                                  "z.rs"]);
+    assert_eq!(printed(&root, &["-P", "*.rs", "--first-only"]), "\
+first
+└───a
+    └───b
+        └───x.rs
+");
+    let output = tree(&root, &["-P", "*.md", "--first-only"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn skips_mounts_of_excluded_filesystem_types() {
+    let root = fixture("fstype", &["a", "m/"]);
+    let mount_point = root.join("m");
+    if mount_tmpfs(&mount_point) {
+        fs::write(mount_point.join("d"), "").unwrap();
+        let excluded = tree(&root, &["-f", "--exclude-fstype", "tmpfs"]);
+        let other = tree(&root, &["-f", "--exclude-fstype", "ext4,xfs"]);
+        unbind(&mount_point);
+        assert_eq!(String::from_utf8(excluded.stdout).unwrap(), "\
+fstype
+├───a
+└───m  [mount point, not crossed]
+
+1 mount point not crossed:
+    m
+");
+        assert!(String::from_utf8(other.stdout).unwrap().ends_with("d\n"));
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn counts_each_directory_of_a_collapsed_chain() {
+    let root = fixture("chain", &["a/b/c/", "a/b/c/x", "g/h/", "k"]);
+    let count = printed(&root, &["-f", "--count"]);
+    assert!(count.starts_with("directories: 5\nfiles: 2\n"), "{}", count);
+    assert_eq!(printed(&root, &["-f", "--collapse-dirs", "--count"]), count);
+    // the structured outputs keep each directory of a chain
+    let json = printed(&root, &["-f", "--collapse", "--json"]);
+    assert!(json.contains("\"name\":\"b\""), "{}", json);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn groups_files_by_extension() {
+    let root = fixture("extensions", &["main.c", "util.h", "main.h", "README",
+                                       "util.c", "src/", "src/lib.rs"]);
+    assert_eq!(printed(&root, &["-f", "--sort", "extension"]), "\
+extensions
+├───README
+├───src
+│   └───lib.rs
+├───main.c
+├───util.c
+├───main.h
+└───util.h
+");
+    assert_eq!(printed(&root, &["-f", "--sort", "ext", "--dirsfirst"]), "\
+extensions
+├───src
+│   └───lib.rs
+├───README
+├───main.c
+├───util.c
+├───main.h
+└───util.h
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn tells_empty_directories_from_filtered_ones() {
+    let root = fixture("empty", &["empty/", "full/", "full/b", "hidden/",
+                                  "hidden/a.log"]);
+    assert_eq!(printed(&root, &["-f", "--show-empty", "-I", "*.log"]), "\
+empty
+├───empty  [empty]
+├───full
+│   └───b
+└───hidden  [contents hidden]
+
+1 empty directory
+");
+    // without -f the files of full are hidden too
+    assert_eq!(printed(&root, &["--show-empty", "--no-report"]), "\
+empty
+├───empty  [empty]
+├───full  [contents hidden]
+└───hidden  [contents hidden]
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn counts_entries_and_their_size() {
+    let root = fixture("count", &["d/", "d/e/"]);
+    for (file, contents) in [("a", "12"), ("d/b", "1234"), ("d/e/f", "1")] {
+        fs::write(root.join(file), contents).unwrap();
+    }
+    let counted = "\
+directories: 2
+files: 3
+symlinks: 0
+other: 0
+size: 7
+";
+    assert_eq!(printed(&root, &["-f", "--count"]), counted);
+    assert_eq!(printed(&root, &["-f", "--count-only"]), counted);
+    // the filters apply, and without -f no files are counted
+    assert!(printed(&root, &["-f", "--count", "-I", "b"])
+        .ends_with("files: 2\nsymlinks: 0\nother: 0\nsize: 3\n"));
+    assert!(printed(&root, &["--count"]).ends_with("files: 0\nsymlinks: 0\n\
+                                                    other: 0\nsize: 0\n"));
+    assert_eq!(printed(&root, &["-f", "--count", "--porcelain"]),
+               "directories=2 files=3 symlinks=0 other=0 size=7\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn groups_files_with_the_same_contents() {
+    let root = fixture("duplicates", &["d/"]);
+    for (file, contents) in [("a", "hello\n"), ("c", "hello\n"),
+                             ("d/b", "hello\n"), ("o", "other\n"),
+                             ("p", "unique\n")] {
+        fs::write(root.join(file), contents).unwrap();
+    }
+    fs::hard_link(root.join("c"), root.join("hl")).unwrap();
+    // o has the size of the copies but not their contents
+    assert_eq!(printed(&root, &["-f", "--duplicates"]), "\
+5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03  [12B wasted]
+    a
+    c
+    d/b
+
+1 group of duplicates, 12B wasted
+
+hardlinks of the same file
+    c
+    hl
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn walks_a_linked_root_as_its_target() {
+    let root = fixture("linked-root", &["target/", "target/a", "target/d/",
+                                        "target/d/b"]);
+    std::os::unix::fs::symlink("target", root.join("link")).unwrap();
+    for args in [&["-f"][..], &["-f", "--json"], &["--count"]] {
+        let linked = [args, &["link"]].concat();
+        let target = [args, &["target"]].concat();
+        assert_eq!(printed(&root, &linked), printed(&root, &target),
+                   "{:?}", args);
+    }
+    assert!(printed(&root, &["-f", "link"]).ends_with("└───b\n"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn draws_bars_in_proportion_to_sizes() {
+    let root = fixture("bars", &["d/"]);
+    let sizes = [("big", 1000), ("quarter", 250), ("tiny", 1), ("empty", 0),
+                 ("d/x", 40), ("d/e", 0)];
+    for (file, size) in sizes {
+        fs::write(root.join(file), vec![0; size]).unwrap();
+    }
+    assert_eq!(printed(&root, &["-f", "--bars"]), "\
+bars
+├───big  ▇▇▇▇▇▇▇▇▇▇
+├───d
+│   ├───e
+│   └───x  ▇▇▇▇▇▇▇▇▇▇
+├───empty
+├───quarter  ▇▇▇▁▁▁▁▁▁▁
+└───tiny  ▇▁▁▁▁▁▁▁▁▁
+");
+    assert_eq!(printed(&root, &["-f", "--bars-global", "--ascii"]), "\
+bars
++---big  ##########
++---d
+|   +---e
+|   \\---x  #.........
++---empty
++---quarter  ###.......
+\\---tiny  #.........
+");
+    assert_eq!(printed(&root, &["-f", "--bars", "--du"]), "\
+bars  [1.3K in 6 files]
+├───big  ▇▇▇▇▇▇▇▇▇▇
+├───d  [40B in 2 files]  ▇▁▁▁▁▁▁▁▁▁
+│   ├───e
+│   └───x  ▇▇▇▇▇▇▇▇▇▇
+├───empty
+├───quarter  ▇▇▇▁▁▁▁▁▁▁
+└───tiny  ▇▁▁▁▁▁▁▁▁▁
+");
+    // bars are shortened to fit in the terminal, and left out when fewer
+    // than three cells would
+    let output = process::Command::new(env!("CARGO_BIN_EXE_tree"))
+        .args(["-f", "--bars"])
+        .current_dir(&root)
+        .env("COLUMNS", "14")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+bars
+├───big  ▇▇▇▇▇
+├───d
+│   ├───e
+│   └───x  ▇▇▇
+├───empty
+├───quarter
+└───tiny  ▇▁▁▁
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn draws_each_style() {
+    let root = fixture("styles", &["a", "b/", "b/c/", "b/c/d", "b/e"]);
+    let cases = [
+        ("plain", "\
+styles
+├───a
+└───b
+    ├───c
+    │   └───d
+    └───e
+", "\
+┌───a
+│       ┌───d
+│   ┌───c
+│   ├───e
+├───b
+styles
+"),
+        ("rounded", "\
+styles
+├───a
+╰───b
+    ├───c
+    │   ╰───d
+    ╰───e
+", "\
+╭───a
+│       ╭───d
+│   ╭───c
+│   ├───e
+├───b
+styles
+"),
+        ("double", "\
+styles
+╠═══a
+╚═══b
+    ╠═══c
+    ║   ╚═══d
+    ╚═══e
+", "\
+╔═══a
+║       ╔═══d
+║   ╔═══c
+║   ╠═══e
+╠═══b
+styles
+"),
+        ("heavy", "\
+styles
+┣━━━a
+┗━━━b
+    ┣━━━c
+    ┃   ┗━━━d
+    ┗━━━e
+", "\
+┏━━━a
+┃       ┏━━━d
+┃   ┏━━━c
+┃   ┣━━━e
+┣━━━b
+styles
+"),
+    ];
+    for (style, tree, post_order) in cases {
+        assert_eq!(printed(&root, &["-f", "--style", style]), tree);
+        assert_eq!(printed(&root, &["-f", "--style", style, "--post-order"]),
+                   post_order);
+    }
+    // the ASCII connectors have no lines to restyle
+    assert_eq!(printed(&root, &["-f", "--style", "heavy", "--ascii"]), "\
+styles
++---a
+\\---b
+    +---c
+    |   \\---d
+    \\---e
+");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn shows_only_the_given_types() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = fixture("types", &["d/", "d/e/", "d/g", "f", "x"]);
+    fs::set_permissions(root.join("x"), fs::Permissions::from_mode(0o755))
+        .unwrap();
+    std::os::unix::fs::symlink("f", root.join("l")).unwrap();
+    let cases = [
+        ("f", "├───d\n│   ├───e\n│   └───g\n├───f\n└───x\n"),
+        ("d", "└───d\n    └───e\n"),
+        ("l", "├───d\n│   └───e\n└───l\n"),
+        ("x", "├───d\n│   └───e\n└───x\n"),
+    ];
+    for (kind, expected) in cases {
+        assert_eq!(printed(&root, &["--type", kind]),
+                   format!("types\n{}", expected), "{}", kind);
+    }
+    assert_eq!(printed(&root, &["--type", "x", "--type", "l", "--prune"]),
+               "types\n├───l\n└───x\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn excludes_exact_paths() {
+    let root = fixture("exclude", &["gen/", "gen/b", "src/", "src/c",
+                                    "src/gen/", "src/gen/a"]);
+    let without_src_gen = "exclude\n├───gen\n│   └───b\n└───src\n    └───c\n";
+    for path in ["src/gen", "./src/../src/gen/"] {
+        assert_eq!(printed(&root, &["-f", "--exclude", path]),
+                   without_src_gen, "{}", path);
+    }
+    let absolute = root.canonicalize().unwrap().join("gen");
+    assert_eq!(printed(&root, &["-f", "--exclude", absolute.to_str().unwrap()]),
+               "exclude\n└───src\n    ├───c\n    └───gen\n        └───a\n");
+    assert_eq!(rejected(&root, &["--exclude", "../x"]),
+               "ERROR: \"../x\" Is outside of the tree\n");
+    let output = tree(&root, &["-f", "--exclude", "nope"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .starts_with("WARNING: \"nope\" doesn't exist in"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn ends_quietly_when_stdout_is_closed() {
+    use std::io::Read;
+
+    // more than a pipe holds, so the tree is still being written when the
+    // reader goes away
+    let names: Vec<String> = (0..4000)
+        .map(|i| format!("a-file-with-a-long-enough-name-{}", i))
+        .collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let root = fixture("closed", &names);
+    let mut child = process::Command::new(env!("CARGO_BIN_EXE_tree"))
+        .arg("-f")
+        .current_dir(&root)
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut start = [0; 6];
+    stdout.read_exact(&mut start).unwrap();
+    assert_eq!(&start, b"closed");
+    drop(stdout);
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+    assert!(output.status.success());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn shows_what_changed_since_a_reference_file() {
+    let root = fixture("changed", &["d/", "d/old", "d/new", "e/", "e/old",
+                                    "reference"]);
+    for old in ["d/old", "e/old"] {
+        set_mtime(&root.join(old), 1_000_000);
+    }
+    set_mtime(&root.join("reference"), 2_000_000);
+    assert_eq!(printed(&root, &["--changed-since", "reference"]), "\
+changed
+└───d
+    └───new
+");
+    let output = tree(&root, &["--changed-since", "reference", "-v"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("skipped, it didn't change since the --changed-since \
+                   reference"));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn merges_several_roots_into_one_tree() {
+    let root = fixture("merge", &["one/", "one/src/", "one/src/a", "one/x",
+                                  "two/", "two/docs/", "two/src/", "two/src/b",
+                                  "two/x", "three/", "three/one/",
+                                  "three/one/q"]);
+    assert_eq!(printed(&root, &["-f", "--merge", "two", "one"]), "\
+<multiple>
+├───one
+│   ├───src
+│   │   └───a
+│   └───x
+└───two
+    ├───docs
+    ├───src
+    │   └───b
+    └───x
+");
+    // the summaries cover every root, which is only walked once
+    assert!(printed(&root, &["-f", "--merge", "--count", "one", "two", "one"])
+        .starts_with("directories: 5\nfiles: 4\n"));
+    // roots with the same name are told apart by the path they were given as
+    assert_eq!(printed(&root, &["--merge", "one", "three/one", "-L", "1"]),
+               "<multiple>\n├───one\n│   └───…\n└───three/one\n");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn replaces_the_rows_of_an_earlier_export() {
+    let root = fixture("export", &["a", "b"]);
+    let file = root.with_extension("db");
+    printed(&root, &["-f", "--sqlite", file.to_str().unwrap()]);
+    fs::remove_file(root.join("b")).unwrap();
+    printed(&root, &["-f", "--sqlite", file.to_str().unwrap()]);
+    let connection = rusqlite::Connection::open(&file).unwrap();
+    let mut statement = connection
+        .prepare("SELECT path FROM entries ORDER BY path").unwrap();
+    let paths: Vec<String> = statement.query_map([], |row| row.get(0))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(paths, [".", "a"]);
+    fs::remove_dir_all(root).unwrap();
+    fs::remove_file(file).unwrap();
+}